@@ -0,0 +1,183 @@
+use crate::keyfile::KeyFile;
+use crate::overrides::DESKTOP_ENTRY;
+use crate::xdg;
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const GNOME_ENABLED: &str = "X-GNOME-Autostart-enabled";
+
+/// An entry from the XDG autostart directories. User entries shadow system
+/// entries with the same file name.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutostartEntry {
+    /// File name, e.g. `nm-applet.desktop`.
+    pub id: String,
+    pub name: String,
+    pub exec: Option<String>,
+    /// The file that takes effect.
+    pub path: PathBuf,
+    /// Whether the effective file lives in `$XDG_CONFIG_HOME/autostart`.
+    pub user: bool,
+    /// Whether a system-wide entry with this id exists.
+    pub system: bool,
+    /// `Hidden` is not set and `X-GNOME-Autostart-enabled` is not false.
+    pub enabled: bool,
+    /// Whether `OnlyShowIn`/`NotShowIn` let the entry run in the current
+    /// desktop.
+    pub shown_in_current_desktop: bool,
+}
+
+impl AutostartEntry {
+    fn load(id: &str, path: &Path, user: bool, system: bool) -> Option<Self> {
+        let file = KeyFile::load(path).ok()?;
+        let get = |key| file.get(DESKTOP_ENTRY, key);
+
+        let hidden = get("Hidden").as_deref() == Some("true");
+        let gnome_disabled = get(GNOME_ENABLED).as_deref() == Some("false");
+
+        Some(Self {
+            id: id.to_string(),
+            name: get("Name").unwrap_or_else(|| id.to_string()),
+            exec: get("Exec"),
+            path: path.to_path_buf(),
+            user,
+            system,
+            enabled: !hidden && !gnome_disabled,
+            shown_in_current_desktop: shown_in(
+                file.get_list(DESKTOP_ENTRY, "OnlyShowIn"),
+                file.get_list(DESKTOP_ENTRY, "NotShowIn"),
+                &xdg::current_desktops(),
+            ),
+        })
+    }
+
+    /// Whether the session would start this entry.
+    pub fn active(&self) -> bool {
+        self.enabled && self.shown_in_current_desktop
+    }
+}
+
+fn shown_in(only: Option<Vec<String>>, not: Option<Vec<String>>, desktops: &[String]) -> bool {
+    let matches = |list: Vec<String>| list.iter().any(|d| desktops.contains(&d.to_lowercase()));
+    if let Some(only) = only {
+        return matches(only);
+    }
+    !not.is_some_and(matches)
+}
+
+pub fn user_dir() -> PathBuf {
+    xdg::config_home().join("autostart")
+}
+
+pub fn system_dirs() -> Vec<PathBuf> {
+    xdg::config_dirs()
+        .into_iter()
+        .map(|d| d.join("autostart"))
+        .collect()
+}
+
+/// The desktop files directly in an autostart directory; unlike
+/// application directories, these are not searched recursively.
+fn desktop_files(dir: &Path) -> Vec<(String, PathBuf)> {
+    xdg::desktop_files(dir)
+        .into_iter()
+        .filter(|(_, path)| path.parent() == Some(dir))
+        .collect()
+}
+
+fn system_path(id: &str) -> Option<PathBuf> {
+    system_dirs()
+        .into_iter()
+        .map(|d| d.join(id))
+        .find(|p| p.exists())
+}
+
+/// Lists every autostart entry, most important definition first.
+pub fn list() -> Vec<AutostartEntry> {
+    let mut effective: IndexMap<String, (PathBuf, bool)> = IndexMap::new();
+    for (id, path) in desktop_files(&user_dir()) {
+        effective.insert(id, (path, true));
+    }
+    for dir in system_dirs() {
+        for (id, path) in desktop_files(&dir) {
+            effective.entry(id).or_insert((path, false));
+        }
+    }
+
+    let mut entries: Vec<AutostartEntry> = effective
+        .iter()
+        .filter_map(|(id, (path, user))| {
+            let system = !user || system_path(id).is_some();
+            AutostartEntry::load(id, path, *user, system)
+        })
+        .collect();
+    entries.sort_by_key(|e| e.name.to_lowercase());
+    entries
+}
+
+pub fn get(id: &str) -> Option<AutostartEntry> {
+    let user = user_dir().join(id);
+    if user.exists() {
+        return AutostartEntry::load(id, &user, true, system_path(id).is_some());
+    }
+    AutostartEntry::load(id, &system_path(id)?, false, true)
+}
+
+/// Enables or disables `id`. Disabling a system entry writes a user copy
+/// with `Hidden=true`, as the Autostart spec prescribes; system files are
+/// never modified.
+pub fn set_enabled(id: &str, enabled: bool) -> io::Result<()> {
+    let user = user_dir().join(id);
+    let source = if user.exists() {
+        user.clone()
+    } else {
+        system_path(id).ok_or_else(|| not_found(id))?
+    };
+
+    let mut file = KeyFile::load(&source)?;
+    if enabled {
+        file.remove(DESKTOP_ENTRY, "Hidden");
+        if file.get(DESKTOP_ENTRY, GNOME_ENABLED).is_some() {
+            file.set(DESKTOP_ENTRY, GNOME_ENABLED, "true");
+        }
+    } else {
+        file.set(DESKTOP_ENTRY, "Hidden", "true");
+    }
+    file.save(&user)
+}
+
+/// Adds an autostart entry for the desktop file at `source`, e.g. an
+/// installed application.
+pub fn add(id: &str, source: &Path) -> io::Result<PathBuf> {
+    let mut file = KeyFile::load(source)?;
+    file.remove(DESKTOP_ENTRY, "Hidden");
+    file.remove(DESKTOP_ENTRY, GNOME_ENABLED);
+    write(id, &file)
+}
+
+/// Writes `file` as the user autostart entry `id`.
+pub fn write(id: &str, file: &KeyFile) -> io::Result<PathBuf> {
+    let path = user_dir().join(id);
+    file.save(&path)?;
+    Ok(path)
+}
+
+/// Removes the user entry `id`. A system entry with the same id becomes
+/// effective again.
+pub fn remove(id: &str) -> io::Result<()> {
+    let path = user_dir().join(id);
+    if !path.exists() {
+        return Err(not_found(id));
+    }
+    std::fs::remove_file(path)
+}
+
+fn not_found(id: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no autostart entry {}", id),
+    )
+}
@@ -0,0 +1,131 @@
+use crate::keyfile::write_atomic;
+use crate::locale::Translations;
+use crate::transliterate::Transliteration;
+use crate::{DesktopEntry, xdg};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Bumped whenever the cached representation changes.
+const VERSION: u32 = 3;
+
+/// Modification time of an application directory or desktop file, used to
+/// tell whether the cache is still in sync with the disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub path: PathBuf,
+    pub mtime: u128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "DesktopEntry")]
+struct DesktopEntryDef {
+    id: String,
+    name: String,
+    lower_name: String,
+    type_: Option<String>,
+    categories: Vec<String>,
+    keywords: Vec<String>,
+    comment: Option<String>,
+    exec: Option<String>,
+    icon_name: Option<String>,
+    icon_path: Option<PathBuf>,
+    mime_types: Vec<String>,
+    actions: Vec<String>,
+    dbus_activatable: bool,
+    no_display: bool,
+    terminal: bool,
+    working_dir: Option<PathBuf>,
+    path: PathBuf,
+    translations: Translations,
+    /// Cheap enough to redo on load, and not worth the cache size.
+    #[serde(skip)]
+    transliterations: IndexMap<String, Vec<Transliteration>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedEntry(#[serde(with = "DesktopEntryDef")] DesktopEntry);
+
+/// Parsed desktop entries and resolved icon paths, persisted in
+/// `$XDG_CACHE_HOME/shiori/entries.json` so startup does not have to parse
+/// every desktop file. Entries carry all their translations, so the cache
+/// does not depend on the locale.
+#[derive(Serialize, Deserialize)]
+pub struct EntryCache {
+    version: u32,
+    pub stamps: Vec<Stamp>,
+    entries: Vec<CachedEntry>,
+}
+
+impl EntryCache {
+    pub fn path() -> PathBuf {
+        xdg::cache_home().join("shiori").join("entries.json")
+    }
+
+    /// Reads the cache without checking it against the disk.
+    pub fn load() -> Option<Self> {
+        let data = std::fs::read(Self::path()).ok()?;
+        let cache: Self = serde_json::from_slice(&data).ok()?;
+        (cache.version == VERSION).then_some(cache)
+    }
+
+    pub fn store<'a>(
+        stamps: &[Stamp],
+        entries: impl IntoIterator<Item = &'a DesktopEntry>,
+    ) -> io::Result<()> {
+        let cache = EntryCache {
+            version: VERSION,
+            stamps: stamps.to_vec(),
+            entries: entries.into_iter().cloned().map(CachedEntry).collect(),
+        };
+        let data = serde_json::to_vec(&cache)?;
+        write_atomic(&Self::path(), &data)
+    }
+
+    pub fn into_entries(self) -> impl Iterator<Item = DesktopEntry> {
+        self.entries.into_iter().map(|e| e.0)
+    }
+}
+
+/// Stamps for every application directory (recursively) and desktop file,
+/// in a stable order.
+pub fn collect_stamps() -> Vec<Stamp> {
+    let mut stamps = Vec::new();
+    let dirs = std::iter::once(xdg::data_home()).chain(xdg::data_dirs());
+    for dir in dirs {
+        collect_dir(&dir.join("applications"), &mut stamps);
+    }
+    stamps
+}
+
+fn collect_dir(dir: &Path, stamps: &mut Vec<Stamp>) {
+    let Some(mtime) = modified_nanos(dir) else {
+        return;
+    };
+    stamps.push(Stamp {
+        path: dir.to_path_buf(),
+        mtime,
+    });
+
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = read_dir.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            collect_dir(&path, stamps);
+        } else if path.extension().is_some_and(|e| e == "desktop")
+            && let Some(mtime) = modified_nanos(&path)
+        {
+            stamps.push(Stamp { path, mtime });
+        }
+    }
+}
+
+fn modified_nanos(path: &Path) -> Option<u128> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
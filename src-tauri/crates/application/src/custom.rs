@@ -0,0 +1,135 @@
+use crate::DesktopEntry;
+use crate::exec::quote_args;
+use crate::keyfile::{KeyFile, escape, join_list};
+use crate::locale::Translations;
+use crate::overrides::DESKTOP_ENTRY;
+use crate::xdg;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+
+/// Prefix of the ids given to custom entries, keeping them apart from
+/// desktop file ids.
+pub const ID_PREFIX: &str = "custom:";
+
+/// A launcher entry defined in shiori's configuration instead of a
+/// `.desktop` file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CustomEntry {
+    /// Stable id within the custom namespace; derived from `name` if unset.
+    pub id: Option<String>,
+    pub name: String,
+    /// A shell command line, run with `sh -c`.
+    pub command: String,
+    pub icon: Option<String>,
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub terminal: bool,
+    pub working_dir: Option<PathBuf>,
+}
+
+impl CustomEntry {
+    /// The entry's id in the `ApplicationService` index, e.g.
+    /// `custom:deploy-staging`.
+    pub fn id(&self) -> String {
+        let slug = match &self.id {
+            Some(id) => slugify(id),
+            None => slugify(&self.name),
+        };
+        format!("{}{}", ID_PREFIX, slug)
+    }
+
+    /// The desktop file id used when the entry is promoted to a real
+    /// `.desktop` file.
+    pub fn desktop_id(&self) -> String {
+        let id = self.id();
+        format!("shiori-{}.desktop", &id[ID_PREFIX.len()..])
+    }
+
+    pub fn exec(&self) -> String {
+        // `%` would otherwise be read as a field code.
+        let command = self.command.replace('%', "%%");
+        quote_args(&["sh", "-c", &command])
+    }
+
+    pub fn to_desktop_entry(&self) -> DesktopEntry {
+        let mut entry = DesktopEntry {
+            id: self.id(),
+            name: self.name.clone(),
+            lower_name: self.name.to_lowercase(),
+            type_: Some("Application".to_string()),
+            categories: Vec::new(),
+            keywords: self.keywords.clone(),
+            comment: self.comment.clone(),
+            exec: Some(self.exec()),
+            icon_name: self.icon.clone(),
+            icon_path: None,
+            mime_types: Vec::new(),
+            actions: Vec::new(),
+            dbus_activatable: false,
+            no_display: false,
+            terminal: self.terminal,
+            working_dir: self.working_dir.clone(),
+            path: PathBuf::new(),
+            translations: Translations::default(),
+            transliterations: Default::default(),
+        };
+        entry.transliterate();
+        entry.icon_path = entry.resolve_icon();
+        entry
+    }
+
+    /// Renders the entry as a `.desktop` file.
+    pub fn to_keyfile(&self) -> KeyFile {
+        let mut file = KeyFile::default();
+        file.set(DESKTOP_ENTRY, "Type", "Application");
+        file.set(DESKTOP_ENTRY, "Name", &escape(&self.name));
+        if let Some(comment) = &self.comment {
+            file.set(DESKTOP_ENTRY, "Comment", &escape(comment));
+        }
+        file.set(DESKTOP_ENTRY, "Exec", &escape(&self.exec()));
+        if let Some(icon) = &self.icon {
+            file.set(DESKTOP_ENTRY, "Icon", &escape(icon));
+        }
+        if !self.keywords.is_empty() {
+            file.set(DESKTOP_ENTRY, "Keywords", &join_list(&self.keywords));
+        }
+        if let Some(dir) = &self.working_dir {
+            file.set(DESKTOP_ENTRY, "Path", &escape(&dir.to_string_lossy()));
+        }
+        file.set(
+            DESKTOP_ENTRY,
+            "Terminal",
+            if self.terminal { "true" } else { "false" },
+        );
+        file
+    }
+
+    /// Writes the entry to `$XDG_DATA_HOME/applications` as a `.desktop`
+    /// file and returns its path.
+    pub fn promote(&self) -> io::Result<PathBuf> {
+        let path = xdg::data_home()
+            .join("applications")
+            .join(self.desktop_id());
+        self.to_keyfile().save(&path)?;
+        Ok(path)
+    }
+}
+
+fn slugify(s: &str) -> String {
+    let mut slug = String::with_capacity(s.len());
+    for c in s.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+    slug
+}
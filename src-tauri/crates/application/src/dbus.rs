@@ -0,0 +1,102 @@
+use crate::DesktopEntry;
+use crate::mime::to_uri;
+use std::collections::HashMap;
+use std::io;
+use zbus::blocking::Connection;
+use zbus::zvariant::Value;
+
+const APPLICATION_INTERFACE: &str = "org.freedesktop.Application";
+
+/// Launches `DBusActivatable=true` entries through the
+/// `org.freedesktop.Application` interface.
+pub struct DBusLauncher {
+    conn: Connection,
+}
+
+impl DBusLauncher {
+    pub fn new(conn: Connection) -> Self {
+        Self { conn }
+    }
+
+    pub fn session() -> io::Result<Self> {
+        Ok(Self::new(Connection::session().map_err(to_io)?))
+    }
+
+    /// Calls `Activate`, or `Open` when there are files or URLs to pass.
+    pub fn activate(
+        &self,
+        entry: &DesktopEntry,
+        targets: &[&str],
+        activation_token: Option<&str>,
+    ) -> io::Result<()> {
+        let platform_data = platform_data(activation_token);
+        if targets.is_empty() {
+            self.call(entry, "Activate", &(platform_data,))
+        } else {
+            let uris: Vec<String> = targets.iter().map(|t| to_uri(t)).collect();
+            self.call(entry, "Open", &(uris, platform_data))
+        }
+    }
+
+    /// Calls `ActivateAction` for one of the entry's `Actions=`.
+    pub fn activate_action(
+        &self,
+        entry: &DesktopEntry,
+        action: &str,
+        activation_token: Option<&str>,
+    ) -> io::Result<()> {
+        let parameter: Vec<Value> = Vec::new();
+        let platform_data = platform_data(activation_token);
+        self.call(entry, "ActivateAction", &(action, parameter, platform_data))
+    }
+
+    fn call<B>(&self, entry: &DesktopEntry, method: &str, body: &B) -> io::Result<()>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        let name = bus_name(&entry.id);
+        self.conn
+            .call_method(
+                Some(name.as_str()),
+                object_path(&name).as_str(),
+                Some(APPLICATION_INTERFACE),
+                method,
+                body,
+            )
+            .map_err(to_io)?;
+        Ok(())
+    }
+}
+
+/// The well-known bus name of an application is its desktop id without the
+/// `.desktop` suffix.
+pub fn bus_name(id: &str) -> String {
+    id.strip_suffix(".desktop").unwrap_or(id).to_string()
+}
+
+/// `org.example.App-Name` -> `/org/example/App_Name`.
+pub fn object_path(bus_name: &str) -> String {
+    let mut path = String::with_capacity(bus_name.len() + 1);
+    path.push('/');
+    for c in bus_name.chars() {
+        match c {
+            '.' => path.push('/'),
+            '-' => path.push('_'),
+            c => path.push(c),
+        }
+    }
+    path
+}
+
+fn platform_data(activation_token: Option<&str>) -> HashMap<&'static str, Value<'_>> {
+    let mut data = HashMap::new();
+    if let Some(token) = activation_token {
+        data.insert("activation-token", Value::from(token));
+        data.insert("desktop-startup-id", Value::from(token));
+    }
+    data
+}
+
+fn to_io(e: zbus::Error) -> io::Error {
+    io::Error::other(e.to_string())
+}
@@ -0,0 +1,168 @@
+use crate::DesktopEntry;
+use crate::mime::{to_local_path, url_scheme};
+use std::io;
+
+/// Expands the field codes of an `Exec` line into an argument vector.
+///
+/// `targets` are the files or URLs being opened. `%f`/`%u` take the first
+/// target, `%F`/`%U` all of them; local paths are passed for `%f`/`%F` and
+/// URLs are kept as is for `%u`/`%U`. Use [`expand_instances`] to launch
+/// every target.
+pub fn expand_exec(entry: &DesktopEntry, exec: &str, targets: &[&str]) -> io::Result<Vec<String>> {
+    expand_words(entry, split(exec)?, targets)
+}
+
+/// Expands an `Exec` line into one argument vector per process to start.
+///
+/// Lines that take a single file or URL (`%f`/`%u`) but no list start one
+/// instance per target, as the spec requires; anything else starts a single
+/// instance with every target.
+pub fn expand_instances(
+    entry: &DesktopEntry,
+    exec: &str,
+    targets: &[&str],
+) -> io::Result<Vec<Vec<String>>> {
+    let words = split(exec)?;
+    let codes: Vec<char> = words.iter().flat_map(|w| field_codes(w)).collect();
+    let per_target = codes.iter().any(|c| matches!(c, 'f' | 'u'))
+        && !codes.iter().any(|c| matches!(c, 'F' | 'U'));
+
+    if per_target && targets.len() > 1 {
+        targets
+            .iter()
+            .map(|target| expand_words(entry, words.clone(), &[target]))
+            .collect()
+    } else {
+        Ok(vec![expand_words(entry, words, targets)?])
+    }
+}
+
+fn split(exec: &str) -> io::Result<Vec<String>> {
+    shell_words::split(exec).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// The field code letters in `word`, skipping `%%`.
+fn field_codes(word: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = word.chars();
+    std::iter::from_fn(move || {
+        loop {
+            if chars.next()? != '%' {
+                continue;
+            }
+            match chars.next()? {
+                '%' => continue,
+                code => return Some(code),
+            }
+        }
+    })
+}
+
+fn expand_words(
+    entry: &DesktopEntry,
+    words: Vec<String>,
+    targets: &[&str],
+) -> io::Result<Vec<String>> {
+    let mut args = Vec::with_capacity(words.len());
+    for word in words {
+        match word.as_str() {
+            "%f" => args.extend(targets.first().map(|t| local_path(t))),
+            "%F" => args.extend(targets.iter().map(|t| local_path(t))),
+            "%u" => args.extend(targets.first().map(|t| t.to_string())),
+            "%U" => args.extend(targets.iter().map(|t| t.to_string())),
+            "%i" => {
+                if let Some(icon) = &entry.icon_name {
+                    args.push("--icon".to_string());
+                    args.push(icon.clone());
+                }
+            }
+            _ => {
+                let expanded = expand_inline(entry, &word, targets);
+                if !expanded.is_empty() || word.is_empty() {
+                    args.push(expanded);
+                }
+            }
+        }
+    }
+
+    if args.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No command found",
+        ));
+    }
+    Ok(args)
+}
+
+/// Prefixes `args` with a terminal emulator for `Terminal=true` entries:
+/// `$TERMINAL` if set, `x-terminal-emulator` otherwise.
+pub fn wrap_in_terminal(args: Vec<String>) -> Vec<String> {
+    let terminal = std::env::var("TERMINAL")
+        .ok()
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| "x-terminal-emulator".to_string());
+
+    let mut wrapped = shell_words::split(&terminal).unwrap_or_else(|_| vec![terminal]);
+    wrapped.push("-e".to_string());
+    wrapped.extend(args);
+    wrapped
+}
+
+/// Joins arguments into an `Exec` value, double-quoting those that contain
+/// reserved characters as the Desktop Entry spec requires.
+pub fn quote_args<S: AsRef<str>>(args: &[S]) -> String {
+    let quoted: Vec<String> = args.iter().map(|a| quote_arg(a.as_ref())).collect();
+    quoted.join(" ")
+}
+
+fn quote_arg(arg: &str) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(',
+        ')', '`',
+    ];
+    if !arg.is_empty() && !arg.contains(RESERVED) {
+        return arg.to_string();
+    }
+
+    let mut out = String::with_capacity(arg.len() + 2);
+    out.push('"');
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+/// Expands field codes embedded inside a larger argument, e.g. `--file=%f`.
+fn expand_inline(entry: &DesktopEntry, word: &str, targets: &[&str]) -> String {
+    let mut out = String::with_capacity(word.len());
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => out.push('%'),
+            Some('f') | Some('F') => {
+                out.push_str(&targets.first().map(|t| local_path(t)).unwrap_or_default())
+            }
+            Some('u') | Some('U') => out.push_str(targets.first().copied().unwrap_or("")),
+            Some('c') => out.push_str(&entry.name),
+            Some('k') => out.push_str(&entry.path.to_string_lossy()),
+            // %i is only meaningful as a standalone argument, the rest are
+            // deprecated and must be removed.
+            Some(_) | None => {}
+        }
+    }
+    out
+}
+
+fn local_path(target: &str) -> String {
+    match url_scheme(target) {
+        Some("file") | None => to_local_path(target).to_string_lossy().into_owned(),
+        Some(_) => target.to_string(),
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A single line of a key file, kept verbatim so files can be written back
+/// without losing comments or unknown groups.
+#[derive(Debug, Clone)]
+pub enum Line {
+    Other(String),
+    Group(String),
+    Entry {
+        key: String,
+        value: String,
+        /// The line as read, written back while the entry is unchanged so
+        /// spacing around `=` survives.
+        raw: Option<String>,
+    },
+}
+
+/// Minimal parser for the freedesktop "desktop entry" key file format, as
+/// used by `mimeapps.list`, `mimeinfo.cache` and `.desktop` files.
+#[derive(Debug, Clone, Default)]
+pub struct KeyFile {
+    pub lines: Vec<Line>,
+}
+
+impl KeyFile {
+    pub fn parse(input: &str) -> Self {
+        let lines = input
+            .lines()
+            .map(|raw| {
+                let trimmed = raw.trim();
+                if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                    Line::Group(trimmed[1..trimmed.len() - 1].to_string())
+                } else if trimmed.starts_with('#') || !trimmed.contains('=') {
+                    Line::Other(raw.to_string())
+                } else {
+                    let (key, value) = trimmed.split_once('=').unwrap();
+                    Line::Entry {
+                        key: key.trim_end().to_string(),
+                        value: value.trim_start().to_string(),
+                        raw: Some(raw.to_string()),
+                    }
+                }
+            })
+            .collect();
+
+        Self { lines }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Iterates `(line index, key, value)` of every entry in `group`.
+    pub fn entries<'a>(&'a self, group: &str) -> impl Iterator<Item = (usize, &'a str, &'a str)> {
+        let mut current: Option<&str> = None;
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, line)| match line {
+                Line::Group(name) => {
+                    current = Some(name);
+                    None
+                }
+                Line::Entry { key, value, .. } if current == Some(group) => {
+                    Some((i, key.as_str(), value.as_str()))
+                }
+                _ => None,
+            })
+    }
+
+    /// The raw value of `key`, with its escape sequences intact.
+    pub fn get_raw(&self, group: &str, key: &str) -> Option<&str> {
+        self.entries(group)
+            .find(|(_, k, _)| *k == key)
+            .map(|(_, _, v)| v)
+    }
+
+    /// The value of `key` with `\s`, `\n`, `\t`, `\r` and `\\` unescaped.
+    pub fn get(&self, group: &str, key: &str) -> Option<String> {
+        self.get_raw(group, key).map(unescape)
+    }
+
+    /// The value of a `;`-separated list key, split and unescaped.
+    pub fn get_list(&self, group: &str, key: &str) -> Option<Vec<String>> {
+        self.get_raw(group, key).map(split_list)
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Group(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn has_group(&self, group: &str) -> bool {
+        self.groups().any(|g| g == group)
+    }
+
+    /// Sets `key` in `group`, replacing an existing value in place or
+    /// appending the key (and the group, if needed) otherwise.
+    pub fn set(&mut self, group: &str, key: &str, value: &str) {
+        if let Some(i) = self.position(group, key) {
+            self.lines[i] = Line::Entry {
+                key: key.to_string(),
+                value: value.to_string(),
+                raw: None,
+            };
+            return;
+        }
+
+        let entry = Line::Entry {
+            key: key.to_string(),
+            value: value.to_string(),
+            raw: None,
+        };
+        match self.group_insert_position(group) {
+            Some(pos) => self.lines.insert(pos, entry),
+            None => {
+                if self
+                    .lines
+                    .last()
+                    .is_some_and(|l| !matches!(l, Line::Other(s) if s.trim().is_empty()))
+                {
+                    self.lines.push(Line::Other(String::new()));
+                }
+                self.lines.push(Line::Group(group.to_string()));
+                self.lines.push(entry);
+            }
+        }
+    }
+
+    /// Removes `key` from `group`, returning whether it was present.
+    pub fn remove(&mut self, group: &str, key: &str) -> bool {
+        match self.position(group, key) {
+            Some(i) => {
+                self.lines.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn position(&self, group: &str, key: &str) -> Option<usize> {
+        self.entries(group)
+            .find(|(_, k, _)| *k == key)
+            .map(|(i, _, _)| i)
+    }
+
+    /// The line right after the last entry of `group`, or after its header
+    /// when it has no entries.
+    fn group_insert_position(&self, group: &str) -> Option<usize> {
+        let header = self
+            .lines
+            .iter()
+            .position(|l| matches!(l, Line::Group(name) if name == group))?;
+        let last = self.entries(group).map(|(i, _, _)| i).last();
+        Some(last.unwrap_or(header) + 1)
+    }
+
+    /// Writes the file atomically.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_atomic(path, self.to_string().as_bytes())
+    }
+
+    /// Writes the file atomically, keeping the previous version next to it
+    /// as `<name>.bak`.
+    pub fn save_with_backup(&self, path: &Path) -> io::Result<()> {
+        write_with_backup(path, self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for KeyFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Other(raw) => writeln!(f, "{}", raw)?,
+                Line::Group(name) => writeln!(f, "[{}]", name)?,
+                Line::Entry { raw: Some(raw), .. } => writeln!(f, "{}", raw)?,
+                Line::Entry { key, value, .. } => writeln!(f, "{}={}", key, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Replaces `path` with `contents` via a temporary file and a rename, so
+/// readers never observe a half-written file. The temporary name is unique
+/// per process and call, so concurrent writers never share it.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let suffix = format!(
+        ".{}.{}.tmp",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    let tmp = sibling(path, &suffix);
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Like [`write_atomic`], but copies an existing file to `<name>.bak` first.
+pub fn write_with_backup(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if path.exists() {
+        fs::copy(path, sibling(path, ".bak"))?;
+    }
+    write_atomic(path, contents)
+}
+
+/// Resolves the escape sequences of a string value. Unknown sequences are
+/// kept as they are.
+pub fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            push_unescaped(&mut out, chars.next());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn push_unescaped(out: &mut String, next: Option<char>) {
+    match next {
+        Some('s') => out.push(' '),
+        Some('n') => out.push('\n'),
+        Some('t') => out.push('\t'),
+        Some('r') => out.push('\r'),
+        Some('\\') => out.push('\\'),
+        Some(c) => {
+            out.push('\\');
+            out.push(c);
+        }
+        None => out.push('\\'),
+    }
+}
+
+/// Escapes a string for use as a value, the inverse of [`unescape`].
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            _ => out.push(c),
+        }
+    }
+    // Leading spaces would be trimmed by the parser.
+    match out.strip_prefix(' ') {
+        Some(rest) => format!("\\s{}", rest),
+        None => out,
+    }
+}
+
+/// Splits a `;`-separated string list, honoring `\;` escapes and
+/// unescaping every item.
+pub fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => current.push(';'),
+                next => push_unescaped(&mut current, next),
+            },
+            ';' => items.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        items.push(current);
+    }
+    items.retain(|s| !s.is_empty());
+    items
+}
+
+/// Joins a string list with `;` separators, escaping every item and its
+/// embedded semicolons.
+pub fn join_list<S: AsRef<str>>(items: &[S]) -> String {
+    let mut out = String::new();
+    for item in items {
+        out.push_str(&escape(item.as_ref()).replace(';', "\\;"));
+        out.push(';');
+    }
+    out
+}
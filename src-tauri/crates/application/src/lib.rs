@@ -1,13 +1,31 @@
+pub mod autostart;
+pub mod cache;
+pub mod custom;
+pub mod dbus;
+pub mod exec;
+pub mod keyfile;
+pub mod locale;
+pub mod mime;
+pub mod overrides;
+pub mod transliterate;
+pub mod validate;
+pub mod xdg;
+
 use indexmap::IndexMap;
 use serde::ser::SerializeStruct;
 use serde::{Serialize, Serializer};
+use std::collections::HashSet;
 use std::process::Command;
 
-use freedesktop_desktop_entry::{desktop_entries, get_languages_from_env};
+use freedesktop_desktop_entry::get_languages_from_env;
 use freedesktop_icons::{lookup, LookupBuilder};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use freedesktop_desktop_entry::DesktopEntry as FDesktopEntry;
+use custom::CustomEntry;
+use keyfile::KeyFile;
+use mime::{AssociationChange, MimeApps};
+use overrides::{DESKTOP_ENTRY, EntryChanges};
+use transliterate::Transliteration;
 
 #[derive(Debug, Clone)]
 pub struct DesktopEntry {
@@ -16,14 +34,134 @@ pub struct DesktopEntry {
     pub lower_name: String,
     pub type_: Option<String>,
     pub categories: Vec<String>,
+    pub keywords: Vec<String>,
     pub comment: Option<String>,
     pub exec: Option<String>,
+    pub icon_name: Option<String>,
+    /// Icon file resolved from `icon_name` (or the id) at load time.
+    pub icon_path: Option<PathBuf>,
+    pub mime_types: Vec<String>,
+    pub actions: Vec<String>,
+    pub dbus_activatable: bool,
+    pub no_display: bool,
+    pub terminal: bool,
+    pub working_dir: Option<PathBuf>,
     pub path: PathBuf,
+    /// Every translation of `Name`, `Comment` and `Keywords`, so the entry
+    /// can be matched in any language and re-localized without a reload.
+    pub translations: locale::Translations,
+    /// Pinyin/romaji forms of the names in CJK scripts, keyed by name. See
+    /// [`DesktopEntry::transliterate`].
+    pub transliterations: IndexMap<String, Vec<Transliteration>>,
 }
 
 impl DesktopEntry {
+    /// Reads the `[Desktop Entry]` group of a parsed desktop file, keeping
+    /// the translations for every locale. The entry is not localized yet.
+    pub fn from_keyfile(id: &str, path: &Path, file: &KeyFile) -> Option<Self> {
+        if !file.has_group(DESKTOP_ENTRY) {
+            return None;
+        }
+        let get = |key: &str| file.get(DESKTOP_ENTRY, key);
+        let list = |key: &str| file.get_list(DESKTOP_ENTRY, key).unwrap_or_default();
+        let flag = |key: &str| get(key).as_deref() == Some("true");
+        let name = get("Name").unwrap_or_default();
+        Some(DesktopEntry {
+            id: id.to_string(),
+            lower_name: name.to_lowercase(),
+            name,
+            type_: get("Type"),
+            categories: list("Categories"),
+            keywords: list("Keywords"),
+            comment: get("Comment"),
+            exec: get("Exec"),
+            icon_name: get("Icon"),
+            icon_path: None,
+            mime_types: list("MimeType"),
+            actions: list("Actions"),
+            dbus_activatable: flag("DBusActivatable"),
+            no_display: flag("NoDisplay"),
+            terminal: flag("Terminal"),
+            working_dir: get("Path").map(PathBuf::from),
+            path: path.to_path_buf(),
+            translations: locale::Translations::from_keyfile(file),
+            transliterations: IndexMap::new(),
+        })
+    }
+
+    /// Transliterates every name the entry can be found by, once, so that
+    /// matching does not redo it for each query.
+    pub fn transliterate(&mut self) {
+        let names: Vec<String> = self.names().map(String::from).collect();
+        self.transliterations = names
+            .into_iter()
+            .filter_map(|name| {
+                let forms = transliterate::transliterate(&name);
+                (!forms.is_empty()).then_some((name, forms))
+            })
+            .collect();
+    }
+
+    /// The transliterations of `name`, one of [`DesktopEntry::names`].
+    pub fn transliterations_of(&self, name: &str) -> &[Transliteration] {
+        self.transliterations.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Looks up an icon named after the id, without the `.desktop` suffix.
     pub fn icon<'a>(&'a self) -> LookupBuilder<'a> {
-        lookup(&self.id)
+        lookup(self.id.strip_suffix(".desktop").unwrap_or(&self.id))
+    }
+
+    /// Finds the icon file for `Icon=`, which may be an absolute path or a
+    /// theme icon name, falling back to an icon named after the id.
+    pub fn resolve_icon(&self) -> Option<PathBuf> {
+        if let Some(name) = &self.icon_name {
+            let path = std::path::Path::new(name);
+            if path.is_absolute() {
+                return path.exists().then(|| path.to_path_buf());
+            }
+            if let Some(found) = lookup(name).with_cache().find() {
+                return Some(found);
+            }
+        }
+        self.icon().with_cache().find()
+    }
+
+    /// Picks `name`, `comment` and `keywords` for the preferred `locales`.
+    pub fn localize(&mut self, locales: &[String]) {
+        if let Some(name) = self.translations.name(locales) {
+            self.name = name.to_string();
+            self.lower_name = name.to_lowercase();
+        }
+        if let Some(comment) = self.translations.comment(locales) {
+            self.comment = Some(comment.to_string());
+        }
+        if let Some(keywords) = self.translations.keywords(locales) {
+            self.keywords = keywords.iter().filter(|k| !k.is_empty()).cloned().collect();
+        }
+    }
+
+    /// The displayed name followed by its translations in other locales.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(
+            self.translations
+                .names
+                .values()
+                .map(String::as_str)
+                .filter(move |n| *n != self.name),
+        )
+    }
+
+    /// The displayed keywords followed by keywords from other locales.
+    pub fn all_keywords(&self) -> impl Iterator<Item = &str> {
+        self.keywords.iter().map(String::as_str).chain(
+            self.translations
+                .keywords
+                .values()
+                .flatten()
+                .map(String::as_str)
+                .filter(move |k| !k.is_empty() && !self.keywords.iter().any(|s| s == k)),
+        )
     }
 }
 
@@ -32,16 +170,20 @@ impl Serialize for DesktopEntry {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("DesktopEntry", 8)?;
+        let mut state = serializer.serialize_struct("DesktopEntry", 12)?;
 
         state.serialize_field("id", &self.id)?;
         state.serialize_field("name", &self.name)?;
         state.serialize_field("type", &self.type_)?;
         state.serialize_field("categories", &self.categories)?;
+        state.serialize_field("keywords", &self.keywords)?;
         state.serialize_field("comment", &self.comment)?;
         state.serialize_field("exec", &self.exec)?;
+        state.serialize_field("mimeTypes", &self.mime_types)?;
+        state.serialize_field("actions", &self.actions)?;
+        state.serialize_field("terminal", &self.terminal)?;
         state.serialize_field("path", &self.path)?;
-        state.serialize_field("iconPath", &self.icon().with_cache().find())?;
+        state.serialize_field("iconPath", &self.icon_path)?;
 
         state.end()
     }
@@ -51,6 +193,11 @@ pub struct ApplicationService {
     pub locales: Vec<String>,
     // ID -> DesktopEntry
     pub entries: IndexMap<String, DesktopEntry>,
+    pub mime_apps: MimeApps,
+    /// Entries from shiori's configuration, merged into `entries`.
+    pub custom_entries: Vec<CustomEntry>,
+    /// State of the application directories when `entries` were loaded.
+    pub stamps: Vec<cache::Stamp>,
 }
 
 impl ApplicationService {
@@ -58,74 +205,299 @@ impl ApplicationService {
         let mut s = Self {
             locales: locales.to_vec(),
             entries: IndexMap::new(),
+            mime_apps: MimeApps::load(),
+            custom_entries: Vec::new(),
+            stamps: cache::collect_stamps(),
         };
+        s.load_entries();
+        s
+    }
+
+    pub fn launch(&self, id: &str, file_url: Option<&str>) -> std::io::Result<()> {
+        let targets: Vec<&str> = file_url.into_iter().filter(|u| !u.is_empty()).collect();
+        self.launch_with(id, &targets)
+    }
 
-        let mut items = desktop_entries(locales);
-        items.sort_by(|a, b| a.id().to_lowercase().cmp(&b.id().to_lowercase()));
-        for item in items.into_iter() {
-            let id = item.id().to_string();
-            let entry = s.convert_entry(item);
-            s.entries.insert(id, entry);
+    /// Launches `id` with the given files or URLs substituted into its
+    /// `Exec` line.
+    pub fn launch_with(&self, id: &str, targets: &[&str]) -> std::io::Result<()> {
+        self.launch_with_token(None, id, targets, None)
+    }
+
+    /// Launches `id`, activating it over D-Bus when the entry is
+    /// `DBusActivatable` and falling back to `Exec` if that fails.
+    /// `activation_token` is passed as platform data, or through
+    /// `XDG_ACTIVATION_TOKEN`/`DESKTOP_STARTUP_ID` for `Exec`. Without a
+    /// `dbus` launcher a session bus connection is opened when needed.
+    pub fn launch_with_token(
+        &self,
+        dbus: Option<&dbus::DBusLauncher>,
+        id: &str,
+        targets: &[&str],
+        activation_token: Option<&str>,
+    ) -> std::io::Result<()> {
+        let entry = self.get_or_err(id)?;
+
+        if entry.dbus_activatable {
+            let activated = with_launcher(dbus, |l| l.activate(entry, targets, activation_token));
+            if activated.is_ok() || entry.exec.is_none() {
+                return activated;
+            }
         }
 
-        s
+        let exec = entry.exec.as_ref().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} has no Exec", id),
+            )
+        })?;
+        spawn_exec(entry, exec, targets, activation_token)
     }
 
-    pub fn launch(&self, id: &str, file_url: Option<&str>) -> std::io::Result<()> {
-        let exec = self.get(id);
-        if exec.is_none() {
-            panic!("no exec");
-        }
-        let exec = exec.unwrap().exec.as_ref().unwrap();
-        let expanded = exec
-            .replace("%u", file_url.unwrap_or(""))
-            .replace("%U", file_url.unwrap_or(""))
-            .replace("%f", file_url.unwrap_or(""))
-            .replace("%F", file_url.unwrap_or(""))
-            .replace("%i", "")
-            .replace("%c", "")
-            .replace("%k", "");
-
-        // 2. 拆分字符串为程序和参数（注意处理引号）
-        let mut parts = shell_words::split(&expanded).expect("Failed to parse Exec line");
-        if parts.is_empty() {
+    /// Runs one of the entry's `[Desktop Action <action>]` groups.
+    pub fn launch_action(
+        &self,
+        dbus: Option<&dbus::DBusLauncher>,
+        id: &str,
+        action: &str,
+        activation_token: Option<&str>,
+    ) -> std::io::Result<()> {
+        let entry = self.get_or_err(id)?;
+        if !entry.actions.iter().any(|a| a == action) {
             return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "No command found",
+                std::io::ErrorKind::NotFound,
+                format!("{} has no action {}", id, action),
             ));
         }
 
-        // 3. 构造并启动命令
-        let program = parts.remove(0);
-        Command::new(program).args(parts).spawn()?; // 也可以用 `.status()?` 或 `.output()?`
+        if entry.dbus_activatable {
+            let activated =
+                with_launcher(dbus, |l| l.activate_action(entry, action, activation_token));
+            if activated.is_ok() {
+                return activated;
+            }
+        }
+
+        let file = keyfile::KeyFile::load(&entry.path)?;
+        let exec = file
+            .get(&format!("Desktop Action {}", action), "Exec")
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("action {} of {} has no Exec", action, id),
+                )
+            })?;
+        spawn_exec(entry, &exec, &[], activation_token)
+    }
 
+    /// Writes a user override for `id` in `$XDG_DATA_HOME/applications`,
+    /// starting from the entry's current file, and reloads the entries.
+    pub fn update_entry(&mut self, id: &str, changes: &EntryChanges) -> std::io::Result<()> {
+        let source = self.get_or_err(id)?.path.clone();
+        overrides::write_override(id, &source, changes, &self.locales)?;
+        self.refresh();
         Ok(())
     }
 
-    fn convert_entry(&self, entry: FDesktopEntry) -> DesktopEntry {
-        let locales = &self.locales;
-        let name = entry.name(locales).unwrap_or_default();
-        DesktopEntry {
-            id: entry.id().to_string(),
-            name: name.to_string(),
-            lower_name: name.to_lowercase(),
-            type_: entry.type_().map(String::from),
-            categories: entry
-                .categories()
-                .unwrap_or_default()
-                .into_iter()
-                .filter_map(|s| {
-                    if s.len() > 0 {
-                        Some(s.to_string())
-                    } else {
-                        None
-                    }
-                })
-                .collect(),
-            comment: entry.comment(locales).map(String::from),
-            exec: entry.exec().map(String::from),
-            // path: entry.path().map(PathBuf::from),
-            path: entry.path,
+    /// Removes the user override for `id`, restoring the system entry.
+    pub fn reset_entry(&mut self, id: &str) -> std::io::Result<()> {
+        overrides::remove_override(id)?;
+        self.refresh();
+        Ok(())
+    }
+
+    /// Replaces the custom entries merged into the index. Entries that have
+    /// been promoted to a `.desktop` file are skipped, as the file already
+    /// provides them.
+    pub fn set_custom_entries(&mut self, custom_entries: Vec<CustomEntry>) {
+        self.entries
+            .retain(|id, _| !id.starts_with(custom::ID_PREFIX));
+        self.custom_entries = custom_entries;
+        self.insert_custom_entries();
+    }
+
+    fn insert_custom_entries(&mut self) {
+        for custom in &self.custom_entries {
+            if self.entries.contains_key(&custom.desktop_id()) {
+                continue;
+            }
+            self.entries.insert(custom.id(), custom.to_desktop_entry());
+        }
+    }
+
+    /// Writes the custom entry `id` out as a `.desktop` file and reloads the
+    /// index, after which the file replaces the custom entry.
+    pub fn promote_custom_entry(&mut self, id: &str) -> std::io::Result<PathBuf> {
+        let custom = self
+            .custom_entries
+            .iter()
+            .find(|c| c.id() == id)
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::NotFound, format!("no entry {}", id))
+            })?;
+        let path = custom.promote()?;
+        self.refresh();
+        Ok(path)
+    }
+
+    /// Starts application `id` with the session by adding it to
+    /// `$XDG_CONFIG_HOME/autostart`.
+    pub fn add_autostart(&self, id: &str) -> std::io::Result<PathBuf> {
+        if let Some(custom) = self.custom_entries.iter().find(|c| c.id() == id) {
+            return autostart::write(&custom.desktop_id(), &custom.to_keyfile());
+        }
+        let entry = self.get_or_err(id)?;
+        autostart::add(&entry.id, &entry.path)
+    }
+
+    /// Validates the desktop file behind `id`.
+    pub fn validate(&self, id: &str) -> std::io::Result<Vec<validate::Issue>> {
+        let entry = self.get_or_err(id)?;
+        if entry.path.as_os_str().is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(validate::validate_file(&entry.path))
+    }
+
+    fn get_or_err(&self, id: &str) -> std::io::Result<&DesktopEntry> {
+        self.get(id).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, format!("no entry {}", id))
+        })
+    }
+
+    /// Applications that can open `target` (a path or URL), in preference
+    /// order.
+    pub fn applications_for(&self, target: &str) -> Vec<&DesktopEntry> {
+        let mime = mime::mime_type_for(target);
+        self.applications_for_mime(&mime)
+    }
+
+    pub fn applications_for_mime(&self, mime: &str) -> Vec<&DesktopEntry> {
+        self.associations(mime)
+            .iter()
+            .filter_map(|id| self.get(id))
+            .collect()
+    }
+
+    /// The default application for `target` (a path or URL).
+    pub fn default_application_for(&self, target: &str) -> Option<&DesktopEntry> {
+        let mime = mime::mime_type_for(target);
+        self.default_application_for_mime(&mime)
+    }
+
+    pub fn default_application_for_mime(&self, mime: &str) -> Option<&DesktopEntry> {
+        let associations = self.associations(mime);
+        self.mime_apps
+            .default_for(mime, &associations, |id| self.entries.contains_key(id))
+            .and_then(|id| self.get(&id))
+    }
+
+    /// Opens `target` with `id`, or with the default application when `id`
+    /// is `None`.
+    pub fn open(&self, target: &str, id: Option<&str>) -> std::io::Result<()> {
+        self.open_with_token(None, target, id, None)
+    }
+
+    /// [`ApplicationService::open`], passing `activation_token` on as
+    /// [`ApplicationService::launch_with_token`] does.
+    pub fn open_with_token(
+        &self,
+        dbus: Option<&dbus::DBusLauncher>,
+        target: &str,
+        id: Option<&str>,
+        activation_token: Option<&str>,
+    ) -> std::io::Result<()> {
+        let id = match id {
+            Some(id) => id.to_string(),
+            None => self
+                .default_application_for(target)
+                .map(|e| e.id.clone())
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("no application for {}", target),
+                    )
+                })?,
+        };
+        self.launch_with_token(dbus, &id, &[target], activation_token)
+    }
+
+    /// Makes `id` the default handler for a MIME type or URL scheme.
+    pub fn set_default_application(&mut self, mime: &str, id: &str) -> std::io::Result<()> {
+        self.update_association(mime, id, AssociationChange::SetDefault)
+    }
+
+    pub fn add_association(&mut self, mime: &str, id: &str) -> std::io::Result<()> {
+        self.update_association(mime, id, AssociationChange::Add)
+    }
+
+    pub fn remove_association(&mut self, mime: &str, id: &str) -> std::io::Result<()> {
+        self.update_association(mime, id, AssociationChange::Remove)
+    }
+
+    fn update_association(
+        &mut self,
+        mime: &str,
+        id: &str,
+        change: AssociationChange,
+    ) -> std::io::Result<()> {
+        if !self.entries.contains_key(id) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no entry {}", id),
+            ));
+        }
+
+        let mime = mime::normalize_mime(mime);
+        mime::update_user_mimeapps(&mime, id, change)?;
+        self.mime_apps = MimeApps::load();
+        Ok(())
+    }
+
+    fn associations(&self, mime: &str) -> Vec<String> {
+        let installed = self
+            .entries
+            .values()
+            .filter(|e| e.mime_types.iter().any(|m| m == mime))
+            .map(|e| e.id.as_str());
+        self.mime_apps.associations(mime, installed)
+    }
+
+    /// Reads every desktop file in the application directories, parsing
+    /// each file once. An id defined in several directories comes from the
+    /// most important one, and a `Hidden` definition removes it.
+    fn load_entries(&mut self) {
+        let mut seen = HashSet::new();
+        let mut entries = Vec::new();
+        for dir in xdg::application_dirs() {
+            for (id, path) in xdg::desktop_files(&dir) {
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                let Ok(file) = KeyFile::load(&path) else {
+                    continue;
+                };
+                if file.get(DESKTOP_ENTRY, "Hidden").as_deref() == Some("true") {
+                    continue;
+                }
+                if let Some(mut entry) = DesktopEntry::from_keyfile(&id, &path, &file) {
+                    entry.localize(&self.locales);
+                    entry.transliterate();
+                    entry.icon_path = entry.resolve_icon();
+                    entries.push(entry);
+                }
+            }
+        }
+        entries.sort_by_key(|e| e.id.to_lowercase());
+        self.entries = entries.into_iter().map(|e| (e.id.clone(), e)).collect();
+    }
+
+    /// Switches the preferred locales and re-localizes every entry from its
+    /// stored translations, without reading any desktop file.
+    pub fn set_locales(&mut self, locales: &[String]) {
+        self.locales = locales.to_vec();
+        for entry in self.entries.values_mut() {
+            entry.localize(locales);
         }
     }
 
@@ -134,16 +506,60 @@ impl ApplicationService {
         Self::new(&locales)
     }
 
+    /// Builds the service from the on-disk entry cache without reading any
+    /// desktop file. The cache may be stale: compare `stamps` with
+    /// [`cache::collect_stamps`] off the startup path and rebuild if needed.
+    pub fn from_cache() -> Option<Self> {
+        let locales = get_languages_from_env();
+        let cache = cache::EntryCache::load()?;
+
+        let stamps = cache.stamps.clone();
+        let entries = cache
+            .into_entries()
+            .map(|mut e| {
+                e.localize(&locales);
+                e.transliterate();
+                (e.id.clone(), e)
+            })
+            .collect();
+        Some(Self {
+            locales,
+            entries,
+            mime_apps: MimeApps::load(),
+            custom_entries: Vec::new(),
+            stamps,
+        })
+    }
+
+    /// Persists the entries parsed from desktop files; custom entries come
+    /// from the configuration and are not cached.
+    pub fn save_cache(&self) -> std::io::Result<()> {
+        let entries = self
+            .entries
+            .values()
+            .filter(|e| !e.id.starts_with(custom::ID_PREFIX));
+        cache::EntryCache::store(&self.stamps, entries)
+    }
+
     pub fn refresh(&mut self) {
-        self.entries.clear();
+        self.mime_apps = MimeApps::load();
+        self.stamps = cache::collect_stamps();
+        self.load_entries();
+        self.insert_custom_entries();
+    }
 
-        let mut items = desktop_entries(&self.locales);
-        items.sort_by(|a, b| a.id().to_lowercase().cmp(&b.id().to_lowercase()));
-        for item in items.into_iter() {
-            let id = item.id().to_string();
-            let entry = self.convert_entry(item);
-            self.entries.insert(id, entry);
+    /// Takes the desktop entries of `fresh`, built without holding on to
+    /// this service. The locales, custom entries and MIME associations stay,
+    /// as they may have changed in the meantime.
+    pub fn adopt_entries(&mut self, fresh: ApplicationService) {
+        self.stamps = fresh.stamps;
+        self.entries = fresh.entries;
+        if fresh.locales != self.locales {
+            for entry in self.entries.values_mut() {
+                entry.localize(&self.locales);
+            }
         }
+        self.insert_custom_entries();
     }
 
     pub fn get(&self, id: &str) -> Option<&DesktopEntry> {
@@ -163,3 +579,42 @@ impl ApplicationService {
         None
     }
 }
+
+/// Runs `activate` with `dbus`, or with a new session bus connection.
+fn with_launcher(
+    dbus: Option<&dbus::DBusLauncher>,
+    activate: impl FnOnce(&dbus::DBusLauncher) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    match dbus {
+        Some(launcher) => activate(launcher),
+        None => activate(&dbus::DBusLauncher::session()?),
+    }
+}
+
+fn spawn_exec(
+    entry: &DesktopEntry,
+    exec: &str,
+    targets: &[&str],
+    activation_token: Option<&str>,
+) -> std::io::Result<()> {
+    for mut parts in exec::expand_instances(entry, exec, targets)? {
+        if entry.terminal {
+            parts = exec::wrap_in_terminal(parts);
+        }
+        let program = parts.remove(0);
+
+        let mut command = Command::new(program);
+        command.args(parts);
+        if let Some(dir) = entry.working_dir.as_ref().filter(|d| d.is_dir()) {
+            command.current_dir(dir);
+        }
+        if let Some(token) = activation_token {
+            command
+                .env("XDG_ACTIVATION_TOKEN", token)
+                .env("DESKTOP_STARTUP_ID", token);
+        }
+        command.spawn()?;
+    }
+
+    Ok(())
+}
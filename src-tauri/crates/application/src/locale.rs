@@ -0,0 +1,93 @@
+use crate::keyfile::{KeyFile, split_list, unescape};
+use crate::overrides::DESKTOP_ENTRY;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// The user's preferred locales from `$LANGUAGE`, `$LC_ALL`, `$LC_MESSAGES`
+/// and `$LANG`, most preferred first.
+pub fn from_env() -> Vec<String> {
+    freedesktop_desktop_entry::get_languages_from_env()
+}
+
+/// `Name`, `Comment` and `Keywords` in every locale a desktop file provides,
+/// keyed by locale. The untranslated value uses the empty key.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Translations {
+    pub names: IndexMap<String, String>,
+    pub comments: IndexMap<String, String>,
+    pub keywords: IndexMap<String, Vec<String>>,
+}
+
+impl Translations {
+    pub fn from_keyfile(file: &KeyFile) -> Self {
+        let mut translations = Self::default();
+        for (_, key, value) in file.entries(DESKTOP_ENTRY) {
+            let (base, locale) = match key.split_once('[') {
+                Some((base, rest)) => (base, rest.trim_end_matches(']')),
+                None => (key, ""),
+            };
+            let locale = locale.to_string();
+            match base {
+                "Name" => {
+                    translations.names.insert(locale, unescape(value));
+                }
+                "Comment" => {
+                    translations.comments.insert(locale, unescape(value));
+                }
+                "Keywords" => {
+                    translations.keywords.insert(locale, split_list(value));
+                }
+                _ => {}
+            }
+        }
+        translations
+    }
+
+    pub fn name(&self, locales: &[String]) -> Option<&str> {
+        lookup(&self.names, locales).map(String::as_str)
+    }
+
+    pub fn comment(&self, locales: &[String]) -> Option<&str> {
+        lookup(&self.comments, locales).map(String::as_str)
+    }
+
+    pub fn keywords(&self, locales: &[String]) -> Option<&[String]> {
+        lookup(&self.keywords, locales).map(Vec::as_slice)
+    }
+}
+
+/// Picks the value for the most preferred locale, following the matching
+/// rules of the Desktop Entry spec (`lang_COUNTRY@MODIFIER`, `lang_COUNTRY`,
+/// `lang@MODIFIER`, `lang`), then the untranslated value.
+pub fn lookup<'a, T>(values: &'a IndexMap<String, T>, locales: &[String]) -> Option<&'a T> {
+    locales
+        .iter()
+        .flat_map(|l| variants(l))
+        .find_map(|l| values.get(&l))
+        .or_else(|| values.get(""))
+}
+
+fn variants(locale: &str) -> Vec<String> {
+    // The encoding is never part of the key.
+    let (base, modifier) = match locale.split_once('@') {
+        Some((base, modifier)) => (base, Some(modifier)),
+        None => (locale, None),
+    };
+    let base = base.split('.').next().unwrap_or(base);
+    let lang = base.split('_').next().unwrap_or(base);
+
+    let mut variants = Vec::with_capacity(4);
+    if let Some(modifier) = modifier {
+        variants.push(format!("{}@{}", base, modifier));
+    }
+    variants.push(base.to_string());
+    if let Some(modifier) = modifier
+        && lang != base
+    {
+        variants.push(format!("{}@{}", lang, modifier));
+    }
+    if lang != base {
+        variants.push(lang.to_string());
+    }
+    variants
+}
@@ -0,0 +1,315 @@
+use crate::keyfile::{KeyFile, join_list, split_list};
+use crate::xdg;
+use indexmap::{IndexMap, IndexSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_APPLICATIONS: &str = "Default Applications";
+const ADDED_ASSOCIATIONS: &str = "Added Associations";
+const REMOVED_ASSOCIATIONS: &str = "Removed Associations";
+const MIME_CACHE: &str = "MIME Cache";
+
+/// Associations read from one `mimeapps.list` file.
+#[derive(Debug, Clone, Default)]
+pub struct MimeAppsList {
+    pub path: PathBuf,
+    pub default: IndexMap<String, Vec<String>>,
+    pub added: IndexMap<String, Vec<String>>,
+    pub removed: IndexMap<String, Vec<String>>,
+}
+
+impl MimeAppsList {
+    pub fn load(path: &Path) -> Option<Self> {
+        let file = KeyFile::load(path).ok()?;
+        let group = |name: &str| {
+            file.entries(name)
+                .map(|(_, k, v)| (k.to_string(), split_list(v)))
+                .collect::<IndexMap<_, _>>()
+        };
+
+        Some(Self {
+            path: path.to_path_buf(),
+            default: group(DEFAULT_APPLICATIONS),
+            added: group(ADDED_ASSOCIATIONS),
+            removed: group(REMOVED_ASSOCIATIONS),
+        })
+    }
+}
+
+/// The resolved MIME association database, following the lookup order of the
+/// "Association between MIME types and applications" spec.
+#[derive(Debug, Clone, Default)]
+pub struct MimeApps {
+    /// `mimeapps.list` files, most important first.
+    pub lists: Vec<MimeAppsList>,
+    /// MIME type -> desktop ids, merged from every `mimeinfo.cache`.
+    pub cache: IndexMap<String, Vec<String>>,
+}
+
+impl MimeApps {
+    pub fn load() -> Self {
+        let lists = mimeapps_paths()
+            .iter()
+            .filter_map(|p| MimeAppsList::load(p))
+            .collect();
+
+        let mut cache: IndexMap<String, Vec<String>> = IndexMap::new();
+        let data_dirs = std::iter::once(xdg::data_home()).chain(xdg::data_dirs());
+        for dir in data_dirs {
+            let Ok(file) = KeyFile::load(&dir.join("applications/mimeinfo.cache")) else {
+                continue;
+            };
+            for (_, mime, ids) in file.entries(MIME_CACHE) {
+                let known = cache.entry(mime.to_string()).or_default();
+                for id in split_list(ids) {
+                    if !known.contains(&id) {
+                        known.push(id);
+                    }
+                }
+            }
+        }
+
+        Self { lists, cache }
+    }
+
+    /// Desktop ids associated with `mime`, in preference order. `installed`
+    /// supplies entries advertising the type through their own `MimeType=`
+    /// key, which covers directories without an up-to-date cache.
+    pub fn associations<'a>(
+        &self,
+        mime: &str,
+        installed: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<String> {
+        let mut removed: IndexSet<&str> = IndexSet::new();
+        let mut result: IndexSet<String> = IndexSet::new();
+
+        for list in &self.lists {
+            for id in list.added.get(mime).into_iter().flatten() {
+                if !removed.contains(id.as_str()) {
+                    result.insert(id.clone());
+                }
+            }
+            for id in list.removed.get(mime).into_iter().flatten() {
+                removed.insert(id);
+            }
+        }
+
+        for id in self.cache.get(mime).into_iter().flatten() {
+            if !removed.contains(id.as_str()) {
+                result.insert(id.clone());
+            }
+        }
+        for id in installed {
+            if !removed.contains(id) {
+                result.insert(id.to_string());
+            }
+        }
+
+        result.into_iter().collect()
+    }
+
+    /// The default application for `mime`. `is_installed` filters out ids
+    /// that point at entries which no longer exist.
+    pub fn default_for(
+        &self,
+        mime: &str,
+        associations: &[String],
+        is_installed: impl Fn(&str) -> bool,
+    ) -> Option<String> {
+        let mut removed: IndexSet<&str> = IndexSet::new();
+        for list in &self.lists {
+            for id in list.default.get(mime).into_iter().flatten() {
+                if !removed.contains(id.as_str()) && is_installed(id) {
+                    return Some(id.clone());
+                }
+            }
+            for id in list.removed.get(mime).into_iter().flatten() {
+                removed.insert(id);
+            }
+        }
+
+        associations.iter().find(|id| is_installed(id)).cloned()
+    }
+}
+
+/// A change to the user's `mimeapps.list`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssociationChange {
+    /// Make the application the default, which also adds the association.
+    SetDefault,
+    Add,
+    /// Remove the association, hiding it even if a system file or the
+    /// application's own `MimeType=` declares it.
+    Remove,
+}
+
+/// Applies `change` for `mime` and `id` to `$XDG_CONFIG_HOME/mimeapps.list`.
+/// Unrelated groups and comments are preserved and the previous file is kept
+/// as a backup.
+pub fn update_user_mimeapps(mime: &str, id: &str, change: AssociationChange) -> io::Result<()> {
+    let path = user_mimeapps_path();
+    let mut file = match KeyFile::load(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => KeyFile::default(),
+        Err(e) => return Err(e),
+    };
+
+    let update = |file: &mut KeyFile, group: &str, edit: &dyn Fn(&mut Vec<String>)| {
+        let mut ids = file.get_list(group, mime).unwrap_or_default();
+        edit(&mut ids);
+        if ids.is_empty() {
+            file.remove(group, mime);
+        } else {
+            file.set(group, mime, &join_list(&ids));
+        }
+    };
+    let prepend = |ids: &mut Vec<String>| {
+        ids.retain(|i| i != id);
+        ids.insert(0, id.to_string());
+    };
+    let append = |ids: &mut Vec<String>| {
+        if !ids.iter().any(|i| i == id) {
+            ids.push(id.to_string());
+        }
+    };
+    let drop = |ids: &mut Vec<String>| ids.retain(|i| i != id);
+
+    match change {
+        AssociationChange::SetDefault => {
+            update(&mut file, DEFAULT_APPLICATIONS, &prepend);
+            update(&mut file, ADDED_ASSOCIATIONS, &append);
+            update(&mut file, REMOVED_ASSOCIATIONS, &drop);
+        }
+        AssociationChange::Add => {
+            update(&mut file, ADDED_ASSOCIATIONS, &append);
+            update(&mut file, REMOVED_ASSOCIATIONS, &drop);
+        }
+        AssociationChange::Remove => {
+            update(&mut file, DEFAULT_APPLICATIONS, &drop);
+            update(&mut file, ADDED_ASSOCIATIONS, &drop);
+            update(&mut file, REMOVED_ASSOCIATIONS, &append);
+        }
+    }
+
+    file.save_with_backup(&path)
+}
+
+pub fn user_mimeapps_path() -> PathBuf {
+    xdg::config_home().join("mimeapps.list")
+}
+
+/// Accepts either a MIME type or a bare URL scheme such as `https`.
+pub fn normalize_mime(mime_or_scheme: &str) -> String {
+    if mime_or_scheme.contains('/') {
+        mime_or_scheme.to_string()
+    } else {
+        format!("x-scheme-handler/{}", mime_or_scheme.to_lowercase())
+    }
+}
+
+/// Every `mimeapps.list` location, most important first.
+pub fn mimeapps_paths() -> Vec<PathBuf> {
+    let desktops = xdg::current_desktops();
+    let dirs = std::iter::once(xdg::config_home())
+        .chain(xdg::config_dirs())
+        .chain(std::iter::once(xdg::data_home().join("applications")))
+        .chain(xdg::data_dirs().into_iter().map(|d| d.join("applications")));
+
+    let mut paths = Vec::new();
+    for dir in dirs {
+        for desktop in &desktops {
+            paths.push(dir.join(format!("{}-mimeapps.list", desktop)));
+        }
+        paths.push(dir.join("mimeapps.list"));
+    }
+    paths
+}
+
+/// Determines the MIME type for a local path or URL. URLs with a scheme other
+/// than `file` map to `x-scheme-handler/<scheme>`.
+pub fn mime_type_for(target: &str) -> String {
+    if let Some(scheme) = url_scheme(target)
+        && scheme != "file"
+    {
+        return format!("x-scheme-handler/{}", scheme);
+    }
+
+    let path = to_local_path(target);
+    if path.is_dir() {
+        return "inode/directory".to_string();
+    }
+
+    mime_guess::from_path(&path)
+        .first_raw()
+        .unwrap_or("application/octet-stream")
+        .to_string()
+}
+
+/// Schemes whose URLs have no `//` authority part.
+const OPAQUE_SCHEMES: &[&str] = &[
+    "about", "data", "geo", "magnet", "mailto", "news", "sip", "sms", "tel", "urn", "xmpp",
+];
+
+/// The scheme of a URL. A colon alone does not make a URL, so relative paths
+/// such as `notes:draft.txt` are not mistaken for one.
+pub(crate) fn url_scheme(target: &str) -> Option<&str> {
+    let (scheme, rest) = target.split_once(':')?;
+    let valid = scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    let url = rest.starts_with("//")
+        || OPAQUE_SCHEMES
+            .iter()
+            .any(|opaque| scheme.eq_ignore_ascii_case(opaque));
+    (valid && url).then_some(scheme)
+}
+
+/// Converts a `file://` URL to a path; anything else is treated as a path.
+pub(crate) fn to_local_path(target: &str) -> PathBuf {
+    match target.strip_prefix("file://") {
+        Some(rest) => {
+            let path = rest.find('/').map(|i| &rest[i..]).unwrap_or(rest);
+            PathBuf::from(percent_decode(path))
+        }
+        None => PathBuf::from(target),
+    }
+}
+
+/// Converts a path to a `file://` URL; URLs are returned unchanged.
+pub(crate) fn to_uri(target: &str) -> String {
+    if url_scheme(target).is_some() {
+        return target.to_string();
+    }
+
+    let path = std::path::absolute(target).unwrap_or_else(|_| PathBuf::from(target));
+    let mut uri = String::from("file://");
+    for b in path.to_string_lossy().bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    uri
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(b) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
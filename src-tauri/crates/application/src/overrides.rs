@@ -0,0 +1,127 @@
+use crate::keyfile::{KeyFile, escape, join_list};
+use crate::validate::{self, Severity};
+use crate::xdg;
+use serde::Deserialize;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub(crate) const DESKTOP_ENTRY: &str = "Desktop Entry";
+
+/// Changes applied on top of an entry when writing a user override.
+/// `None` leaves the corresponding key untouched.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EntryChanges {
+    pub name: Option<String>,
+    pub icon: Option<String>,
+    pub exec: Option<String>,
+    pub keywords: Option<Vec<String>>,
+    /// Maps to `NoDisplay`, which hides the entry from menus and search.
+    pub hidden: Option<bool>,
+}
+
+impl EntryChanges {
+    /// Applies the changes to the `[Desktop Entry]` group, escaping the
+    /// values. Localized variants for `locales` are updated as well so the
+    /// new value is what actually shows up; other variants are left alone.
+    pub fn apply(&self, file: &mut KeyFile, locales: &[String]) {
+        if let Some(name) = &self.name {
+            set_localized(file, "Name", &escape(name), locales);
+        }
+        if let Some(keywords) = &self.keywords {
+            set_localized(file, "Keywords", &join_list(keywords), locales);
+        }
+        if let Some(icon) = &self.icon {
+            file.set(DESKTOP_ENTRY, "Icon", &escape(icon));
+        }
+        if let Some(exec) = &self.exec {
+            file.set(DESKTOP_ENTRY, "Exec", &escape(exec));
+        }
+        if let Some(hidden) = self.hidden {
+            file.set(
+                DESKTOP_ENTRY,
+                "NoDisplay",
+                if hidden { "true" } else { "false" },
+            );
+        }
+    }
+}
+
+fn set_localized(file: &mut KeyFile, key: &str, value: &str, locales: &[String]) {
+    file.set(DESKTOP_ENTRY, key, value);
+    for locale in locales {
+        let localized = format!("{}[{}]", key, locale);
+        if file.get(DESKTOP_ENTRY, &localized).is_some() {
+            file.set(DESKTOP_ENTRY, &localized, value);
+        }
+    }
+}
+
+/// Where a user override for `id` lives.
+pub fn override_path(id: &str) -> PathBuf {
+    xdg::data_home().join("applications").join(id)
+}
+
+/// System-wide files that a user override for `id` shadows, including
+/// ones in subdirectories such as `kde/foo.desktop` for `kde-foo.desktop`.
+pub fn system_paths(id: &str) -> Vec<PathBuf> {
+    xdg::data_dirs()
+        .into_iter()
+        .flat_map(|d| xdg::desktop_files(&d.join("applications")))
+        .filter(|(file_id, _)| file_id == id)
+        .map(|(_, path)| path)
+        .collect()
+}
+
+/// Copies the entry at `source` to the user override location for `id`,
+/// applies `changes`, validates the result and writes it. Only errors the
+/// changes introduce are rejected; ones the source already has are kept.
+pub fn write_override(
+    id: &str,
+    source: &Path,
+    changes: &EntryChanges,
+    locales: &[String],
+) -> io::Result<PathBuf> {
+    let original = KeyFile::load(source)?;
+    let mut file = original.clone();
+    changes.apply(&mut file, locales);
+
+    let path = override_path(id);
+    check(&original, &file, &path)?;
+    file.save(&path)?;
+    Ok(path)
+}
+
+/// Removes the user override for `id`. Fails when there is no system entry
+/// to fall back to, as deleting the file would remove the application.
+pub fn remove_override(id: &str) -> io::Result<()> {
+    if system_paths(id).is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} has no system entry to reset to", id),
+        ));
+    }
+    std::fs::remove_file(override_path(id))
+}
+
+fn check(original: &KeyFile, file: &KeyFile, path: &Path) -> io::Result<()> {
+    let errors = |file| {
+        validate::validate_keyfile(file, path)
+            .into_iter()
+            .filter(|issue| issue.severity == Severity::Error)
+    };
+    // Lines move when keys are added, so issues are compared by message.
+    let existing: Vec<String> = errors(original).map(|issue| issue.message).collect();
+    let errors: Vec<String> = errors(file)
+        .filter(|issue| !existing.contains(&issue.message))
+        .map(|issue| issue.to_string())
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            errors.join("\n"),
+        ))
+    }
+}
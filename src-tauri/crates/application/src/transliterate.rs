@@ -0,0 +1,327 @@
+use pinyin::ToPinyinMulti;
+
+/// Upper bound for the forms produced for one name, so names full of
+/// polyphonic characters do not blow up the index.
+const MAX_FORMS: usize = 16;
+
+/// A Latin rendering of a CJK name, e.g. `zhongduan` or `zd` for `终端`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transliteration {
+    pub text: String,
+    /// For every char of `text`, the index of the char of the original name
+    /// it was produced from.
+    pub sources: Vec<usize>,
+}
+
+impl Transliteration {
+    /// Maps char indices in `text`, e.g. fuzzy match positions, onto the
+    /// original name.
+    pub fn source_indices(&self, indices: &[usize]) -> Vec<usize> {
+        let mut mapped: Vec<usize> = indices
+            .iter()
+            .filter_map(|&i| self.sources.get(i).copied())
+            .collect();
+        mapped.dedup();
+        mapped
+    }
+}
+
+/// One char of the original name and the ways it can be read.
+struct Segment {
+    readings: Vec<String>,
+    kind: Kind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// A char that is not CJK, read as itself.
+    Other,
+    /// A CJK syllable, contributing its first letter to the initials.
+    Syllable,
+    /// Part of a syllable (`っ`, `ー`, the `ゃ` of `きゃ`), left out of the
+    /// initials.
+    Modifier,
+}
+
+/// Full readings and initials of `name` in pinyin (Chinese) or romaji
+/// (Japanese). Polyphonic characters contribute one form per reading.
+/// Returns nothing for names without CJK characters.
+pub fn transliterate(name: &str) -> Vec<Transliteration> {
+    let chars: Vec<char> = name.chars().collect();
+    if !chars.iter().any(|&c| is_han(c) || is_kana(c)) {
+        return Vec::new();
+    }
+
+    let mut bases = Vec::new();
+    if chars.iter().any(|&c| is_kana(c)) {
+        bases.push(segments(&chars, true));
+    } else {
+        bases.push(segments(&chars, false));
+        // Kanji-only names, e.g. `端末`, may be Japanese as well.
+        if contains_word(&chars) {
+            bases.push(segments(&chars, true));
+        }
+    }
+
+    let mut forms: Vec<Transliteration> = Vec::new();
+    for base in &bases {
+        for choice in choices(base) {
+            for form in [full(base, &choice), initials(base, &choice)] {
+                if forms.len() < MAX_FORMS && !forms.iter().any(|f| f.text == form.text) {
+                    forms.push(form);
+                }
+            }
+        }
+    }
+    forms
+}
+
+/// Reading picks: first all first readings, then each alternative reading
+/// of each segment on its own.
+fn choices(segments: &[Segment]) -> Vec<Vec<usize>> {
+    let mut choices = vec![vec![0; segments.len()]];
+    for (i, segment) in segments.iter().enumerate() {
+        for alt in 1..segment.readings.len() {
+            let mut choice = vec![0; segments.len()];
+            choice[i] = alt;
+            choices.push(choice);
+        }
+    }
+    choices.truncate(MAX_FORMS);
+    choices
+}
+
+fn full(segments: &[Segment], choice: &[usize]) -> Transliteration {
+    let mut text = String::new();
+    let mut sources = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let reading = &segment.readings[choice[i]];
+        text.push_str(reading);
+        sources.extend(std::iter::repeat_n(i, reading.chars().count()));
+    }
+    Transliteration { text, sources }
+}
+
+fn initials(segments: &[Segment], choice: &[usize]) -> Transliteration {
+    let mut text = String::new();
+    let mut sources = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let reading = &segment.readings[choice[i]];
+        let taken: String = match segment.kind {
+            Kind::Other => reading.clone(),
+            Kind::Syllable => reading.chars().take(1).collect(),
+            Kind::Modifier => String::new(),
+        };
+        sources.extend(std::iter::repeat_n(i, taken.chars().count()));
+        text.push_str(&taken);
+    }
+    Transliteration { text, sources }
+}
+
+fn segments(chars: &[char], japanese: bool) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if japanese && let Some((len, readings)) = match_word(&chars[i..]) {
+            for reading in readings.split('|').take(len) {
+                segments.push(syllable(vec![reading.to_string()]));
+            }
+            i += len;
+            continue;
+        }
+
+        let c = chars[i];
+        if is_kana(c) {
+            let consumed = push_kana(&chars[i..], &mut segments);
+            i += consumed;
+            continue;
+        }
+        if let Some(multi) = c.to_pinyin_multi() {
+            let mut readings: Vec<String> = Vec::new();
+            for p in multi {
+                let plain = p.plain().to_string();
+                if !readings.contains(&plain) {
+                    readings.push(plain);
+                }
+            }
+            segments.push(syllable(readings));
+        } else {
+            segments.push(Segment {
+                readings: vec![c.to_lowercase().collect()],
+                kind: Kind::Other,
+            });
+        }
+        i += 1;
+    }
+
+    // A sokuon doubles the consonant that follows it.
+    for i in 0..segments.len() {
+        if segments[i].readings[0] == SOKUON {
+            let next = segments
+                .get(i + 1)
+                .and_then(|s| s.readings[0].chars().next())
+                .filter(|c| !"aiueon".contains(*c));
+            let doubled = match next {
+                Some('c') => "t".to_string(),
+                Some(c) => c.to_string(),
+                None => String::new(),
+            };
+            segments[i].readings = vec![doubled];
+        }
+    }
+    segments
+}
+
+fn syllable(readings: Vec<String>) -> Segment {
+    Segment {
+        readings,
+        kind: Kind::Syllable,
+    }
+}
+
+fn modifier(readings: Vec<String>) -> Segment {
+    Segment {
+        readings,
+        kind: Kind::Modifier,
+    }
+}
+
+/// Placeholder reading for `っ`, resolved once the next syllable is known.
+const SOKUON: &str = "\u{0}";
+
+/// Appends the segments for the kana at the start of `chars`, returning how
+/// many chars were consumed.
+fn push_kana(chars: &[char], segments: &mut Vec<Segment>) -> usize {
+    let c = to_hiragana(chars[0]);
+    if c == 'っ' {
+        segments.push(modifier(vec![SOKUON.to_string()]));
+        return 1;
+    }
+    if c == 'ー' {
+        // Long vowels are often left out when typing, e.g. `taminaru`.
+        // The `ゅ` of `みゅ` reads as nothing, so look past it.
+        let vowel = segments
+            .iter()
+            .rev()
+            .find_map(|s| s.readings[0].chars().last())
+            .filter(|c| "aiueo".contains(*c))
+            .map(String::from)
+            .unwrap_or_default();
+        segments.push(modifier(vec![vowel, String::new()]));
+        return 1;
+    }
+
+    if let Some(&next) = chars.get(1) {
+        let pair: String = [c, to_hiragana(next)].iter().collect();
+        if let Some(romaji) = kana_romaji(&pair) {
+            // The whole syllable is attributed to the first char.
+            segments.push(syllable(vec![romaji.to_string()]));
+            segments.push(modifier(vec![String::new()]));
+            return 2;
+        }
+    }
+    let romaji = kana_romaji(&c.to_string()).unwrap_or_default();
+    segments.push(syllable(vec![romaji.to_string()]));
+    1
+}
+
+fn to_hiragana(c: char) -> char {
+    match c {
+        'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{3096}' | '\u{30A1}'..='\u{30FA}' | 'ー')
+}
+
+fn is_han(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+fn kana_romaji(kana: &str) -> Option<&'static str> {
+    KANA.iter().find(|(k, _)| *k == kana).map(|(_, r)| *r)
+}
+
+fn match_word(chars: &[char]) -> Option<(usize, &'static str)> {
+    (1..=chars.len().min(3)).rev().find_map(|len| {
+        let word: String = chars[..len].iter().collect();
+        WORDS
+            .iter()
+            .find(|(w, _)| *w == word)
+            .map(|(_, reading)| (len, *reading))
+    })
+}
+
+fn contains_word(chars: &[char]) -> bool {
+    (0..chars.len()).any(|i| match_word(&chars[i..]).is_some())
+}
+
+/// Hepburn romaji for hiragana; katakana is mapped onto hiragana first.
+#[rustfmt::skip]
+const KANA: &[(&str, &str)] = &[
+    ("きゃ", "kya"), ("きゅ", "kyu"), ("きょ", "kyo"),
+    ("しゃ", "sha"), ("しゅ", "shu"), ("しぇ", "she"), ("しょ", "sho"),
+    ("ちゃ", "cha"), ("ちゅ", "chu"), ("ちぇ", "che"), ("ちょ", "cho"),
+    ("にゃ", "nya"), ("にゅ", "nyu"), ("にょ", "nyo"),
+    ("ひゃ", "hya"), ("ひゅ", "hyu"), ("ひょ", "hyo"),
+    ("みゃ", "mya"), ("みゅ", "myu"), ("みょ", "myo"),
+    ("りゃ", "rya"), ("りゅ", "ryu"), ("りょ", "ryo"),
+    ("ぎゃ", "gya"), ("ぎゅ", "gyu"), ("ぎょ", "gyo"),
+    ("じゃ", "ja"), ("じゅ", "ju"), ("じぇ", "je"), ("じょ", "jo"),
+    ("びゃ", "bya"), ("びゅ", "byu"), ("びょ", "byo"),
+    ("ぴゃ", "pya"), ("ぴゅ", "pyu"), ("ぴょ", "pyo"),
+    ("ふぁ", "fa"), ("ふぃ", "fi"), ("ふぇ", "fe"), ("ふぉ", "fo"),
+    ("うぃ", "wi"), ("うぇ", "we"), ("うぉ", "wo"),
+    ("てぃ", "ti"), ("でぃ", "di"), ("とぅ", "tu"), ("どぅ", "du"),
+    ("ゔぁ", "va"), ("ゔぃ", "vi"), ("ゔぇ", "ve"), ("ゔぉ", "vo"),
+    ("あ", "a"), ("い", "i"), ("う", "u"), ("え", "e"), ("お", "o"),
+    ("ぁ", "a"), ("ぃ", "i"), ("ぅ", "u"), ("ぇ", "e"), ("ぉ", "o"),
+    ("か", "ka"), ("き", "ki"), ("く", "ku"), ("け", "ke"), ("こ", "ko"),
+    ("が", "ga"), ("ぎ", "gi"), ("ぐ", "gu"), ("げ", "ge"), ("ご", "go"),
+    ("さ", "sa"), ("し", "shi"), ("す", "su"), ("せ", "se"), ("そ", "so"),
+    ("ざ", "za"), ("じ", "ji"), ("ず", "zu"), ("ぜ", "ze"), ("ぞ", "zo"),
+    ("た", "ta"), ("ち", "chi"), ("つ", "tsu"), ("て", "te"), ("と", "to"),
+    ("だ", "da"), ("ぢ", "ji"), ("づ", "zu"), ("で", "de"), ("ど", "do"),
+    ("な", "na"), ("に", "ni"), ("ぬ", "nu"), ("ね", "ne"), ("の", "no"),
+    ("は", "ha"), ("ひ", "hi"), ("ふ", "fu"), ("へ", "he"), ("ほ", "ho"),
+    ("ば", "ba"), ("び", "bi"), ("ぶ", "bu"), ("べ", "be"), ("ぼ", "bo"),
+    ("ぱ", "pa"), ("ぴ", "pi"), ("ぷ", "pu"), ("ぺ", "pe"), ("ぽ", "po"),
+    ("ま", "ma"), ("み", "mi"), ("む", "mu"), ("め", "me"), ("も", "mo"),
+    ("や", "ya"), ("ゆ", "yu"), ("よ", "yo"), ("ゃ", "ya"), ("ゅ", "yu"), ("ょ", "yo"),
+    ("ら", "ra"), ("り", "ri"), ("る", "ru"), ("れ", "re"), ("ろ", "ro"),
+    ("わ", "wa"), ("ゐ", "i"), ("ゑ", "e"), ("を", "o"), ("ん", "n"),
+    ("ゔ", "vu"), ("ゎ", "wa"), ("ゕ", "ka"), ("ゖ", "ke"),
+];
+
+/// Readings of kanji words common in application names, one reading per
+/// kanji separated by `|`. Kanji have no single reading in Japanese, so only
+/// words listed here are romanized; other kanji fall back to pinyin.
+#[rustfmt::skip]
+const WORDS: &[(&str, &str)] = &[
+    ("表計算", "hyou|kei|san"), ("管理者", "kan|ri|sha"), ("証明書", "shou|mei|sho"),
+    ("日本語", "ni|hon|go"), ("入出力", "nyuu|shutsu|ryoku"), ("予定表", "yo|tei|hyou"),
+    ("端末", "tan|matsu"), ("設定", "set|tei"), ("電卓", "den|taku"), ("画像", "ga|zou"),
+    ("音楽", "on|gaku"), ("動画", "dou|ga"), ("文書", "bun|sho"), ("表示", "hyou|ji"),
+    ("編集", "hen|shuu"), ("管理", "kan|ri"), ("写真", "sha|shin"), ("時計", "to|kei"),
+    ("天気", "ten|ki"), ("地図", "chi|zu"), ("辞書", "ji|sho"), ("辞典", "ji|ten"),
+    ("翻訳", "hon|yaku"), ("検索", "ken|saku"), ("計算", "kei|san"), ("電話", "den|wa"),
+    ("連絡", "ren|raku"), ("予定", "yo|tei"), ("日記", "nik|ki"), ("日程", "nit|tei"),
+    ("手帳", "te|chou"), ("記録", "ki|roku"), ("録音", "roku|on"), ("映像", "ei|zou"),
+    ("再生", "sai|sei"), ("印刷", "in|satsu"), ("削除", "saku|jo"), ("保存", "ho|zon"),
+    ("圧縮", "as|shuku"), ("解凍", "kai|tou"), ("共有", "kyou|yuu"), ("更新", "kou|shin"),
+    ("情報", "jou|hou"), ("監視", "kan|shi"), ("起動", "ki|dou"), ("入力", "nyuu|ryoku"),
+    ("文字", "mo|ji"), ("変換", "hen|kan"), ("容量", "you|ryou"), ("使用", "shi|you"),
+    ("接続", "setsu|zoku"), ("無線", "mu|sen"), ("電源", "den|gen"), ("画面", "ga|men"),
+    ("壁紙", "kabe|gami"), ("背景", "hai|kei"), ("言語", "gen|go"), ("地域", "chi|iki"),
+    ("日付", "hi|zuke"), ("時刻", "ji|koku"), ("時間", "ji|kan"), ("通知", "tsuu|chi"),
+    ("音量", "on|ryou"), ("機器", "ki|ki"), ("装置", "sou|chi"), ("開発", "kai|hatsu"),
+    ("環境", "kan|kyou"), ("図形", "zu|kei"), ("描画", "byou|ga"), ("電子", "den|shi"),
+    ("郵便", "yuu|bin"), ("手紙", "te|gami"), ("受信", "ju|shin"), ("送信", "sou|shin"),
+    ("閲覧", "etsu|ran"), ("数独", "suu|doku"), ("麻雀", "maa|jan"), ("将棋", "shou|gi"),
+    ("囲碁", "i|go"), ("地雷", "ji|rai"), ("音声", "on|sei"), ("認識", "nin|shiki"),
+    ("書類", "sho|rui"), ("資料", "shi|ryou"), ("作成", "saku|sei"), ("作業", "sa|gyou"),
+    ("分析", "bun|seki"), ("仮想", "ka|sou"), ("機械", "ki|kai"), ("暗号", "an|gou"),
+    ("秘密", "hi|mitsu"), ("鍵", "kagi"), ("窓", "mado"), ("箱", "hako"), ("色", "iro"),
+];
@@ -0,0 +1,546 @@
+use crate::keyfile::{KeyFile, Line};
+use crate::overrides::DESKTOP_ENTRY;
+use crate::xdg;
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Hint,
+    Warning,
+    Error,
+}
+
+/// A problem found in a desktop file, in the spirit of
+/// `desktop-file-validate`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub path: PathBuf,
+    /// 1-based line, when the issue can be pinned to one.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Hint => "hint",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.line {
+            Some(line) => write!(
+                f,
+                "{}:{}: {}: {}",
+                self.path.display(),
+                line,
+                severity,
+                self.message
+            ),
+            None => write!(f, "{}: {}: {}", self.path.display(), severity, self.message),
+        }
+    }
+}
+
+const MAIN_CATEGORIES: &[&str] = &[
+    "AudioVideo",
+    "Audio",
+    "Video",
+    "Development",
+    "Education",
+    "Game",
+    "Graphics",
+    "Network",
+    "Office",
+    "Science",
+    "Settings",
+    "System",
+    "Utility",
+];
+
+const ADDITIONAL_CATEGORIES: &[&str] = &[
+    "Building",
+    "Debugger",
+    "IDE",
+    "GUIDesigner",
+    "Profiling",
+    "RevisionControl",
+    "Translation",
+    "Calendar",
+    "ContactManagement",
+    "Database",
+    "Dictionary",
+    "Chart",
+    "Email",
+    "Finance",
+    "FlowChart",
+    "PDA",
+    "ProjectManagement",
+    "Presentation",
+    "Spreadsheet",
+    "WordProcessor",
+    "2DGraphics",
+    "VectorGraphics",
+    "RasterGraphics",
+    "3DGraphics",
+    "Scanning",
+    "OCR",
+    "Photography",
+    "Publishing",
+    "Viewer",
+    "TextTools",
+    "DesktopSettings",
+    "HardwareSettings",
+    "Printing",
+    "PackageManager",
+    "Dialup",
+    "InstantMessaging",
+    "Chat",
+    "IRCClient",
+    "Feed",
+    "FileTransfer",
+    "HamRadio",
+    "News",
+    "P2P",
+    "RemoteAccess",
+    "Telephony",
+    "TelephonyTools",
+    "VideoConference",
+    "WebBrowser",
+    "WebDevelopment",
+    "Midi",
+    "Mixer",
+    "Sequencer",
+    "Tuner",
+    "TV",
+    "AudioVideoEditing",
+    "Player",
+    "Recorder",
+    "DiscBurning",
+    "ActionGame",
+    "AdventureGame",
+    "ArcadeGame",
+    "BoardGame",
+    "BlocksGame",
+    "CardGame",
+    "KidsGame",
+    "LogicGame",
+    "RolePlaying",
+    "Shooter",
+    "Simulation",
+    "SportsGame",
+    "StrategyGame",
+    "Art",
+    "Construction",
+    "Music",
+    "Languages",
+    "ArtificialIntelligence",
+    "Astronomy",
+    "Biology",
+    "Chemistry",
+    "ComputerScience",
+    "DataVisualization",
+    "Economy",
+    "Electricity",
+    "Geography",
+    "Geology",
+    "Geoscience",
+    "History",
+    "Humanities",
+    "ImageProcessing",
+    "Literature",
+    "Maps",
+    "Math",
+    "NumericalAnalysis",
+    "MedicalSoftware",
+    "Physics",
+    "Robotics",
+    "Spirituality",
+    "Sports",
+    "ParallelComputing",
+    "Amusement",
+    "Archiving",
+    "Compression",
+    "Electronics",
+    "Emulator",
+    "Engineering",
+    "FileTools",
+    "FileManager",
+    "TerminalEmulator",
+    "Filesystem",
+    "Monitor",
+    "Security",
+    "Accessibility",
+    "Calculator",
+    "Clock",
+    "TextEditor",
+    "Documentation",
+    "Adult",
+    "Core",
+    "KDE",
+    "GNOME",
+    "XFCE",
+    "DDE",
+    "GTK",
+    "Qt",
+    "Motif",
+    "Java",
+    "ConsoleOnly",
+];
+
+const BOOLEAN_KEYS: &[&str] = &[
+    "NoDisplay",
+    "Hidden",
+    "DBusActivatable",
+    "Terminal",
+    "StartupNotify",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+];
+
+/// Keys from older versions of the spec that are no longer used.
+const DEPRECATED_KEYS: &[&str] = &[
+    "Encoding",
+    "MiniIcon",
+    "TerminalOptions",
+    "Protocols",
+    "Extensions",
+    "BinaryPattern",
+    "MapNotify",
+    "SwallowTitle",
+    "SwallowExec",
+    "SortOrder",
+    "FilePattern",
+];
+
+const DEPRECATED_FIELD_CODES: &[char] = &['d', 'D', 'n', 'N', 'v', 'm'];
+const FIELD_CODES: &[char] = &['f', 'F', 'u', 'U', 'i', 'c', 'k', '%'];
+
+/// Validates the desktop file at `path`.
+pub fn validate_file(path: &Path) -> Vec<Issue> {
+    match KeyFile::load(path) {
+        Ok(file) => validate_keyfile(&file, path),
+        Err(e) => vec![Issue {
+            severity: Severity::Error,
+            path: path.to_path_buf(),
+            line: None,
+            message: format!("cannot read file: {}", e),
+        }],
+    }
+}
+
+/// Validates every desktop file in the XDG application directories. An id
+/// defined twice in one directory is an error; one defined again in a less
+/// important directory is shadowed, which is reported as a hint.
+pub fn validate_all() -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut effective: IndexMap<String, PathBuf> = IndexMap::new();
+    for dir in xdg::application_dirs() {
+        let mut ids: IndexMap<String, PathBuf> = IndexMap::new();
+        for (id, path) in xdg::desktop_files(&dir) {
+            issues.extend(validate_file(&path));
+
+            if let Some(previous) = ids.get(&id) {
+                issues.push(Issue {
+                    severity: Severity::Error,
+                    path,
+                    line: None,
+                    message: format!(
+                        "desktop id {} is also defined by {}",
+                        id,
+                        previous.display()
+                    ),
+                });
+                continue;
+            }
+            if let Some(shadowing) = effective.get(&id) {
+                issues.push(Issue {
+                    severity: Severity::Hint,
+                    path: path.clone(),
+                    line: None,
+                    message: format!("desktop id {} is overridden by {}", id, shadowing.display()),
+                });
+            }
+            ids.insert(id, path);
+        }
+        for (id, path) in ids {
+            effective.entry(id).or_insert(path);
+        }
+    }
+    issues
+}
+
+pub fn validate_keyfile(file: &KeyFile, path: &Path) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut push = |severity, line: Option<usize>, message: String| {
+        issues.push(Issue {
+            severity,
+            path: path.to_path_buf(),
+            line: line.map(|i| i + 1),
+            message,
+        })
+    };
+
+    check_structure(file, &mut push);
+
+    let Some(header) = file
+        .lines
+        .iter()
+        .position(|l| matches!(l, Line::Group(g) if g == DESKTOP_ENTRY))
+    else {
+        push(
+            Severity::Error,
+            None,
+            format!("missing [{}] group", DESKTOP_ENTRY),
+        );
+        return issues;
+    };
+
+    let line_of = |key: &str| {
+        file.entries(DESKTOP_ENTRY)
+            .find(|(_, k, _)| *k == key)
+            .map(|(i, _, _)| i)
+    };
+    let get = |key: &str| file.get(DESKTOP_ENTRY, key);
+
+    let type_ = get("Type");
+    let type_ = type_.as_deref();
+    match type_ {
+        None => push(
+            Severity::Error,
+            Some(header),
+            "missing required key Type".into(),
+        ),
+        Some("Application" | "Link" | "Directory") => {}
+        Some(other) => push(
+            Severity::Error,
+            line_of("Type"),
+            format!("invalid Type {}", other),
+        ),
+    }
+    if get("Name").is_none_or(|v| v.trim().is_empty()) {
+        push(
+            Severity::Error,
+            Some(header),
+            "missing required key Name".into(),
+        );
+    }
+    if type_ == Some("Link") && get("URL").is_none() {
+        push(
+            Severity::Error,
+            Some(header),
+            "Link entry without URL".into(),
+        );
+    }
+
+    for key in DEPRECATED_KEYS {
+        if let Some(line) = line_of(key) {
+            push(
+                Severity::Warning,
+                Some(line),
+                format!("deprecated key {}", key),
+            );
+        }
+    }
+
+    for key in BOOLEAN_KEYS {
+        if let Some(value) = get(key)
+            && value != "true"
+            && value != "false"
+        {
+            push(
+                Severity::Error,
+                line_of(key),
+                format!("{} must be true or false, got {}", key, value),
+            );
+        }
+    }
+
+    if let Some(categories) = file.get_list(DESKTOP_ENTRY, "Categories") {
+        for category in &categories {
+            let known = MAIN_CATEGORIES.contains(&category.as_str())
+                || ADDITIONAL_CATEGORIES.contains(&category.as_str());
+            if !known && !category.starts_with("X-") {
+                push(
+                    Severity::Error,
+                    line_of("Categories"),
+                    format!("unregistered category {}", category),
+                );
+            }
+        }
+        let has_main = categories
+            .iter()
+            .any(|c| MAIN_CATEGORIES.contains(&c.as_str()));
+        if !has_main && get("NoDisplay").as_deref() != Some("true") {
+            push(
+                Severity::Hint,
+                line_of("Categories"),
+                "no main category, the entry may not appear in menus".into(),
+            );
+        }
+    }
+
+    if type_ == Some("Application") {
+        match get("Exec") {
+            Some(exec) => check_exec(&exec, line_of("Exec"), &mut push),
+            None if get("DBusActivatable").as_deref() == Some("true") => {}
+            None => push(
+                Severity::Error,
+                Some(header),
+                "Application entry without Exec".into(),
+            ),
+        }
+    }
+
+    if let Some(try_exec) = get("TryExec")
+        && find_executable(&try_exec).is_none()
+    {
+        push(
+            Severity::Warning,
+            line_of("TryExec"),
+            format!("TryExec {} not found, the entry will be ignored", try_exec),
+        );
+    }
+
+    if let Some(icon) = get("Icon") {
+        let found = if Path::new(&icon).is_absolute() {
+            Path::new(&icon).exists()
+        } else {
+            freedesktop_icons::lookup(&icon)
+                .with_cache()
+                .find()
+                .is_some()
+        };
+        if !found {
+            push(
+                Severity::Warning,
+                line_of("Icon"),
+                format!("icon {} not found", icon),
+            );
+        }
+    }
+
+    issues
+}
+
+/// Lines that are neither comments, groups nor entries, entries before the
+/// first group, and keys defined twice in a group.
+fn check_structure(file: &KeyFile, push: &mut impl FnMut(Severity, Option<usize>, String)) {
+    let mut group: Option<&str> = None;
+    let mut seen: IndexMap<&str, usize> = IndexMap::new();
+    for (i, line) in file.lines.iter().enumerate() {
+        match line {
+            Line::Group(name) => {
+                group = Some(name);
+                seen.clear();
+            }
+            Line::Other(raw) => {
+                let trimmed = raw.trim();
+                if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                    push(Severity::Error, Some(i), format!("invalid line {:?}", raw));
+                }
+            }
+            Line::Entry { key, .. } => {
+                if group.is_none() {
+                    push(
+                        Severity::Error,
+                        Some(i),
+                        "entry before the first group".into(),
+                    );
+                }
+                let valid = key.split('[').next().is_some_and(|k| {
+                    !k.is_empty() && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                });
+                if !valid {
+                    push(Severity::Error, Some(i), format!("invalid key {}", key));
+                }
+                if let Some(first) = seen.insert(key, i) {
+                    push(
+                        Severity::Error,
+                        Some(i),
+                        format!("duplicate key {}, first defined on line {}", key, first + 1),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Checks the unescaped `Exec` value, so its quoting is seen the way the
+/// launcher sees it.
+fn check_exec(
+    exec: &str,
+    line: Option<usize>,
+    push: &mut impl FnMut(Severity, Option<usize>, String),
+) {
+    // Field codes are not allowed inside quoted arguments.
+    let mut in_quotes = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_quotes => {
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            '%' => match chars.next() {
+                Some(code) if in_quotes && code != '%' => push(
+                    Severity::Error,
+                    line,
+                    format!("field code %{} inside a quoted argument", code),
+                ),
+                Some(code) if DEPRECATED_FIELD_CODES.contains(&code) => push(
+                    Severity::Warning,
+                    line,
+                    format!("deprecated field code %{}", code),
+                ),
+                Some(code) if !FIELD_CODES.contains(&code) => push(
+                    Severity::Error,
+                    line,
+                    format!("unknown field code %{}", code),
+                ),
+                Some(_) => {}
+                None => push(Severity::Error, line, "dangling % at end of Exec".into()),
+            },
+            _ => {}
+        }
+    }
+    if in_quotes {
+        push(Severity::Error, line, "unterminated quote in Exec".into());
+        return;
+    }
+
+    match shell_words::split(exec) {
+        Ok(words) if words.is_empty() => push(Severity::Error, line, "empty Exec".into()),
+        Ok(words) => {
+            if words.first().is_some_and(|p| find_executable(p).is_none()) {
+                push(
+                    Severity::Warning,
+                    line,
+                    format!("program {} not found", words[0]),
+                );
+            }
+        }
+        Err(e) => push(
+            Severity::Error,
+            line,
+            format!("invalid Exec quoting: {}", e),
+        ),
+    }
+}
+
+/// Resolves `program` like the shell would, through `$PATH` unless it is a
+/// path already.
+pub fn find_executable(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(program))
+            .find(|p| p.is_file())
+    })
+}
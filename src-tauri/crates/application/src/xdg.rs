@@ -0,0 +1,98 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+fn home() -> PathBuf {
+    env::var_os("HOME").map(PathBuf::from).unwrap_or_default()
+}
+
+fn dir_from_env(var: &str, fallback: &str) -> PathBuf {
+    match env::var_os(var) {
+        Some(v) if !v.is_empty() => PathBuf::from(v),
+        _ => home().join(fallback),
+    }
+}
+
+fn dirs_from_env(var: &str, fallback: &[&str]) -> Vec<PathBuf> {
+    match env::var(var) {
+        Ok(v) if !v.is_empty() => v
+            .split(':')
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        _ => fallback.iter().map(PathBuf::from).collect(),
+    }
+}
+
+pub fn config_home() -> PathBuf {
+    dir_from_env("XDG_CONFIG_HOME", ".config")
+}
+
+pub fn config_dirs() -> Vec<PathBuf> {
+    dirs_from_env("XDG_CONFIG_DIRS", &["/etc/xdg"])
+}
+
+pub fn cache_home() -> PathBuf {
+    dir_from_env("XDG_CACHE_HOME", ".cache")
+}
+
+pub fn data_home() -> PathBuf {
+    dir_from_env("XDG_DATA_HOME", ".local/share")
+}
+
+pub fn data_dirs() -> Vec<PathBuf> {
+    dirs_from_env("XDG_DATA_DIRS", &["/usr/local/share", "/usr/share"])
+}
+
+/// The `applications` directories below `$XDG_DATA_HOME` and
+/// `$XDG_DATA_DIRS`, most important first.
+pub fn application_dirs() -> Vec<PathBuf> {
+    std::iter::once(data_home())
+        .chain(data_dirs())
+        .map(|d| d.join("applications"))
+        .collect()
+}
+
+/// Desktop files below `dir`, recursively and sorted by path, with their
+/// desktop ids: `applications/kde/foo.desktop` has the id `kde-foo.desktop`.
+pub fn desktop_files(dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut files = Vec::new();
+    collect_desktop_files(dir, &mut files);
+    files.sort();
+    files
+        .into_iter()
+        .map(|path| (desktop_id(dir, &path), path))
+        .collect()
+}
+
+fn collect_desktop_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_desktop_files(&path, files);
+        } else if path.extension().is_some_and(|e| e == "desktop") {
+            files.push(path);
+        }
+    }
+}
+
+fn desktop_id(dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    relative
+        .iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Lowercased names from `$XDG_CURRENT_DESKTOP`, most specific first.
+pub fn current_desktops() -> Vec<String> {
+    env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_lowercase())
+        .collect()
+}
@@ -0,0 +1,88 @@
+//! Autostart entries in `$XDG_CONFIG_HOME/autostart` and
+//! `$XDG_CONFIG_DIRS/autostart`.
+
+mod common;
+
+use application::autostart;
+use common::Xdg;
+
+const APPLET: &str = concat!(
+    "[Desktop Entry]\n",
+    "Type=Application\n",
+    "Name=Network\n",
+    "Exec=nm-applet\n",
+    "X-GNOME-Autostart-enabled=true\n",
+);
+
+#[test]
+fn disables_system_entries_with_a_user_copy() {
+    let xdg = Xdg::new("autostart-disable");
+    xdg.dir.write("etc/autostart/nm-applet.desktop", APPLET);
+
+    let entry = autostart::get("nm-applet.desktop").unwrap();
+    assert!(entry.enabled && !entry.user && entry.system);
+
+    autostart::set_enabled("nm-applet.desktop", false).unwrap();
+    assert_eq!(
+        xdg.dir.read("config/autostart/nm-applet.desktop"),
+        format!("{}Hidden=true\n", APPLET)
+    );
+    assert_eq!(xdg.dir.read("etc/autostart/nm-applet.desktop"), APPLET);
+    let entry = autostart::get("nm-applet.desktop").unwrap();
+    assert!(!entry.enabled && entry.user && entry.system);
+
+    autostart::set_enabled("nm-applet.desktop", true).unwrap();
+    assert_eq!(xdg.dir.read("config/autostart/nm-applet.desktop"), APPLET);
+
+    // Removing the user copy falls back to the system entry.
+    autostart::remove("nm-applet.desktop").unwrap();
+    let entry = autostart::get("nm-applet.desktop").unwrap();
+    assert!(entry.enabled && !entry.user);
+    assert!(autostart::remove("nm-applet.desktop").is_err());
+}
+
+#[test]
+fn removes_user_only_entries() {
+    let xdg = Xdg::new("autostart-remove");
+    xdg.dir.write("config/autostart/notes.desktop", APPLET);
+    let entry = autostart::get("notes.desktop").unwrap();
+    assert!(entry.user && !entry.system);
+
+    autostart::remove("notes.desktop").unwrap();
+    assert!(autostart::get("notes.desktop").is_none());
+    assert!(autostart::list().is_empty());
+    assert!(autostart::set_enabled("notes.desktop", true).is_err());
+}
+
+#[test]
+fn lists_effective_entries() {
+    let xdg = Xdg::new("autostart-list");
+    xdg.dir.write("etc/autostart/nm-applet.desktop", APPLET);
+    xdg.dir.write(
+        "config/autostart/nm-applet.desktop",
+        &APPLET.replace("=true", "=false"),
+    );
+    xdg.dir.write(
+        "etc/autostart/other.desktop",
+        "[Desktop Entry]\nName=Other Desktop\nExec=other\nOnlyShowIn=KDE;\n",
+    );
+    xdg.dir.write(
+        "etc/autostart/test.desktop",
+        "[Desktop Entry]\nName=Test Desktop\nExec=test\nNotShowIn=GNOME;\n",
+    );
+    // Autostart directories are not searched recursively.
+    xdg.dir.write("etc/autostart/nested/deep.desktop", APPLET);
+
+    let entries: Vec<(String, bool, bool, bool)> = autostart::list()
+        .into_iter()
+        .map(|e| (e.id.clone(), e.user, e.enabled, e.active()))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            ("nm-applet.desktop".to_string(), true, false, false),
+            ("other.desktop".to_string(), false, true, false),
+            ("test.desktop".to_string(), false, true, true),
+        ]
+    );
+}
@@ -0,0 +1,104 @@
+//! The on-disk entry cache and telling when it is stale.
+
+mod common;
+
+use application::ApplicationService;
+use application::cache::{EntryCache, collect_stamps};
+use application::custom::CustomEntry;
+use common::Xdg;
+use std::fs::File;
+use std::time::{Duration, SystemTime};
+
+fn entry(name: &str) -> String {
+    format!(
+        "[Desktop Entry]\nType=Application\nName={}\nName[de]={} (de)\nExec=true\n",
+        name, name
+    )
+}
+
+fn touch(path: &std::path::Path, secs: u64) {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(time)
+        .unwrap();
+}
+
+#[test]
+fn stamps_change_with_the_files() {
+    let xdg = Xdg::new("cache-stamps");
+    let file = xdg
+        .dir
+        .write("share/first/applications/a.desktop", &entry("A"));
+    touch(&file, 1_000);
+    let stamps = collect_stamps();
+    assert_eq!(stamps, collect_stamps());
+    assert!(stamps.iter().any(|s| s.path == file));
+
+    // Rewriting a file in place leaves the directory mtime alone.
+    touch(&file, 2_000);
+    assert_ne!(collect_stamps(), stamps);
+
+    let stamps = collect_stamps();
+    xdg.dir
+        .write("share/second/applications/kde/b.desktop", &entry("B"));
+    assert_ne!(collect_stamps(), stamps);
+
+    let stamps = collect_stamps();
+    std::fs::remove_file(&file).unwrap();
+    assert_ne!(collect_stamps(), stamps);
+}
+
+#[test]
+fn round_trips_entries_with_their_translations() {
+    let xdg = Xdg::new("cache-store");
+    xdg.dir
+        .write("share/first/applications/a.desktop", &entry("A"));
+    let mut service = ApplicationService::new(&["de".to_string()]);
+    service.set_custom_entries(vec![CustomEntry {
+        name: "Custom".to_string(),
+        command: "true".to_string(),
+        ..Default::default()
+    }]);
+    service.save_cache().unwrap();
+    assert!(EntryCache::path().starts_with(xdg.dir.path().join("cache")));
+
+    let cache = EntryCache::load().unwrap();
+    assert_eq!(cache.stamps, service.stamps);
+    let entries: Vec<_> = cache.into_entries().collect();
+    // Custom entries come from the configuration, not the cache.
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id, "a.desktop");
+    assert_eq!(entries[0].name, "A (de)");
+    assert_eq!(entries[0].translations.names[""], "A");
+
+    std::fs::write(EntryCache::path(), b"{\"version\": 1}").unwrap();
+    assert!(EntryCache::load().is_none());
+}
+
+#[test]
+fn adopts_fresh_entries_keeping_live_settings() {
+    let xdg = Xdg::new("cache-adopt");
+    xdg.dir
+        .write("share/first/applications/a.desktop", &entry("A"));
+    let mut live = ApplicationService::new(&[]);
+    let stale = live.stamps.clone();
+
+    xdg.dir
+        .write("share/first/applications/b.desktop", &entry("B"));
+    let fresh = ApplicationService::new(&[]);
+    // Edits made while `fresh` was loading.
+    live.set_locales(&["de".to_string()]);
+    live.set_custom_entries(vec![CustomEntry {
+        name: "Custom".to_string(),
+        command: "true".to_string(),
+        ..Default::default()
+    }]);
+
+    live.adopt_entries(fresh);
+    assert_ne!(live.stamps, stale);
+    let names: Vec<&str> = live.entries.values().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["A (de)", "B (de)", "Custom"]);
+}
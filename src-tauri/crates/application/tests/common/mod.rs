@@ -0,0 +1,110 @@
+//! Scratch directories standing in for the XDG base directories.
+
+#![allow(dead_code)]
+
+use application::DesktopEntry;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// The XDG variables are process-wide, so the tests using them take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// A directory removed again when dropped.
+pub struct Scratch(PathBuf);
+
+impl Scratch {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("shiori-app-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `contents` to `relative`, creating its parents.
+    pub fn write(&self, relative: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    pub fn read(&self, relative: &str) -> String {
+        std::fs::read_to_string(self.0.join(relative)).unwrap()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// XDG base directories inside a scratch directory:
+///
+/// - `config` and `etc` for `$XDG_CONFIG_HOME` and `$XDG_CONFIG_DIRS`
+/// - `data` for `$XDG_DATA_HOME`
+/// - `share/first` and `share/second` for `$XDG_DATA_DIRS`
+/// - `cache` for `$XDG_CACHE_HOME`
+///
+/// The desktop is `Test`.
+pub struct Xdg {
+    pub dir: Scratch,
+    _serial: MutexGuard<'static, ()>,
+}
+
+impl Xdg {
+    pub fn new(name: &str) -> Self {
+        let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = Scratch::new(name);
+        let root = dir.path();
+        let dirs = |names: &[&str]| {
+            let paths: Vec<String> = names
+                .iter()
+                .map(|n| root.join(n).to_string_lossy().into_owned())
+                .collect();
+            paths.join(":")
+        };
+        // SAFETY: every test touching the environment holds `SERIAL`.
+        unsafe {
+            std::env::set_var("XDG_CONFIG_HOME", dirs(&["config"]));
+            std::env::set_var("XDG_CONFIG_DIRS", dirs(&["etc"]));
+            std::env::set_var("XDG_DATA_HOME", dirs(&["data"]));
+            std::env::set_var("XDG_DATA_DIRS", dirs(&["share/first", "share/second"]));
+            std::env::set_var("XDG_CACHE_HOME", dirs(&["cache"]));
+            std::env::set_var("XDG_CURRENT_DESKTOP", "Test");
+        }
+        Self {
+            dir,
+            _serial: serial,
+        }
+    }
+}
+
+/// A bare application entry.
+pub fn entry(id: &str) -> DesktopEntry {
+    DesktopEntry {
+        id: id.to_string(),
+        name: id.to_string(),
+        lower_name: id.to_lowercase(),
+        type_: Some("Application".to_string()),
+        categories: Vec::new(),
+        keywords: Vec::new(),
+        comment: None,
+        exec: None,
+        icon_name: None,
+        icon_path: None,
+        mime_types: Vec::new(),
+        actions: Vec::new(),
+        dbus_activatable: false,
+        no_display: false,
+        terminal: false,
+        working_dir: None,
+        path: PathBuf::from(format!("/usr/share/applications/{}.desktop", id)),
+        translations: Default::default(),
+        transliterations: Default::default(),
+    }
+}
@@ -0,0 +1,73 @@
+//! Custom entries from the configuration and their promotion to desktop
+//! files.
+
+mod common;
+
+use application::ApplicationService;
+use application::custom::CustomEntry;
+use application::exec::expand_exec;
+use common::Xdg;
+
+fn deploy() -> CustomEntry {
+    CustomEntry {
+        name: "Deploy: Staging".to_string(),
+        command: r#"make deploy ENV="staging" 2>&1 | tee ~/deploy\ 100%.log"#.to_string(),
+        comment: Some("Push\nto staging".to_string()),
+        keywords: vec!["ship".to_string(), "a;b".to_string()],
+        terminal: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn promotes_to_a_desktop_file() {
+    let xdg = Xdg::new("custom-promote");
+    let entry = deploy();
+    assert_eq!(entry.id(), "custom:deploy-staging");
+
+    let path = entry.promote().unwrap();
+    assert_eq!(
+        path,
+        xdg.dir
+            .path()
+            .join("data/applications/shiori-deploy-staging.desktop")
+    );
+    assert_eq!(
+        xdg.dir
+            .read("data/applications/shiori-deploy-staging.desktop"),
+        concat!(
+            "[Desktop Entry]\n",
+            "Type=Application\n",
+            "Name=Deploy: Staging\n",
+            "Comment=Push\\nto staging\n",
+            r#"Exec=sh -c "make deploy ENV=\\"staging\\" 2>&1 | tee ~/deploy\\\\ 100%%.log""#,
+            "\n",
+            "Keywords=ship;a\\;b;\n",
+            "Terminal=true\n",
+        )
+    );
+
+    // Read back, the file runs the same command.
+    let service = ApplicationService::new(&[]);
+    let promoted = service.get("shiori-deploy-staging.desktop").unwrap();
+    assert_eq!(promoted.comment.as_deref(), Some("Push\nto staging"));
+    assert_eq!(promoted.keywords, ["ship", "a;b"]);
+    assert!(promoted.terminal);
+    let args = expand_exec(promoted, promoted.exec.as_deref().unwrap(), &[]).unwrap();
+    assert_eq!(args, ["sh", "-c", &entry.command]);
+}
+
+#[test]
+fn promoted_files_replace_custom_entries() {
+    let _xdg = Xdg::new("custom-replace");
+    let mut service = ApplicationService::new(&[]);
+    service.set_custom_entries(vec![deploy()]);
+    assert!(service.get("custom:deploy-staging").is_some());
+
+    service
+        .promote_custom_entry("custom:deploy-staging")
+        .unwrap();
+    assert!(service.get("custom:deploy-staging").is_none());
+    assert!(service.get("shiori-deploy-staging.desktop").is_some());
+    assert!(service.promote_custom_entry("custom:missing").is_err());
+}
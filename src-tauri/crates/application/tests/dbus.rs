@@ -0,0 +1,254 @@
+//! D-Bus activation against a mock `org.freedesktop.Application` service on
+//! a private bus. Skipped when `dbus-daemon` is not installed.
+
+mod common;
+
+use application::dbus::DBusLauncher;
+use application::mime::MimeApps;
+use application::{ApplicationService, DesktopEntry};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use zbus::blocking::connection;
+use zbus::zvariant::OwnedValue;
+
+const APP_ID: &str = "org.example.Mock.desktop";
+
+/// A method call the mock application received.
+#[derive(Debug, Clone, PartialEq)]
+struct Call {
+    method: &'static str,
+    args: Vec<String>,
+    platform_data: HashMap<String, String>,
+}
+
+struct MockApplication {
+    calls: Arc<Mutex<Vec<Call>>>,
+}
+
+impl MockApplication {
+    fn record(
+        &self,
+        method: &'static str,
+        args: Vec<String>,
+        platform_data: HashMap<String, OwnedValue>,
+    ) {
+        let platform_data = platform_data
+            .into_iter()
+            .map(|(key, value)| (key, String::try_from(value).unwrap()))
+            .collect();
+        self.calls.lock().unwrap().push(Call {
+            method,
+            args,
+            platform_data,
+        });
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.Application")]
+impl MockApplication {
+    fn activate(&self, platform_data: HashMap<String, OwnedValue>) {
+        self.record("Activate", Vec::new(), platform_data);
+    }
+
+    fn open(&self, uris: Vec<String>, platform_data: HashMap<String, OwnedValue>) {
+        self.record("Open", uris, platform_data);
+    }
+
+    fn activate_action(
+        &self,
+        action: String,
+        parameter: Vec<OwnedValue>,
+        platform_data: HashMap<String, OwnedValue>,
+    ) {
+        assert!(parameter.is_empty());
+        self.record("ActivateAction", vec![action], platform_data);
+    }
+}
+
+/// A private `dbus-daemon`, killed when dropped.
+struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--print-address", "--nofork"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        Some(Self {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    fn connect(&self) -> connection::Builder<'static> {
+        connection::Builder::address(self.address.as_str()).unwrap()
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// The mock application on a private bus, and a launcher on the same bus.
+struct Mock {
+    calls: Arc<Mutex<Vec<Call>>>,
+    launcher: DBusLauncher,
+    /// Serves the mock for as long as it is open.
+    _service: zbus::blocking::Connection,
+    _bus: Bus,
+}
+
+impl Mock {
+    fn start() -> Option<Self> {
+        let bus = Bus::start()?;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let service = bus
+            .connect()
+            .name("org.example.Mock")
+            .unwrap()
+            .serve_at(
+                "/org/example/Mock",
+                MockApplication {
+                    calls: calls.clone(),
+                },
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let launcher = DBusLauncher::new(bus.connect().build().unwrap());
+        Some(Self {
+            calls,
+            launcher,
+            _service: service,
+            _bus: bus,
+        })
+    }
+
+    fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+fn activatable() -> DesktopEntry {
+    let mut entry = common::entry(APP_ID);
+    entry.dbus_activatable = true;
+    entry.actions = vec!["new-window".to_string()];
+    entry
+}
+
+fn token_data(token: &str) -> HashMap<String, String> {
+    HashMap::from([
+        ("activation-token".to_string(), token.to_string()),
+        ("desktop-startup-id".to_string(), token.to_string()),
+    ])
+}
+
+#[test]
+fn activates_and_opens_files() {
+    let Some(mock) = Mock::start() else {
+        return;
+    };
+    let launcher = &mock.launcher;
+    let entry = activatable();
+
+    launcher.activate(&entry, &[], Some("token-1")).unwrap();
+    launcher
+        .activate(&entry, &["/tmp/a b.txt", "https://example.com"], None)
+        .unwrap();
+    assert_eq!(
+        mock.calls(),
+        [
+            Call {
+                method: "Activate",
+                args: Vec::new(),
+                platform_data: token_data("token-1"),
+            },
+            Call {
+                method: "Open",
+                args: vec![
+                    "file:///tmp/a%20b.txt".to_string(),
+                    "https://example.com".to_string()
+                ],
+                platform_data: HashMap::new(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn launches_entries_and_actions_through_the_service() {
+    let Some(mock) = Mock::start() else {
+        return;
+    };
+    let launcher = &mock.launcher;
+    let service = ApplicationService {
+        locales: Vec::new(),
+        entries: IndexMap::from([(APP_ID.to_string(), activatable())]),
+        mime_apps: MimeApps::default(),
+        custom_entries: Vec::new(),
+        stamps: Vec::new(),
+    };
+
+    service
+        .launch_with_token(Some(launcher), APP_ID, &[], Some("token-2"))
+        .unwrap();
+    service
+        .launch_action(Some(launcher), APP_ID, "new-window", Some("token-3"))
+        .unwrap();
+    assert!(
+        service
+            .launch_action(Some(launcher), APP_ID, "missing", None)
+            .is_err()
+    );
+    assert_eq!(
+        mock.calls(),
+        [
+            Call {
+                method: "Activate",
+                args: Vec::new(),
+                platform_data: token_data("token-2"),
+            },
+            Call {
+                method: "ActivateAction",
+                args: vec!["new-window".to_string()],
+                platform_data: token_data("token-3"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn fails_without_exec_when_nobody_owns_the_name() {
+    let Some(bus) = Bus::start() else {
+        return;
+    };
+    let launcher = DBusLauncher::new(bus.connect().build().unwrap());
+    let service = ApplicationService {
+        locales: Vec::new(),
+        entries: IndexMap::from([(APP_ID.to_string(), activatable())]),
+        mime_apps: MimeApps::default(),
+        custom_entries: Vec::new(),
+        stamps: Vec::new(),
+    };
+
+    assert!(
+        service
+            .launch_with_token(Some(&launcher), APP_ID, &[], None)
+            .is_err()
+    );
+}
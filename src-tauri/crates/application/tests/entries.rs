@@ -0,0 +1,81 @@
+//! Loading desktop entries from the XDG application directories.
+
+mod common;
+
+use application::ApplicationService;
+use common::Xdg;
+
+#[test]
+fn loads_each_file_once_with_every_translation() {
+    let xdg = Xdg::new("entries-load");
+    xdg.dir.write(
+        "share/first/applications/editor.desktop",
+        concat!(
+            "[Desktop Entry]\n",
+            "Type=Application\n",
+            "Name=Text Editor\n",
+            "Name[de]=Texteditor\n",
+            "Name[ja]=テキスト\\sエディター\n",
+            "Comment=Edit\\ttext\n",
+            "Keywords=notes;plain\\;text;\n",
+            "Keywords[de]=Notizen;\n",
+            "Exec=editor %F\n",
+            "MimeType=text/plain;\n",
+            "Categories=Utility;TextEditor;\n",
+        ),
+    );
+
+    let service = ApplicationService::new(&["de_DE".to_string()]);
+    let entry = service.get("editor.desktop").unwrap();
+    assert_eq!(entry.name, "Texteditor");
+    assert_eq!(entry.lower_name, "texteditor");
+    assert_eq!(entry.comment.as_deref(), Some("Edit\ttext"));
+    assert_eq!(entry.keywords, ["Notizen"]);
+    assert_eq!(entry.translations.names["ja"], "テキスト エディター");
+    assert_eq!(entry.translations.keywords[""], ["notes", "plain;text"]);
+    assert_eq!(entry.categories, ["Utility", "TextEditor"]);
+    assert_eq!(entry.mime_types, ["text/plain"]);
+
+    let mut service = service;
+    service.set_locales(&[]);
+    let entry = service.get("editor.desktop").unwrap();
+    assert_eq!(entry.name, "Text Editor");
+    assert_eq!(entry.keywords, ["notes", "plain;text"]);
+}
+
+#[test]
+fn follows_directory_precedence() {
+    let xdg = Xdg::new("entries-precedence");
+    let entry = |name: &str| format!("[Desktop Entry]\nType=Application\nName={}\n", name);
+    xdg.dir
+        .write("data/applications/user.desktop", &entry("User"));
+    xdg.dir
+        .write("share/first/applications/user.desktop", &entry("Shadowed"));
+    xdg.dir
+        .write("share/first/applications/kde/tool.desktop", &entry("Tool"));
+    xdg.dir.write(
+        "share/second/applications/kde-tool.desktop",
+        &entry("Older Tool"),
+    );
+    xdg.dir.write(
+        "data/applications/gone.desktop",
+        "[Desktop Entry]\nType=Application\nName=Gone\nHidden=true\n",
+    );
+    xdg.dir
+        .write("share/first/applications/gone.desktop", &entry("Removed"));
+    xdg.dir.write(
+        "share/first/applications/broken.desktop",
+        "[Other]\nName=x\n",
+    );
+
+    let service = ApplicationService::new(&[]);
+    let names: Vec<(&str, &str)> = service
+        .entries
+        .values()
+        .map(|e| (e.id.as_str(), e.name.as_str()))
+        .collect();
+    assert_eq!(
+        names,
+        [("kde-tool.desktop", "Tool"), ("user.desktop", "User")]
+    );
+}
@@ -0,0 +1,110 @@
+//! `Exec` field code expansion.
+
+mod common;
+
+use application::exec::{expand_exec, expand_instances, quote_args, wrap_in_terminal};
+
+#[test]
+fn expands_field_codes() {
+    let mut entry = common::entry("editor");
+    entry.name = "Text Editor".to_string();
+    entry.icon_name = Some("accessories-text-editor".to_string());
+
+    let args = expand_exec(
+        &entry,
+        "editor %i --title=%c --from=%k 100%% %d %n %f",
+        &["file:///tmp/a%20b.txt"],
+    )
+    .unwrap();
+    assert_eq!(
+        args,
+        [
+            "editor",
+            "--icon",
+            "accessories-text-editor",
+            "--title=Text Editor",
+            "--from=/usr/share/applications/editor.desktop",
+            "100%",
+            "/tmp/a b.txt",
+        ]
+    );
+}
+
+#[test]
+fn passes_urls_or_paths() {
+    let entry = common::entry("viewer");
+    let targets = ["https://example.com/a", "file:///tmp/b", "notes:draft.txt"];
+
+    let urls = expand_exec(&entry, "viewer %U", &targets).unwrap();
+    assert_eq!(urls[1..], targets);
+    let paths = expand_exec(&entry, "viewer %F", &targets).unwrap();
+    assert_eq!(
+        paths[1..],
+        ["https://example.com/a", "/tmp/b", "notes:draft.txt"]
+    );
+}
+
+#[test]
+fn starts_one_instance_per_target() {
+    let entry = common::entry("viewer");
+    let targets = ["/tmp/a", "/tmp/b"];
+
+    let instances = expand_instances(&entry, "viewer --file=%f", &targets).unwrap();
+    assert_eq!(
+        instances,
+        [["viewer", "--file=/tmp/a"], ["viewer", "--file=/tmp/b"]]
+    );
+    let instances = expand_instances(&entry, "viewer %u", &targets).unwrap();
+    assert_eq!(instances, [["viewer", "/tmp/a"], ["viewer", "/tmp/b"]]);
+
+    let instances = expand_instances(&entry, "viewer %F", &targets).unwrap();
+    assert_eq!(instances, [["viewer", "/tmp/a", "/tmp/b"]]);
+    // Without a field code the targets cannot be passed at all.
+    let instances = expand_instances(&entry, "viewer 100%%f", &targets).unwrap();
+    assert_eq!(instances, [["viewer", "100%f"]]);
+}
+
+#[test]
+fn rejects_empty_and_unbalanced_lines() {
+    let entry = common::entry("broken");
+    assert!(expand_exec(&entry, "%f", &[]).is_err());
+    assert!(expand_exec(&entry, "broken \"%f", &[]).is_err());
+}
+
+#[test]
+fn quotes_reserved_characters() {
+    let args = [
+        "sh",
+        "-c",
+        r#"echo "$HOME" `date` \ ~; a|b"#,
+        "",
+        "plain-arg",
+    ];
+    let exec = quote_args(&args);
+    assert_eq!(
+        exec,
+        r#"sh -c "echo \"\$HOME\" \`date\` \\ ~; a|b" "" plain-arg"#
+    );
+    // The quoted line reads back as the same arguments.
+    let entry = common::entry("shell");
+    assert_eq!(expand_exec(&entry, &exec, &[]).unwrap(), args);
+}
+
+#[test]
+fn wraps_commands_in_a_terminal() {
+    let _xdg = common::Xdg::new("exec-terminal");
+    let args = || vec!["htop".to_string(), "-d".to_string(), "10".to_string()];
+
+    // SAFETY: every test touching the environment holds the `Xdg` lock.
+    unsafe { std::env::set_var("TERMINAL", "kitty --single-instance") };
+    assert_eq!(
+        wrap_in_terminal(args()),
+        ["kitty", "--single-instance", "-e", "htop", "-d", "10"]
+    );
+    unsafe { std::env::set_var("TERMINAL", "") };
+    assert_eq!(
+        wrap_in_terminal(args()),
+        ["x-terminal-emulator", "-e", "htop", "-d", "10"]
+    );
+    unsafe { std::env::remove_var("TERMINAL") };
+}
@@ -0,0 +1,70 @@
+//! Key file parsing and writing.
+
+mod common;
+
+use application::keyfile::{KeyFile, join_list, split_list, write_atomic, write_with_backup};
+use common::Scratch;
+
+#[test]
+fn unescapes_values() {
+    let file = KeyFile::parse(concat!(
+        "[Desktop Entry]\n",
+        r"Name=\sTwo\tColumns\nand a \\ backslash",
+        "\n",
+        r#"Exec=sh -c "echo \\\\""#,
+        "\n",
+        r"Keywords=a\;b;c\sd;e\\;",
+        "\n",
+    ));
+    assert_eq!(
+        file.get("Desktop Entry", "Name").unwrap(),
+        " Two\tColumns\nand a \\ backslash"
+    );
+    assert_eq!(
+        file.get("Desktop Entry", "Exec").unwrap(),
+        "sh -c \"echo \\\\\""
+    );
+    assert_eq!(
+        file.get_list("Desktop Entry", "Keywords").unwrap(),
+        ["a;b", "c d", "e\\"]
+    );
+    assert_eq!(file.get("Desktop Entry", "Missing"), None);
+}
+
+#[test]
+fn joins_lists_reversibly() {
+    let items = ["a;b", " lead", "back\\slash", "line\nbreak"];
+    assert_eq!(split_list(&join_list(&items)), items);
+}
+
+#[test]
+fn keeps_unmodified_lines_verbatim() {
+    let input = "[Desktop Entry]\nName = Editor\nExec =editor %F\n  Icon=  editor\n";
+    let mut file = KeyFile::parse(input);
+    assert_eq!(file.get("Desktop Entry", "Name").unwrap(), "Editor");
+    assert_eq!(file.to_string(), input);
+
+    file.set("Desktop Entry", "Exec", "editor --new %F");
+    assert_eq!(
+        file.to_string(),
+        "[Desktop Entry]\nName = Editor\nExec=editor --new %F\n  Icon=  editor\n"
+    );
+}
+
+#[test]
+fn backs_up_only_when_asked() {
+    let dir = Scratch::new("keyfile-backup");
+    let path = dir.write("file", "old\n");
+
+    write_atomic(&path, b"new\n").unwrap();
+    assert_eq!(dir.read("file"), "new\n");
+    let names: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["file"]);
+
+    write_with_backup(&path, b"newer\n").unwrap();
+    assert_eq!(dir.read("file"), "newer\n");
+    assert_eq!(dir.read("file.bak"), "new\n");
+}
@@ -0,0 +1,46 @@
+//! Picking translations for the preferred locales.
+
+use application::locale::lookup;
+use indexmap::IndexMap;
+
+fn values(locales: &[&str]) -> IndexMap<String, String> {
+    locales
+        .iter()
+        .map(|l| (l.to_string(), l.to_string()))
+        .collect()
+}
+
+fn pick(values: &IndexMap<String, String>, preferred: &[&str]) -> Option<String> {
+    let preferred: Vec<String> = preferred.iter().map(|l| l.to_string()).collect();
+    lookup(values, &preferred).cloned()
+}
+
+#[test]
+fn falls_back_from_country_and_modifier_to_language() {
+    let all = ["", "sr", "sr@latin", "sr_RS", "sr_RS@latin"];
+    let locale = ["sr_RS.UTF-8@latin"];
+    let expected = ["sr_RS@latin", "sr_RS", "sr@latin", "sr", ""];
+    for (i, want) in expected.iter().enumerate() {
+        let available = values(&all[..all.len() - i]);
+        assert_eq!(pick(&available, &locale).as_deref(), Some(*want));
+    }
+}
+
+#[test]
+fn skips_variants_that_do_not_apply() {
+    let available = values(&["", "de", "de@euro", "pt_BR"]);
+    assert_eq!(pick(&available, &["de_AT"]).as_deref(), Some("de"));
+    assert_eq!(pick(&available, &["pt_PT"]).as_deref(), Some(""));
+    assert_eq!(pick(&available, &["pt"]).as_deref(), Some(""));
+    assert_eq!(pick(&IndexMap::new(), &["de"]), None);
+}
+
+#[test]
+fn prefers_earlier_locales() {
+    let available = values(&["", "fr", "ja_JP"]);
+    assert_eq!(
+        pick(&available, &["ko", "ja_JP", "fr"]).as_deref(),
+        Some("ja_JP")
+    );
+    assert_eq!(pick(&available, &["fr_CA", "ja"]).as_deref(), Some("fr"));
+}
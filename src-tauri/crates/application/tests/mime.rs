@@ -0,0 +1,190 @@
+//! MIME association lookup across `mimeapps.list` files and
+//! `mimeinfo.cache`.
+
+mod common;
+
+use application::mime::{
+    AssociationChange, MimeApps, mime_type_for, mimeapps_paths, update_user_mimeapps,
+};
+use common::Xdg;
+
+fn ids(ids: &[&str]) -> Vec<String> {
+    ids.iter().map(|id| id.to_string()).collect()
+}
+
+#[test]
+fn lists_mimeapps_files_in_precedence_order() {
+    let xdg = Xdg::new("mime-paths");
+    let root = xdg.dir.path();
+    let expected: Vec<_> = [
+        "config/test-mimeapps.list",
+        "config/mimeapps.list",
+        "etc/test-mimeapps.list",
+        "etc/mimeapps.list",
+        "data/applications/test-mimeapps.list",
+        "data/applications/mimeapps.list",
+        "share/first/applications/test-mimeapps.list",
+        "share/first/applications/mimeapps.list",
+        "share/second/applications/test-mimeapps.list",
+        "share/second/applications/mimeapps.list",
+    ]
+    .iter()
+    .map(|p| root.join(p))
+    .collect();
+    assert_eq!(mimeapps_paths(), expected);
+}
+
+#[test]
+fn resolves_associations_and_defaults() {
+    let xdg = Xdg::new("mime-resolve");
+    xdg.dir.write(
+        "config/test-mimeapps.list",
+        "[Default Applications]\ntext/plain=missing.desktop;desktop.desktop;\n",
+    );
+    xdg.dir.write(
+        "config/mimeapps.list",
+        "[Default Applications]\ntext/plain=user.desktop;\n\
+         [Added Associations]\ntext/plain=user.desktop;\n\
+         [Removed Associations]\ntext/plain=cached.desktop;\ntext/html=cached.desktop;\n",
+    );
+    xdg.dir.write(
+        "share/first/applications/mimeapps.list",
+        "[Default Applications]\ntext/html=cached.desktop;\n\
+         [Added Associations]\ntext/plain=cached.desktop;system.desktop;\n",
+    );
+    xdg.dir.write(
+        "share/first/applications/mimeinfo.cache",
+        "[MIME Cache]\ntext/plain=first.desktop;cached.desktop;\n",
+    );
+    xdg.dir.write(
+        "share/second/applications/mimeinfo.cache",
+        "[MIME Cache]\ntext/plain=second.desktop;first.desktop;\n",
+    );
+
+    let apps = MimeApps::load();
+    let associations = apps.associations("text/plain", ["own.desktop", "cached.desktop"]);
+    // Lists first, then the caches in XDG order, then `MimeType=`; removed
+    // ids only hide associations from less important files.
+    assert_eq!(
+        associations,
+        ids(&[
+            "user.desktop",
+            "system.desktop",
+            "first.desktop",
+            "second.desktop",
+            "own.desktop",
+        ])
+    );
+
+    let installed = |id: &str| id != "missing.desktop";
+    assert_eq!(
+        apps.default_for("text/plain", &associations, installed),
+        Some("desktop.desktop".to_string())
+    );
+    // A default removed by a more important file is skipped.
+    assert_eq!(apps.default_for("text/html", &[], installed), None);
+    assert_eq!(
+        apps.default_for("image/png", &ids(&["own.desktop"]), installed),
+        Some("own.desktop".to_string())
+    );
+}
+
+#[test]
+fn detects_mime_types_of_paths_and_urls() {
+    assert_eq!(
+        mime_type_for("https://example.com"),
+        "x-scheme-handler/https"
+    );
+    assert_eq!(
+        mime_type_for("mailto:someone@example.com"),
+        "x-scheme-handler/mailto"
+    );
+    assert_eq!(mime_type_for("file:///tmp/a.png"), "image/png");
+    // A colon in a relative path does not make it a URL.
+    assert_eq!(mime_type_for("notes:draft.txt"), "text/plain");
+}
+
+const FIXTURE: &str = include_str!("fixtures/mimeapps.list");
+
+#[test]
+fn sets_a_default_preserving_the_rest_of_the_file() {
+    let xdg = Xdg::new("mime-default");
+    xdg.dir.write("config/mimeapps.list", FIXTURE);
+
+    update_user_mimeapps("text/plain", "gedit.desktop", AssociationChange::SetDefault).unwrap();
+    assert_eq!(
+        xdg.dir.read("config/mimeapps.list"),
+        "# Written by hand, keep this comment.\n\
+         [Default Applications]\n\
+         text/plain=gedit.desktop;vim.desktop;\n\
+         image/png=eog.desktop;\n\
+         \n\
+         [X-Vendor Extension]\n\
+         zeta=1\n\
+         alpha=2\n\
+         \n\
+         [Added Associations]\n\
+         text/plain=vim.desktop;gedit.desktop;\n\
+         # trailing comment\n"
+    );
+    assert_eq!(xdg.dir.read("config/mimeapps.list.bak"), FIXTURE);
+}
+
+#[test]
+fn adds_and_removes_associations() {
+    let xdg = Xdg::new("mime-edit");
+    xdg.dir.write("config/mimeapps.list", FIXTURE);
+
+    update_user_mimeapps("text/html", "firefox.desktop", AssociationChange::Add).unwrap();
+    update_user_mimeapps("text/plain", "vim.desktop", AssociationChange::Remove).unwrap();
+    assert_eq!(
+        xdg.dir.read("config/mimeapps.list"),
+        "# Written by hand, keep this comment.\n\
+         [Default Applications]\n\
+         image/png=eog.desktop;\n\
+         \n\
+         [X-Vendor Extension]\n\
+         zeta=1\n\
+         alpha=2\n\
+         \n\
+         [Added Associations]\n\
+         text/plain=gedit.desktop;\n\
+         text/html=firefox.desktop;\n\
+         # trailing comment\n\
+         \n\
+         [Removed Associations]\n\
+         text/plain=vim.desktop;\n"
+    );
+    // The backup is the file before the last change.
+    assert!(
+        xdg.dir
+            .read("config/mimeapps.list.bak")
+            .contains("text/html=firefox.desktop;")
+    );
+
+    update_user_mimeapps("text/plain", "vim.desktop", AssociationChange::Add).unwrap();
+    let file = xdg.dir.read("config/mimeapps.list");
+    assert!(file.contains("text/plain=gedit.desktop;vim.desktop;\n"));
+    assert!(!file.contains("[Removed Associations]\ntext/plain"));
+}
+
+#[test]
+fn creates_a_missing_file() {
+    let xdg = Xdg::new("mime-create");
+
+    update_user_mimeapps(
+        "x-scheme-handler/https",
+        "firefox.desktop",
+        AssociationChange::SetDefault,
+    )
+    .unwrap();
+    assert_eq!(
+        xdg.dir.read("config/mimeapps.list"),
+        "[Default Applications]\n\
+         x-scheme-handler/https=firefox.desktop;\n\
+         \n\
+         [Added Associations]\n\
+         x-scheme-handler/https=firefox.desktop;\n"
+    );
+    assert!(!xdg.dir.path().join("config/mimeapps.list.bak").exists());
+}
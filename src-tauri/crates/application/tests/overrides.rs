@@ -0,0 +1,123 @@
+//! User overrides of desktop entries in `$XDG_DATA_HOME/applications`.
+
+mod common;
+
+use application::ApplicationService;
+use application::overrides::{EntryChanges, override_path, remove_override, write_override};
+use common::Xdg;
+
+const SYSTEM: &str = concat!(
+    "[Desktop Entry]\n",
+    "Type=Application\n",
+    "Name=Viewer\n",
+    "Name[de]=Betrachter\n",
+    "Exec=viewer %f\n",
+    "# Not registered, which the override has to put up with.\n",
+    "Categories=Graphics;Vendor;\n",
+    "\n",
+    "[Desktop Action new]\n",
+    "Name=New\n",
+    "Exec=viewer --new\n",
+);
+
+#[test]
+fn writes_overrides_despite_errors_in_the_source() {
+    let xdg = Xdg::new("overrides-write");
+    let source = xdg
+        .dir
+        .write("share/first/applications/viewer.desktop", SYSTEM);
+    let changes = EntryChanges {
+        name: Some("My Viewer".to_string()),
+        exec: Some(r#"viewer --dir "C:\\x" %f"#.to_string()),
+        keywords: Some(vec!["photo".to_string(), "a;b".to_string()]),
+        hidden: Some(true),
+        ..Default::default()
+    };
+
+    let path = write_override("viewer.desktop", &source, &changes, &["de".to_string()]).unwrap();
+    assert_eq!(path, override_path("viewer.desktop"));
+    assert_eq!(
+        xdg.dir.read("data/applications/viewer.desktop"),
+        concat!(
+            "[Desktop Entry]\n",
+            "Type=Application\n",
+            "Name=My Viewer\n",
+            "Name[de]=My Viewer\n",
+            r#"Exec=viewer --dir "C:\\\\x" %f"#,
+            "\n",
+            "# Not registered, which the override has to put up with.\n",
+            "Categories=Graphics;Vendor;\n",
+            "Keywords=photo;a\\;b;\n",
+            "NoDisplay=true\n",
+            "\n",
+            "[Desktop Action new]\n",
+            "Name=New\n",
+            "Exec=viewer --new\n",
+        )
+    );
+    assert_eq!(
+        xdg.dir.read("share/first/applications/viewer.desktop"),
+        SYSTEM
+    );
+}
+
+#[test]
+fn rejects_changes_that_introduce_errors() {
+    let xdg = Xdg::new("overrides-invalid");
+    let source = xdg
+        .dir
+        .write("share/first/applications/viewer.desktop", SYSTEM);
+    let changes = EntryChanges {
+        exec: Some(r#"viewer "unterminated"#.to_string()),
+        ..Default::default()
+    };
+
+    let error = write_override("viewer.desktop", &source, &changes, &[]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(!error.to_string().contains("Vendor"), "{}", error);
+    assert!(!override_path("viewer.desktop").exists());
+}
+
+#[test]
+fn removes_overrides_only_with_a_system_entry() {
+    let xdg = Xdg::new("overrides-remove");
+    xdg.dir
+        .write("share/second/applications/kde/viewer.desktop", SYSTEM);
+    xdg.dir
+        .write("data/applications/kde-viewer.desktop", SYSTEM);
+    xdg.dir.write("data/applications/mine.desktop", SYSTEM);
+
+    remove_override("kde-viewer.desktop").unwrap();
+    assert!(!override_path("kde-viewer.desktop").exists());
+
+    let error = remove_override("mine.desktop").unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+    assert!(override_path("mine.desktop").exists());
+}
+
+#[test]
+fn updates_and_resets_entries() {
+    let xdg = Xdg::new("overrides-service");
+    xdg.dir
+        .write("share/first/applications/viewer.desktop", SYSTEM);
+    let mut service = ApplicationService::new(&[]);
+
+    let changes = EntryChanges {
+        name: Some("Renamed".to_string()),
+        ..Default::default()
+    };
+    service.update_entry("viewer.desktop", &changes).unwrap();
+    let entry = service.get("viewer.desktop").unwrap();
+    assert_eq!(entry.name, "Renamed");
+    assert_eq!(entry.path, override_path("viewer.desktop"));
+
+    service.reset_entry("viewer.desktop").unwrap();
+    let entry = service.get("viewer.desktop").unwrap();
+    assert_eq!(entry.name, "Viewer");
+    assert_eq!(
+        entry.path,
+        xdg.dir
+            .path()
+            .join("share/first/applications/viewer.desktop")
+    );
+}
@@ -0,0 +1,90 @@
+//! Pinyin and romaji forms of CJK names.
+
+mod common;
+
+use application::transliterate::{Transliteration, transliterate};
+
+fn texts(name: &str) -> Vec<String> {
+    transliterate(name).into_iter().map(|t| t.text).collect()
+}
+
+#[test]
+fn leaves_latin_names_alone() {
+    assert!(transliterate("Firefox").is_empty());
+    assert!(transliterate("").is_empty());
+}
+
+#[test]
+fn offers_every_reading_of_polyphonic_characters() {
+    let forms = texts("音乐");
+    // The first reading of every character comes first.
+    assert_eq!(forms[..2], ["yinyue", "yy"]);
+    assert!(forms.contains(&"yinle".to_string()), "{:?}", forms);
+    assert!(forms.contains(&"yl".to_string()), "{:?}", forms);
+
+    let forms = texts("银行");
+    assert!(forms.contains(&"yinxing".to_string()), "{:?}", forms);
+    assert!(forms.contains(&"yinhang".to_string()), "{:?}", forms);
+}
+
+#[test]
+fn reads_kanji_words_in_chinese_and_japanese() {
+    let forms = texts("端末");
+    assert_eq!(forms, ["duanmo", "dm", "tanmatsu", "tm"]);
+}
+
+#[test]
+fn romanizes_kana() {
+    // The long vowel may be typed or left out.
+    assert_eq!(texts("ターミナル"), ["taaminaru", "tmnr", "taminaru"]);
+    // Contracted sounds and the sokuon belong to their syllables.
+    assert_eq!(texts("きゃっと"), ["kyatto", "kt"]);
+    assert_eq!(texts("しゅっちょう"), ["shutchou", "scu"]);
+    assert_eq!(texts("ヴァイオリン"), ["vaiorin", "viorn"]);
+}
+
+#[test]
+fn keeps_latin_parts_of_mixed_names() {
+    assert_eq!(texts("文件 Manager"), ["wenjian manager", "wj manager"]);
+}
+
+#[test]
+fn maps_matches_back_onto_the_name() {
+    let forms = transliterate("ターミナル");
+    let full = &forms[0];
+    assert_eq!(full.sources, [0, 0, 1, 2, 2, 3, 3, 4, 4]);
+    // "mina" in "taaminaru" comes from ミ and ナ.
+    assert_eq!(full.source_indices(&[3, 4, 5, 6]), [2, 3]);
+    // Indices past the end of the form are dropped.
+    assert_eq!(full.source_indices(&[0, 42]), [0]);
+
+    let initials = Transliteration {
+        text: "tmnr".to_string(),
+        sources: vec![0, 2, 3, 4],
+    };
+    assert_eq!(forms[1], initials);
+    assert_eq!(initials.source_indices(&[1, 2]), [2, 3]);
+}
+
+#[test]
+fn entries_transliterate_every_name_once() {
+    let mut entry = common::entry("music.desktop");
+    entry.name = "Music".to_string();
+    entry.translations.names = [("", "Music"), ("zh_CN", "音乐"), ("ja", "ミュージック")]
+        .map(|(locale, name)| (locale.to_string(), name.to_string()))
+        .into_iter()
+        .collect();
+    entry.transliterate();
+
+    assert!(entry.transliterations_of("Music").is_empty());
+    assert_eq!(entry.transliterations_of("音乐")[0].text, "yinyue");
+    assert_eq!(
+        entry.transliterations_of("ミュージック")[0].text,
+        "myuujikku"
+    );
+
+    // Localizing only changes which name is displayed.
+    entry.localize(&["zh_CN".to_string()]);
+    assert_eq!(entry.name, "音乐");
+    assert_eq!(entry.transliterations_of(&entry.name)[0].text, "yinyue");
+}
@@ -0,0 +1,155 @@
+//! Desktop file validation, one fixture per check.
+
+mod common;
+
+use application::validate::{Severity, validate_all, validate_file};
+use common::Xdg;
+use std::path::Path;
+
+fn issues(fixture: &str) -> Vec<(Severity, Option<usize>, String)> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/validate")
+        .join(fixture);
+    validate_file(&path)
+        .into_iter()
+        .map(|issue| {
+            assert_eq!(issue.path, path);
+            (issue.severity, issue.line, issue.message)
+        })
+        .collect()
+}
+
+fn issue(severity: Severity, line: usize, message: &str) -> (Severity, Option<usize>, String) {
+    (severity, Some(line), message.to_string())
+}
+
+#[test]
+fn reports_missing_required_keys() {
+    assert_eq!(
+        issues("missing-keys.desktop"),
+        [
+            issue(Severity::Error, 2, "missing required key Type"),
+            issue(Severity::Error, 2, "missing required key Name"),
+        ]
+    );
+    // `\s` unescapes to a blank name.
+    assert_eq!(
+        issues("link.desktop"),
+        [
+            issue(Severity::Error, 1, "missing required key Name"),
+            issue(Severity::Error, 1, "Link entry without URL"),
+        ]
+    );
+}
+
+#[test]
+fn reports_unregistered_categories() {
+    assert_eq!(
+        issues("categories.desktop"),
+        [
+            issue(
+                Severity::Error,
+                6,
+                "NoDisplay must be true or false, got yes"
+            ),
+            issue(Severity::Error, 5, "unregistered category Gadgets"),
+            issue(
+                Severity::Hint,
+                5,
+                "no main category, the entry may not appear in menus"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn checks_exec_quoting_and_field_codes() {
+    assert_eq!(
+        issues("exec.desktop"),
+        [
+            issue(Severity::Error, 4, "field code %f inside a quoted argument"),
+            issue(Severity::Warning, 4, "deprecated field code %d"),
+            issue(Severity::Error, 4, "unknown field code %z"),
+            issue(Severity::Error, 4, "field code %u inside a quoted argument"),
+            issue(Severity::Error, 4, "unterminated quote in Exec"),
+        ]
+    );
+    // Quotes escaped for the key file and again for Exec are fine.
+    assert_eq!(issues("escaped-exec.desktop"), []);
+}
+
+#[test]
+fn warns_about_deprecated_keys() {
+    assert_eq!(
+        issues("deprecated.desktop"),
+        [
+            issue(Severity::Warning, 2, "deprecated key Encoding"),
+            issue(Severity::Warning, 7, "deprecated key TerminalOptions"),
+            issue(Severity::Warning, 5, "deprecated field code %m"),
+            issue(
+                Severity::Warning,
+                8,
+                "TryExec shiori-no-such-program not found, the entry will be ignored"
+            ),
+            issue(Severity::Warning, 9, "icon /nonexistent/icon.png not found"),
+        ]
+    );
+}
+
+#[test]
+fn checks_the_file_structure() {
+    assert_eq!(
+        issues("structure.desktop"),
+        [
+            issue(Severity::Error, 1, "entry before the first group"),
+            issue(
+                Severity::Error,
+                5,
+                "duplicate key Name, first defined on line 4"
+            ),
+            issue(Severity::Error, 8, "invalid key Bad_Key"),
+            issue(Severity::Error, 9, "invalid line \"not an entry\""),
+        ]
+    );
+}
+
+#[test]
+fn reports_duplicate_ids_by_precedence() {
+    let xdg = Xdg::new("validate-duplicates");
+    let entry = "[Desktop Entry]\nType=Application\nName=App\nExec=sh\nCategories=Utility;\n";
+    let user = xdg.dir.write("data/applications/kde-app.desktop", entry);
+    let nested = xdg
+        .dir
+        .write("share/first/applications/kde/app.desktop", entry);
+    let flat = xdg
+        .dir
+        .write("share/first/applications/kde-app.desktop", entry);
+    let older = xdg
+        .dir
+        .write("share/second/applications/kde-app.desktop", entry);
+
+    let issues: Vec<_> = validate_all()
+        .into_iter()
+        .map(|issue| (issue.severity, issue.path, issue.message))
+        .collect();
+    let overridden = format!(
+        "desktop id kde-app.desktop is overridden by {}",
+        user.display()
+    );
+    assert_eq!(
+        issues,
+        [
+            // `kde/` sorts first, so the flat file is the second definition.
+            (Severity::Hint, nested.clone(), overridden.clone()),
+            (
+                Severity::Error,
+                flat,
+                format!(
+                    "desktop id kde-app.desktop is also defined by {}",
+                    nested.display()
+                ),
+            ),
+            (Severity::Hint, older, overridden),
+        ]
+    );
+}
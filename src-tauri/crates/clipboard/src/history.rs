@@ -0,0 +1,544 @@
+use crate::key::{Key, KeySource};
+use crate::watcher::{ClipboardEvent, Selection};
+use anyhow::{Context, Result};
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use image::ImageFormat;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Bumped whenever the schema changes.
+const SCHEMA_VERSION: i32 = 1;
+
+/// Longest side of generated thumbnails, in pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS items (
+    id INTEGER PRIMARY KEY,
+    created_at INTEGER NOT NULL,
+    source_class TEXT,
+    size INTEGER NOT NULL,
+    text TEXT,
+    thumbnail BLOB,
+    expires_at INTEGER,
+    selection TEXT NOT NULL DEFAULT 'clipboard'
+);
+CREATE TABLE IF NOT EXISTS contents (
+    item_id INTEGER NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+    mime TEXT NOT NULL,
+    data BLOB NOT NULL,
+    PRIMARY KEY (item_id, mime)
+);
+CREATE INDEX IF NOT EXISTS items_created_at ON items(created_at);
+CREATE VIRTUAL TABLE IF NOT EXISTS items_fts
+    USING fts5(text, content='items', content_rowid='id');
+CREATE TRIGGER IF NOT EXISTS items_ai AFTER INSERT ON items BEGIN
+    INSERT INTO items_fts(rowid, text) VALUES (new.id, new.text);
+END;
+CREATE TRIGGER IF NOT EXISTS items_ad AFTER DELETE ON items BEGIN
+    INSERT INTO items_fts(items_fts, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+";
+
+/// A stored clipboard item, without its content.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryItem {
+    pub id: i64,
+    /// Milliseconds since the Unix epoch.
+    pub created_at: i64,
+    /// `WM_CLASS` of the window that owned the selection.
+    pub source_class: Option<String>,
+    /// The selection the item was copied to.
+    pub selection: Selection,
+    pub mime_types: Vec<String>,
+    /// Total size of all contents, in bytes.
+    pub size: u64,
+    pub text: Option<String>,
+    /// When a sensitive item is removed, in milliseconds since the Unix
+    /// epoch. `None` for ordinary items.
+    pub expires_at: Option<i64>,
+}
+
+/// A captured clipboard item.
+#[derive(Debug, Clone, Default)]
+pub struct NewItem {
+    pub source_class: Option<String>,
+    pub selection: Selection,
+    /// Content per MIME type.
+    pub contents: Vec<(String, Vec<u8>)>,
+    /// Text used for search and previews.
+    pub text: Option<String>,
+    /// Set for sensitive items, which are removed after this long.
+    pub expires_in: Option<Duration>,
+}
+
+impl NewItem {
+    pub fn from_event(event: &ClipboardEvent) -> Self {
+        Self {
+            source_class: event.owner_class.clone(),
+            selection: event.selection,
+            contents: event.contents.clone(),
+            text: event.text.clone(),
+            expires_in: None,
+        }
+    }
+
+    pub fn text(text: String, source_class: Option<String>) -> Self {
+        Self {
+            source_class,
+            selection: Selection::Clipboard,
+            contents: vec![(TEXT_MIME.to_string(), text.clone().into_bytes())],
+            text: Some(text),
+            expires_in: None,
+        }
+    }
+
+    fn size(&self) -> u64 {
+        self.contents.iter().map(|(_, d)| d.len() as u64).sum()
+    }
+}
+
+pub const TEXT_MIME: &str = "text/plain;charset=utf-8";
+
+/// Limits enforced after every insertion. `None` disables a limit.
+#[derive(Debug, Clone)]
+pub struct Retention {
+    pub max_items: Option<usize>,
+    pub max_age: Option<Duration>,
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_items: Some(1000),
+            max_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            max_bytes: Some(64 << 20),
+        }
+    }
+}
+
+/// Clipboard history in an SQLCipher-encrypted SQLite database, by
+/// default `$XDG_DATA_HOME/shiori/history.sqlite3`.
+pub struct History {
+    conn: Connection,
+    retention: Retention,
+    key: Key,
+}
+
+impl History {
+    pub fn path() -> PathBuf {
+        data_home().join("shiori").join("history.sqlite3")
+    }
+
+    /// The default location of the key file for [`KeySource::File`].
+    pub fn key_path() -> PathBuf {
+        data_home().join("shiori").join("history.key")
+    }
+
+    /// Opens the database at `path` with `key`. A database written before
+    /// encryption was added is encrypted in place first.
+    pub fn open(path: &Path, key: Key, retention: Retention) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let encrypted = path.with_extension("encrypting");
+        if encrypted.exists() && !path.exists() {
+            // Interrupted right after the plaintext copy was wiped.
+            std::fs::rename(&encrypted, path)?;
+        }
+        if is_plaintext(path)? {
+            encrypt_plaintext(path, &encrypted, &key)?;
+        }
+
+        let conn = Connection::open(path)?;
+        // A wrong key is reported as an error; SQLCipher's log is noise.
+        conn.pragma_update(None, "cipher_log_level", "NONE")?;
+        key.apply(&conn, "key")?;
+        // A wrong key only shows on the first read.
+        conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+            .context("wrong key, or not a history database")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        // Deleted items, sensitive ones above all, are overwritten on disk.
+        conn.pragma_update(None, "secure_delete", true)?;
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self {
+            conn,
+            retention,
+            key,
+        })
+    }
+
+    /// Re-encrypts the database with `key` and keeps it in `source`. When
+    /// storing the new key fails, the old one is put back.
+    pub fn rekey(&mut self, key: Key, source: &KeySource) -> Result<()> {
+        key.apply(&self.conn, "rekey")?;
+        if let Err(e) = source.store(&key) {
+            self.key.apply(&self.conn, "rekey")?;
+            return Err(e);
+        }
+        self.key = key;
+        Ok(())
+    }
+
+    /// Re-encrypts the database with a fresh random key kept in `source`.
+    pub fn rotate_key(&mut self, source: &KeySource) -> Result<()> {
+        self.rekey(Key::generate()?, source)
+    }
+
+    pub fn set_retention(&mut self, retention: Retention) -> Result<()> {
+        self.retention = retention;
+        self.prune()
+    }
+
+    /// Stores `item` and returns its id. When it repeats the most recent
+    /// item, that item is moved to the top instead of storing a copy.
+    pub fn add(&mut self, item: &NewItem) -> Result<i64> {
+        let now = now_millis();
+        if let Some(latest) = self.latest_id()?
+            && self.contents(latest)? == item.contents
+        {
+            self.touch(latest)?;
+            if item.expires_in.is_some() {
+                self.set_expiry(latest, item.expires_in)?;
+            }
+            return Ok(latest);
+        }
+
+        let thumbnail = item
+            .contents
+            .iter()
+            .find(|(mime, _)| mime == "image/png")
+            .and_then(|(_, data)| thumbnail(data));
+
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO items
+                (created_at, source_class, selection, size, text, thumbnail, expires_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                now,
+                item.source_class,
+                item.selection.as_str(),
+                item.size() as i64,
+                item.text,
+                thumbnail,
+                item.expires_in.map(|ttl| now + ttl.as_millis() as i64)
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        for (mime, data) in &item.contents {
+            tx.execute(
+                "INSERT OR REPLACE INTO contents (item_id, mime, data) VALUES (?1, ?2, ?3)",
+                params![id, mime, data],
+            )?;
+        }
+        tx.commit()?;
+
+        self.prune()?;
+        Ok(id)
+    }
+
+    fn latest_id(&self) -> Result<Option<i64>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id FROM items ORDER BY created_at DESC, id DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Moves item `id` to the top of the history.
+    pub fn touch(&self, id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE items SET created_at = ?1 WHERE id = ?2",
+            params![now_millis(), id],
+        )?;
+        Ok(())
+    }
+
+    /// Marks item `id` sensitive, to be removed `expires_in` from now, or
+    /// with `None` keeps it like any other item.
+    pub fn set_expiry(&self, id: i64, expires_in: Option<Duration>) -> Result<bool> {
+        let expires_at = expires_in.map(|ttl| now_millis() + ttl.as_millis() as i64);
+        Ok(self.conn.execute(
+            "UPDATE items SET expires_at = ?1 WHERE id = ?2",
+            params![expires_at, id],
+        )? > 0)
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<HistoryItem>> {
+        let item = self
+            .conn
+            .query_row(
+                "SELECT id, created_at, source_class, size, text, expires_at, selection
+                    FROM items WHERE id = ?1",
+                [id],
+                |row| {
+                    Ok(HistoryItem {
+                        id: row.get(0)?,
+                        created_at: row.get(1)?,
+                        source_class: row.get(2)?,
+                        selection: Selection::from_name(&row.get::<_, String>(6)?)
+                            .unwrap_or_default(),
+                        mime_types: Vec::new(),
+                        size: row.get::<_, i64>(3)? as u64,
+                        text: row.get(4)?,
+                        expires_at: row.get(5)?,
+                    })
+                },
+            )
+            .optional()?;
+        match item {
+            Some(mut item) => {
+                item.mime_types = self.contents(id)?.into_iter().map(|(m, _)| m).collect();
+                Ok(Some(item))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// A PNG thumbnail of the item's image, if it has one.
+    pub fn thumbnail(&self, id: i64) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .conn
+            .query_row("SELECT thumbnail FROM items WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .optional()?
+            .flatten())
+    }
+
+    /// The stored content of item `id`, per MIME type.
+    pub fn contents(&self, id: i64) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT mime, data FROM contents WHERE item_id = ?1 ORDER BY rowid")?;
+        let rows = stmt.query_map([id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// The most recent items.
+    pub fn recent(&self, limit: usize) -> Result<Vec<HistoryItem>> {
+        self.expire()?;
+        let ids = self.ids(
+            "SELECT id FROM items ORDER BY created_at DESC, id DESC LIMIT ?1",
+            params![limit as i64],
+        )?;
+        self.items(&ids)
+    }
+
+    /// Finds items whose text matches `query`: full-text matches first,
+    /// then fuzzy matches by score. An empty query lists recent items.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<HistoryItem>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return self.recent(limit);
+        }
+        self.expire()?;
+
+        let mut ids = self.ids(
+            "SELECT rowid FROM items_fts WHERE items_fts MATCH ?1 ORDER BY rank LIMIT ?2",
+            params![fts_query(query), limit as i64],
+        )?;
+
+        if ids.len() < limit {
+            let matcher = SkimMatcherV2::default();
+            let mut stmt = self
+                .conn
+                .prepare_cached("SELECT id, text FROM items WHERE text IS NOT NULL")?;
+            let mut fuzzy: Vec<(i64, i64)> = stmt
+                .query_map([], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+                })?
+                .filter_map(|row| row.ok())
+                .filter(|(id, _)| !ids.contains(id))
+                .filter_map(|(id, text)| Some((matcher.fuzzy_match(&text, query)?, id)))
+                .collect();
+            fuzzy.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+            ids.extend(fuzzy.into_iter().map(|(_, id)| id).take(limit - ids.len()));
+        }
+
+        self.items(&ids)
+    }
+
+    fn ids(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let rows = stmt.query_map(params, |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn items(&self, ids: &[i64]) -> Result<Vec<HistoryItem>> {
+        let mut items = Vec::with_capacity(ids.len());
+        for &id in ids {
+            if let Some(item) = self.get(id)? {
+                items.push(item);
+            }
+        }
+        Ok(items)
+    }
+
+    pub fn remove(&self, id: i64) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM items WHERE id = ?1", [id])? > 0)
+    }
+
+    /// Removes every item. Freed pages are overwritten, and the
+    /// write-ahead log and free space are dropped, so nothing is left
+    /// behind on disk.
+    pub fn clear(&self) -> Result<()> {
+        self.conn.execute("DELETE FROM items", [])?;
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        self.conn.execute_batch("VACUUM")?;
+        Ok(())
+    }
+
+    /// Removes the sensitive items whose time is up and returns how many.
+    pub fn expire(&self) -> Result<usize> {
+        Ok(self
+            .conn
+            .execute("DELETE FROM items WHERE expires_at <= ?1", [now_millis()])?)
+    }
+
+    /// Drops expired items and the items that exceed the retention limits,
+    /// oldest first.
+    pub fn prune(&self) -> Result<()> {
+        self.expire()?;
+        if let Some(max_age) = self.retention.max_age {
+            let cutoff = now_millis() - max_age.as_millis() as i64;
+            self.conn
+                .execute("DELETE FROM items WHERE created_at < ?1", [cutoff])?;
+        }
+        if let Some(max_items) = self.retention.max_items {
+            self.conn.execute(
+                "DELETE FROM items WHERE id NOT IN
+                    (SELECT id FROM items ORDER BY created_at DESC, id DESC LIMIT ?1)",
+                [max_items as i64],
+            )?;
+        }
+        if let Some(max_bytes) = self.retention.max_bytes {
+            // The newest item is kept even when it alone exceeds the limit.
+            let mut stmt = self
+                .conn
+                .prepare_cached("SELECT id, size FROM items ORDER BY created_at DESC, id DESC")?;
+            let rows: Vec<(i64, i64)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<_>>()?;
+            let mut total = 0u64;
+            for (i, (id, size)) in rows.into_iter().enumerate() {
+                total += size as u64;
+                if i > 0 && total > max_bytes {
+                    self.conn.execute("DELETE FROM items WHERE id = ?1", [id])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `path` is an unencrypted SQLite database.
+fn is_plaintext(path: &Path) -> Result<bool> {
+    let mut header = [0u8; 16];
+    match std::fs::File::open(path).and_then(|mut f| f.read_exact(&mut header)) {
+        Ok(()) => Ok(&header == b"SQLite format 3\0"),
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::UnexpectedEof
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes an encrypted copy of the plaintext database at `path` to
+/// `encrypted`, wipes the plaintext files and moves the copy in place.
+fn encrypt_plaintext(path: &Path, encrypted: &Path, key: &Key) -> Result<()> {
+    let _ = std::fs::remove_file(encrypted);
+    {
+        let conn = Connection::open(path)?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        conn.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            params![encrypted.to_string_lossy(), key.value().as_str()],
+        )?;
+        conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        conn.pragma_update(Some("encrypted"), "user_version", version)?;
+        conn.execute_batch("DETACH DATABASE encrypted")?;
+    }
+    for suffix in ["-wal", "-shm"] {
+        let mut side = path.as_os_str().to_owned();
+        side.push(suffix);
+        match wipe_file(Path::new(&side)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    wipe_file(path)?;
+    std::fs::rename(encrypted, path)?;
+    Ok(())
+}
+
+/// Overwrites a file with zeros before removing it.
+pub(crate) fn wipe_file(path: &Path) -> std::io::Result<()> {
+    let len = std::fs::metadata(path)?.len();
+    let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 64 * 1024];
+    let mut left = len;
+    while left > 0 {
+        let n = left.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        left -= n as u64;
+    }
+    file.sync_all()?;
+    std::fs::remove_file(path)
+}
+
+/// Scales a PNG down to fit `THUMBNAIL_SIZE`, keeping the aspect ratio.
+fn thumbnail(png: &[u8]) -> Option<Vec<u8>> {
+    let image = image::load_from_memory_with_format(png, ImageFormat::Png).ok()?;
+    let mut out = Cursor::new(Vec::new());
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut out, ImageFormat::Png)
+        .ok()?;
+    Some(out.into_inner())
+}
+
+/// Turns free text into an FTS5 query matching every word as a prefix.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn data_home() -> PathBuf {
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".local/share"),
+    }
+}
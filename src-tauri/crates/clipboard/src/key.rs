@@ -0,0 +1,164 @@
+use anyhow::{Context, Result, anyhow};
+use rusqlite::Connection;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+/// Secret Service attributes of the stored key.
+const KEYRING_SERVICE: &str = "shiori";
+const KEYRING_USER: &str = "clipboard-history";
+
+/// Length of generated keys, in bytes.
+const KEY_LEN: usize = 32;
+
+/// The key the history database is encrypted with.
+pub enum Key {
+    /// Random bytes, used as is.
+    Raw(Zeroizing<[u8; KEY_LEN]>),
+    /// A passphrase, stretched by SQLCipher's key derivation.
+    Passphrase(Zeroizing<String>),
+}
+
+impl Key {
+    pub fn generate() -> Result<Self> {
+        let mut bytes = Zeroizing::new([0u8; KEY_LEN]);
+        getrandom::fill(bytes.as_mut()).map_err(|e| anyhow!("no randomness: {}", e))?;
+        Ok(Key::Raw(bytes))
+    }
+
+    pub fn passphrase(passphrase: &str) -> Self {
+        Key::Passphrase(Zeroizing::new(passphrase.to_string()))
+    }
+
+    fn to_hex(&self) -> Option<Zeroizing<String>> {
+        let Key::Raw(bytes) = self else {
+            return None;
+        };
+        let mut hex = Zeroizing::new(String::with_capacity(KEY_LEN * 2));
+        for b in bytes.iter() {
+            hex.push(char::from_digit((b >> 4) as u32, 16).unwrap());
+            hex.push(char::from_digit((b & 0xf) as u32, 16).unwrap());
+        }
+        Some(hex)
+    }
+
+    fn from_hex(hex: &str) -> Result<Self> {
+        let hex = hex.trim();
+        if hex.len() != KEY_LEN * 2 {
+            return Err(anyhow!("stored key has the wrong length"));
+        }
+        let mut bytes = Zeroizing::new([0u8; KEY_LEN]);
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                .map_err(|_| anyhow!("stored key is not hex"))?;
+        }
+        Ok(Key::Raw(bytes))
+    }
+
+    /// The key as SQLCipher takes it: `x'<hex>'` for raw keys.
+    pub(crate) fn value(&self) -> Zeroizing<String> {
+        match self {
+            Key::Raw(_) => Zeroizing::new(format!("x'{}'", self.to_hex().unwrap().as_str())),
+            Key::Passphrase(passphrase) => passphrase.clone(),
+        }
+    }
+
+    /// Runs `PRAGMA key` or, with `pragma` = "rekey", re-encrypts.
+    pub(crate) fn apply(&self, conn: &Connection, pragma: &str) -> Result<()> {
+        conn.pragma_update(None, pragma, self.value().as_str())?;
+        Ok(())
+    }
+}
+
+/// Where the history key is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// A random key in the Secret Service, created on first use.
+    Keyring,
+    /// A random key in a file readable only by the user, created on first
+    /// use. Meant for CI and systems without a keyring.
+    File(PathBuf),
+    /// A key derived from a passphrase the user enters; nothing is stored.
+    Passphrase,
+}
+
+impl KeySource {
+    /// Loads the stored key, creating one when there is none yet. Fails
+    /// for [`KeySource::Passphrase`], whose key only the user knows.
+    pub fn load_or_create(&self) -> Result<Key> {
+        match self.load()? {
+            Some(key) => Ok(key),
+            None => {
+                let key = Key::generate()?;
+                self.store(&key)?;
+                Ok(key)
+            }
+        }
+    }
+
+    fn load(&self) -> Result<Option<Key>> {
+        match self {
+            KeySource::Keyring => match keyring_entry()?.get_password() {
+                Ok(hex) => Key::from_hex(&Zeroizing::new(hex)).map(Some),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(e) => Err(e).context("failed to read the key from the keyring"),
+            },
+            KeySource::File(path) => match std::fs::read_to_string(path) {
+                Ok(hex) => Key::from_hex(&Zeroizing::new(hex)).map(Some),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e).context("failed to read the key file"),
+            },
+            KeySource::Passphrase => Err(anyhow!("the history is locked by a passphrase")),
+        }
+    }
+
+    /// Replaces the stored key. A passphrase key is not stored.
+    pub fn store(&self, key: &Key) -> Result<()> {
+        let Some(hex) = key.to_hex() else {
+            return Ok(());
+        };
+        match self {
+            KeySource::Keyring => keyring_entry()?
+                .set_password(&hex)
+                .context("failed to store the key in the keyring"),
+            KeySource::File(path) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                let tmp = path.with_extension("tmp");
+                let mut file = std::fs::OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .mode(0o600)
+                    .open(&tmp)?;
+                file.write_all(hex.as_bytes())?;
+                file.sync_all()?;
+                std::fs::rename(&tmp, path)?;
+                Ok(())
+            }
+            KeySource::Passphrase => Ok(()),
+        }
+    }
+
+    /// Forgets the stored key, which leaves whatever it encrypted
+    /// unreadable.
+    pub fn delete(&self) -> Result<()> {
+        match self {
+            KeySource::Keyring => match keyring_entry()?.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(e.into()),
+            },
+            KeySource::File(path) => match crate::history::wipe_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            KeySource::Passphrase => Ok(()),
+        }
+    }
+}
+
+fn keyring_entry() -> Result<keyring::Entry> {
+    Ok(keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?)
+}
@@ -0,0 +1,7 @@
+pub mod history;
+pub mod key;
+pub mod owner;
+pub mod privacy;
+pub mod snippet;
+pub mod transform;
+pub mod watcher;
@@ -0,0 +1,433 @@
+use crate::history::TEXT_MIME;
+use crate::watcher::{Atoms, Selection, wait_event};
+use anyhow::{Result, anyhow};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use x11rb::NONE;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+/// `WM_CLASS` class of owner windows, so the watcher can tell our own
+/// ownership changes from other clients'.
+pub const OWNER_CLASS: &str = "ShioriClipboard";
+
+/// Largest INCR chunk, in bytes.
+const INCR_CHUNK: usize = 256 * 1024;
+/// An INCR transfer whose requestor stops reading for this long is dropped.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An INCR transfer in progress: the requestor deletes the property to ask
+/// for the next chunk.
+struct Transfer {
+    requestor: Window,
+    property: Atom,
+    type_: Atom,
+    data: Arc<[u8]>,
+    offset: usize,
+    last_activity: Instant,
+}
+
+/// Content offered under a target, and the type its property gets.
+struct Target {
+    atom: Atom,
+    type_: Atom,
+    data: Arc<[u8]>,
+}
+
+/// Owns a selection and serves stored content to the clients that paste it,
+/// until another client takes the selection.
+pub struct Owner {
+    conn: RustConnection,
+    window: Window,
+    atoms: Atoms,
+    selection: Atom,
+    /// Data per target atom; text is offered under all the usual names.
+    targets: Vec<Target>,
+    /// Server time at which the selection was acquired.
+    time: u32,
+    transfers: Vec<Transfer>,
+}
+
+impl Owner {
+    pub fn new(selection: Selection, contents: Vec<(String, Vec<u8>)>) -> Result<Self> {
+        let (conn, screen_num) = RustConnection::connect(None)?;
+        Self::with_connection(conn, screen_num, selection, contents)
+    }
+
+    pub fn with_connection(
+        conn: RustConnection,
+        screen_num: usize,
+        selection: Selection,
+        contents: Vec<(String, Vec<u8>)>,
+    ) -> Result<Self> {
+        let root = conn.setup().roots[screen_num].root;
+        let atoms = Atoms::new(&conn)?.reply()?;
+
+        let window = conn.generate_id()?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_CLASS,
+            AtomEnum::STRING,
+            format!("shiori-clipboard\0{}\0", OWNER_CLASS).as_bytes(),
+        )?;
+
+        let mut targets = Vec::new();
+        for (mime, data) in contents {
+            if mime == TEXT_MIME {
+                targets.extend(text_targets(&atoms, data));
+            } else {
+                let atom = conn.intern_atom(false, mime.as_bytes())?.reply()?.atom;
+                targets.push(Target {
+                    atom,
+                    type_: atom,
+                    data: data.into(),
+                });
+            }
+        }
+
+        Ok(Self {
+            conn,
+            window,
+            selection: selection.atom(&atoms),
+            atoms,
+            targets,
+            time: x11rb::CURRENT_TIME,
+            transfers: Vec::new(),
+        })
+    }
+
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    /// Takes ownership of the selection.
+    pub fn acquire(&mut self) -> Result<()> {
+        self.time = self.server_time()?;
+        self.conn
+            .set_selection_owner(self.window, self.selection, self.time)?;
+        let owner = self
+            .conn
+            .get_selection_owner(self.selection)?
+            .reply()?
+            .owner;
+        if owner != self.window {
+            return Err(anyhow!("failed to acquire the selection"));
+        }
+        Ok(())
+    }
+
+    /// Serves requests until another client takes the selection. INCR
+    /// transfers already under way are finished before returning.
+    pub fn run(&mut self) -> Result<()> {
+        let mut cleared = false;
+        loop {
+            let event = if self.transfers.is_empty() {
+                if cleared {
+                    break;
+                }
+                self.conn.wait_for_event()?
+            } else {
+                // Wake up when the quietest transfer would stall.
+                let deadline = self
+                    .transfers
+                    .iter()
+                    .map(|t| t.last_activity + TRANSFER_TIMEOUT)
+                    .min();
+                match wait_event(&self.conn, deadline)? {
+                    Some(event) => event,
+                    None => {
+                        self.drop_stalled_transfers()?;
+                        continue;
+                    }
+                }
+            };
+
+            match event {
+                Event::SelectionRequest(request) => self.answer(&request)?,
+                Event::SelectionClear(ev) if ev.selection == self.selection => cleared = true,
+                Event::PropertyNotify(ev) if ev.state == Property::DELETE => {
+                    self.continue_transfer(ev.window, ev.atom)?
+                }
+                _ => {}
+            }
+        }
+
+        self.conn.destroy_window(self.window)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn answer(&mut self, request: &SelectionRequestEvent) -> Result<()> {
+        // Obsolete clients pass no property and expect the target's name.
+        let property = if request.property == NONE {
+            request.target
+        } else {
+            request.property
+        };
+        let served = request.selection == self.selection
+            && (request.time == x11rb::CURRENT_TIME || request.time >= self.time)
+            && if request.target == self.atoms.MULTIPLE {
+                request.property != NONE && self.convert_multiple(request.requestor, property)?
+            } else {
+                self.convert(request.requestor, request.target, property)?
+            };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if served { property } else { NONE },
+        };
+        self.conn
+            .send_event(false, request.requestor, EventMask::NO_EVENT, notify)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Writes `target` to `property` on `requestor`. Returns false for
+    /// targets we do not have.
+    fn convert(&mut self, requestor: Window, target: Atom, property: Atom) -> Result<bool> {
+        if target == self.atoms.TARGETS {
+            let mut atoms = vec![
+                self.atoms.TARGETS,
+                self.atoms.TIMESTAMP,
+                self.atoms.MULTIPLE,
+            ];
+            atoms.extend(self.targets.iter().map(|t| t.atom));
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                requestor,
+                property,
+                AtomEnum::ATOM,
+                &atoms,
+            )?;
+            return Ok(true);
+        }
+        if target == self.atoms.TIMESTAMP {
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                requestor,
+                property,
+                AtomEnum::INTEGER,
+                &[self.time],
+            )?;
+            return Ok(true);
+        }
+
+        let Some((type_, data)) = self
+            .targets
+            .iter()
+            .find(|t| t.atom == target)
+            .map(|t| (t.type_, t.data.clone()))
+        else {
+            return Ok(false);
+        };
+        if data.len() <= self.chunk_len() {
+            self.conn
+                .change_property8(PropMode::REPLACE, requestor, property, type_, &data)?;
+            return Ok(true);
+        }
+
+        // Too large for one request: announce an INCR transfer and send the
+        // chunks as the requestor deletes the property. The requestor's
+        // property changes must be selected before the announcement.
+        self.conn.change_window_attributes(
+            requestor,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            requestor,
+            property,
+            self.atoms.INCR,
+            &[data.len() as u32],
+        )?;
+        self.transfers.push(Transfer {
+            requestor,
+            property,
+            type_,
+            data,
+            offset: 0,
+            last_activity: Instant::now(),
+        });
+        Ok(true)
+    }
+
+    /// Converts each (target, property) pair listed in `property`, and
+    /// replaces the property of the pairs that failed with `None`.
+    fn convert_multiple(&mut self, requestor: Window, property: Atom) -> Result<bool> {
+        let reply = self
+            .conn
+            .get_property(false, requestor, property, AtomEnum::ANY, 0, u32::MAX / 4)?
+            .reply()?;
+        let Some(pairs) = reply.value32() else {
+            return Ok(false);
+        };
+        let mut pairs: Vec<u32> = pairs.collect();
+        for pair in pairs.chunks_exact_mut(2) {
+            let (target, target_property) = (pair[0], pair[1]);
+            let ok = target_property != NONE
+                && target != self.atoms.MULTIPLE
+                && self.convert(requestor, target, target_property)?;
+            if !ok {
+                pair[1] = NONE;
+            }
+        }
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            requestor,
+            property,
+            self.atoms.ATOM_PAIR,
+            &pairs,
+        )?;
+        Ok(true)
+    }
+
+    /// Sends the next chunk of the transfer `property` on `requestor`
+    /// belongs to; after the last one a zero-length chunk ends it.
+    fn continue_transfer(&mut self, requestor: Window, property: Atom) -> Result<()> {
+        let Some(index) = self
+            .transfers
+            .iter()
+            .position(|t| t.requestor == requestor && t.property == property)
+        else {
+            return Ok(());
+        };
+        let chunk_len = self.chunk_len();
+        let transfer = &mut self.transfers[index];
+        let end = (transfer.offset + chunk_len).min(transfer.data.len());
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            requestor,
+            property,
+            transfer.type_,
+            &transfer.data[transfer.offset..end],
+        )?;
+
+        if transfer.offset == end {
+            self.end_transfer(index)?;
+        } else {
+            transfer.offset = end;
+            transfer.last_activity = Instant::now();
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn drop_stalled_transfers(&mut self) -> Result<()> {
+        while let Some(index) = self
+            .transfers
+            .iter()
+            .position(|t| t.last_activity.elapsed() >= TRANSFER_TIMEOUT)
+        {
+            self.end_transfer(index)?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Forgets a transfer and stops listening to its requestor once no
+    /// other transfer goes there.
+    fn end_transfer(&mut self, index: usize) -> Result<()> {
+        let requestor = self.transfers.remove(index).requestor;
+        if self.transfers.iter().all(|t| t.requestor != requestor) {
+            // The requestor may be gone already.
+            let _ = self.conn.change_window_attributes(
+                requestor,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT),
+            );
+        }
+        Ok(())
+    }
+
+    /// Largest property written in one request.
+    fn chunk_len(&self) -> usize {
+        (self.conn.maximum_request_bytes() - 32).min(INCR_CHUNK)
+    }
+
+    /// Gets a server timestamp from a zero-length property change, as ICCCM
+    /// asks for instead of `CurrentTime`.
+    fn server_time(&self) -> Result<u32> {
+        self.conn.change_property8(
+            PropMode::APPEND,
+            self.window,
+            self.atoms.SHIORI_SELECTION,
+            AtomEnum::STRING,
+            &[],
+        )?;
+        self.conn.flush()?;
+        loop {
+            if let Event::PropertyNotify(ev) = self.conn.wait_for_event()?
+                && ev.window == self.window
+            {
+                return Ok(ev.time);
+            }
+        }
+    }
+}
+
+/// The targets UTF-8 `text` is offered under. `TEXT` lets the owner pick
+/// the encoding and gets UTF-8; `STRING` and plain `text/plain` are
+/// Latin-1, so they are only offered when the text fits.
+fn text_targets(atoms: &Atoms, text: Vec<u8>) -> Vec<Target> {
+    let latin1: Option<Vec<u8>> = String::from_utf8_lossy(&text)
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).ok())
+        .collect();
+    let utf8: Arc<[u8]> = text.into();
+    let mut targets: Vec<Target> = [
+        (atoms.UTF8_STRING, atoms.UTF8_STRING),
+        (atoms.TEXT_PLAIN_UTF8, atoms.TEXT_PLAIN_UTF8),
+        (atoms.TEXT, atoms.UTF8_STRING),
+    ]
+    .into_iter()
+    .map(|(atom, type_)| Target {
+        atom,
+        type_,
+        data: utf8.clone(),
+    })
+    .collect();
+    if let Some(latin1) = latin1 {
+        let latin1: Arc<[u8]> = latin1.into();
+        for atom in [atoms.TEXT_PLAIN, AtomEnum::STRING.into()] {
+            targets.push(Target {
+                atom,
+                type_: atom,
+                data: latin1.clone(),
+            });
+        }
+    }
+    targets
+}
+
+/// Acquires `selection` and serves `contents` on a background thread until
+/// another client copies something.
+pub fn spawn(
+    selection: Selection,
+    contents: Vec<(String, Vec<u8>)>,
+) -> Result<JoinHandle<Result<()>>> {
+    let mut owner = Owner::new(selection, contents)?;
+    owner.acquire()?;
+    Ok(std::thread::spawn(move || owner.run()))
+}
@@ -0,0 +1,91 @@
+use crate::history::NewItem;
+use regex::Regex;
+use std::time::Duration;
+use x_window::window::WmClass;
+
+/// Replaces the text that matches `pattern`, or with `sensitive` keeps the
+/// item as is but lets it expire.
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    pattern: Regex,
+    replacement: String,
+    sensitive: bool,
+}
+
+impl Redaction {
+    pub fn new(pattern: &str, replacement: &str, sensitive: bool) -> Result<Self, regex::Error> {
+        Ok(Self {
+            pattern: Regex::new(pattern)?,
+            replacement: replacement.to_string(),
+            sensitive,
+        })
+    }
+}
+
+/// What the watcher skips and how captured items are stored.
+#[derive(Debug, Clone)]
+pub struct Privacy {
+    /// `WM_CLASS` instance or class names whose copies are never captured,
+    /// compared ignoring ASCII case.
+    pub excluded_classes: Vec<String>,
+    pub redactions: Vec<Redaction>,
+    /// How long sensitive items stay in the history.
+    pub sensitive_ttl: Duration,
+    /// Pauses capture entirely.
+    pub incognito: bool,
+}
+
+impl Default for Privacy {
+    fn default() -> Self {
+        Self {
+            excluded_classes: Vec::new(),
+            redactions: Vec::new(),
+            sensitive_ttl: Duration::from_secs(60),
+            incognito: false,
+        }
+    }
+}
+
+impl Privacy {
+    pub fn excludes(&self, class: Option<&WmClass>) -> bool {
+        let Some(class) = class else {
+            return false;
+        };
+        self.excluded_classes.iter().any(|excluded| {
+            excluded.eq_ignore_ascii_case(&class.instance)
+                || excluded.eq_ignore_ascii_case(&class.class)
+        })
+    }
+
+    /// Applies the redaction rules to `item`'s text. A redacted item keeps
+    /// only the redacted text, since its other targets may hold the same
+    /// secret.
+    pub fn apply(&self, mut item: NewItem) -> NewItem {
+        let Some(text) = item.text.clone() else {
+            return item;
+        };
+
+        let mut redacted = text.clone();
+        for redaction in &self.redactions {
+            if !redaction.pattern.is_match(&redacted) {
+                continue;
+            }
+            if redaction.sensitive {
+                item.expires_in = Some(self.sensitive_ttl);
+            } else {
+                redacted = redaction
+                    .pattern
+                    .replace_all(&redacted, redaction.replacement.as_str())
+                    .into_owned();
+            }
+        }
+
+        if redacted != text {
+            let mut clean = NewItem::text(redacted, item.source_class);
+            clean.selection = item.selection;
+            clean.expires_in = item.expires_in;
+            return clean;
+        }
+        item
+    }
+}
@@ -0,0 +1,228 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::format::{Item, StrftimeItems};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Date format of a bare `{date}`.
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A named text template. The body may contain placeholders:
+///
+/// - `{date}` or `{date:<strftime format>}`: the current local time
+/// - `{clipboard}`: the current clipboard text
+/// - `{cursor}`: where the cursor goes after pasting
+/// - `{uuid}`: a random UUID
+/// - `{input:<name>}`: a value the user is asked for
+///
+/// `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Snippet {
+    pub name: String,
+    /// Expands the snippet when typed as the whole query.
+    #[serde(default)]
+    pub keyword: Option<String>,
+    pub body: String,
+}
+
+impl Snippet {
+    pub fn template(&self) -> Result<Template> {
+        Template::parse(&self.body).with_context(|| format!("snippet {:?}", self.name))
+    }
+
+    /// Parses the body once, for a snippet that is kept to be searched and
+    /// expanded.
+    pub fn parse(self) -> Result<ParsedSnippet> {
+        let template = self.template()?;
+        let inputs = template.inputs().into_iter().map(String::from).collect();
+        Ok(ParsedSnippet {
+            snippet: self,
+            template,
+            inputs,
+        })
+    }
+}
+
+/// A snippet with its body parsed.
+#[derive(Debug, Clone)]
+pub struct ParsedSnippet {
+    pub snippet: Snippet,
+    pub template: Template,
+    /// Names of the `{input:…}` prompts, in order of first use.
+    pub inputs: Vec<String>,
+}
+
+/// Reads the snippets in `dir`, one TOML table per `*.toml` file. A
+/// missing directory holds no snippets; invalid files are returned as
+/// errors next to the valid ones.
+pub fn load_dir(dir: &Path) -> Vec<Result<Snippet>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => return vec![Err(e).context(format!("failed to read {}", dir.display()))],
+    };
+    let mut paths: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let content = std::fs::read_to_string(&path)?;
+            toml::from_str(&content).with_context(|| format!("invalid snippet {}", path.display()))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Date(String),
+    Clipboard,
+    Cursor,
+    Uuid,
+    Input(String),
+}
+
+/// A parsed snippet body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+/// An expanded snippet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Expansion {
+    pub text: String,
+    /// Char index of the first `{cursor}`.
+    pub cursor: Option<usize>,
+}
+
+impl Template {
+    pub fn parse(body: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = body.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(anyhow!("unclosed placeholder {{{}", placeholder)),
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::parse(&placeholder)?);
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Names of the `{input:…}` prompts, in order of first use.
+    pub fn inputs(&self) -> Vec<&str> {
+        let mut inputs = Vec::new();
+        for part in &self.parts {
+            if let Part::Input(name) = part
+                && !inputs.contains(&name.as_str())
+            {
+                inputs.push(name.as_str());
+            }
+        }
+        inputs
+    }
+
+    pub fn uses_clipboard(&self) -> bool {
+        self.parts.contains(&Part::Clipboard)
+    }
+
+    /// Fills in the placeholders. Fails when an input is missing.
+    pub fn expand(&self, clipboard: &str, inputs: &HashMap<String, String>) -> Result<Expansion> {
+        let now = chrono::Local::now();
+        let mut text = String::new();
+        let mut cursor = None;
+        for part in &self.parts {
+            match part {
+                Part::Text(s) => text.push_str(s),
+                Part::Date(format) => text.push_str(
+                    &now.format_with_items(StrftimeItems::new(format))
+                        .to_string(),
+                ),
+                Part::Clipboard => text.push_str(clipboard),
+                Part::Cursor => {
+                    cursor.get_or_insert(text.chars().count());
+                }
+                Part::Uuid => text.push_str(&uuid()?),
+                Part::Input(name) => text.push_str(
+                    inputs
+                        .get(name)
+                        .ok_or_else(|| anyhow!("missing input {:?}", name))?,
+                ),
+            }
+        }
+        Ok(Expansion { text, cursor })
+    }
+}
+
+impl Part {
+    fn parse(placeholder: &str) -> Result<Self> {
+        let (name, arg) = match placeholder.split_once(':') {
+            Some((name, arg)) => (name.trim(), Some(arg)),
+            None => (placeholder.trim(), None),
+        };
+        match (name, arg) {
+            ("date", format) => {
+                let format = format.unwrap_or(DEFAULT_DATE_FORMAT);
+                if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                    return Err(anyhow!("invalid date format {:?}", format));
+                }
+                Ok(Part::Date(format.to_string()))
+            }
+            ("clipboard", None) => Ok(Part::Clipboard),
+            ("cursor", None) => Ok(Part::Cursor),
+            ("uuid", None) => Ok(Part::Uuid),
+            ("input", Some(name)) if !name.trim().is_empty() => {
+                Ok(Part::Input(name.trim().to_string()))
+            }
+            _ => Err(anyhow!("unknown placeholder {{{}}}", placeholder)),
+        }
+    }
+}
+
+/// A random (version 4) UUID.
+fn uuid() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow!("no randomness: {}", e))?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
@@ -0,0 +1,254 @@
+use anyhow::{Result, anyhow};
+use base64::Engine as _;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+
+/// A text transform offered on clipboard items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transform {
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    JsonPretty,
+    JsonMinify,
+    Trim,
+    Upper,
+    Lower,
+    SnakeCase,
+    CamelCase,
+    StripFormatting,
+    SortLines,
+    UniqueLines,
+    JwtDecode,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 15] = [
+        Transform::Base64Encode,
+        Transform::Base64Decode,
+        Transform::UrlEncode,
+        Transform::UrlDecode,
+        Transform::JsonPretty,
+        Transform::JsonMinify,
+        Transform::Trim,
+        Transform::Upper,
+        Transform::Lower,
+        Transform::SnakeCase,
+        Transform::CamelCase,
+        Transform::StripFormatting,
+        Transform::SortLines,
+        Transform::UniqueLines,
+        Transform::JwtDecode,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Transform::Base64Encode => "Base64 Encode",
+            Transform::Base64Decode => "Base64 Decode",
+            Transform::UrlEncode => "URL Encode",
+            Transform::UrlDecode => "URL Decode",
+            Transform::JsonPretty => "Pretty-Print JSON",
+            Transform::JsonMinify => "Minify JSON",
+            Transform::Trim => "Trim Whitespace",
+            Transform::Upper => "UPPER CASE",
+            Transform::Lower => "lower case",
+            Transform::SnakeCase => "snake_case",
+            Transform::CamelCase => "camelCase",
+            Transform::StripFormatting => "Strip Formatting",
+            Transform::SortLines => "Sort Lines",
+            Transform::UniqueLines => "Unique Lines",
+            Transform::JwtDecode => "Decode JWT",
+        }
+    }
+
+    /// Other words the transform is found by.
+    pub fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Transform::Base64Encode => &["b64", "encode"],
+            Transform::Base64Decode => &["b64", "decode"],
+            Transform::UrlEncode => &["percent", "escape", "uri"],
+            Transform::UrlDecode => &["percent", "unescape", "uri"],
+            Transform::JsonPretty => &["format", "indent", "beautify"],
+            Transform::JsonMinify => &["compact", "compress"],
+            Transform::Trim => &["strip", "spaces"],
+            Transform::Upper => &["uppercase", "capitals"],
+            Transform::Lower => &["lowercase"],
+            Transform::SnakeCase => &["underscore", "case"],
+            Transform::CamelCase => &["case"],
+            Transform::StripFormatting => &["plain text", "html", "rich"],
+            Transform::SortLines => &["order", "alphabetical"],
+            Transform::UniqueLines => &["dedupe", "duplicates", "uniq"],
+            Transform::JwtDecode => &["token", "json web token", "claims"],
+        }
+    }
+
+    /// Transforms `text`. Fails when it does not fit the transform, e.g.
+    /// invalid JSON.
+    pub fn apply(self, text: &str) -> Result<String> {
+        Ok(match self {
+            Transform::Base64Encode => STANDARD.encode(text),
+            Transform::Base64Decode => utf8(base64_decode(text)?)?,
+            Transform::UrlEncode => url_encode(text),
+            Transform::UrlDecode => url_decode(text)?,
+            Transform::JsonPretty => {
+                serde_json::to_string_pretty(&serde_json::from_str::<serde_json::Value>(text)?)?
+            }
+            Transform::JsonMinify => {
+                serde_json::to_string(&serde_json::from_str::<serde_json::Value>(text)?)?
+            }
+            Transform::Trim => map_lines(text.trim(), |line| line.trim_end().to_string()),
+            Transform::Upper => text.to_uppercase(),
+            Transform::Lower => text.to_lowercase(),
+            Transform::SnakeCase => map_lines(text, |line| words(line).join("_")),
+            Transform::CamelCase => map_lines(text, camel_case),
+            Transform::StripFormatting => text
+                .chars()
+                .filter(|c| !matches!(c, '\u{200b}'..='\u{200d}' | '\u{feff}'))
+                .map(|c| if c == '\u{a0}' { ' ' } else { c })
+                .collect(),
+            Transform::SortLines => {
+                let mut lines: Vec<&str> = text.lines().collect();
+                lines.sort_unstable();
+                lines.join("\n")
+            }
+            Transform::UniqueLines => {
+                let mut seen = std::collections::HashSet::new();
+                let lines: Vec<&str> = text.lines().filter(|line| seen.insert(*line)).collect();
+                lines.join("\n")
+            }
+            Transform::JwtDecode => jwt_decode(text)?,
+        })
+    }
+
+    /// The result of applying the transform when it is worth offering:
+    /// the transform succeeds and changes something. Stripping formatting
+    /// also counts for `rich` items, which lose their HTML or images.
+    pub fn preview(self, text: &str, rich: bool) -> Option<String> {
+        let result = self.apply(text).ok()?;
+        let changed = result != text || (self == Transform::StripFormatting && rich);
+        changed.then_some(result)
+    }
+}
+
+fn utf8(bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes).map_err(|_| anyhow!("decoded data is not text"))
+}
+
+/// Decodes standard or URL-safe base64, with or without padding.
+fn base64_decode(text: &str) -> Result<Vec<u8>> {
+    let compact: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .map(|c| match c {
+            '+' => '-',
+            '/' => '_',
+            c => c,
+        })
+        .collect();
+    Ok(URL_SAFE_NO_PAD.decode(compact)?)
+}
+
+/// Percent-encodes everything but the RFC 3986 unreserved characters.
+fn url_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+fn url_decode(text: &str) -> Result<String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text
+                .get(i + 1..i + 3)
+                .ok_or_else(|| anyhow!("truncated escape"))?;
+            out.push(u8::from_str_radix(hex, 16).map_err(|_| anyhow!("invalid escape"))?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    utf8(out)
+}
+
+fn map_lines(text: &str, f: impl Fn(&str) -> String) -> String {
+    text.lines().map(f).collect::<Vec<_>>().join("\n")
+}
+
+/// Lower-cased words of an identifier or phrase: splits on anything but
+/// letters and digits, and on case changes (`parseHTTPHeader` → parse,
+/// http, header).
+fn words(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        let prev = i.checked_sub(1).map(|p| chars[p]);
+        let next = chars.get(i + 1);
+        let boundary = c.is_uppercase()
+            && prev.is_some_and(|p| {
+                p.is_lowercase()
+                    || p.is_ascii_digit()
+                    || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn camel_case(text: &str) -> String {
+    let mut out = String::new();
+    for (i, word) in words(text).iter().enumerate() {
+        let mut chars = word.chars();
+        if i > 0
+            && let Some(first) = chars.next()
+        {
+            out.extend(first.to_uppercase());
+            out.push_str(chars.as_str());
+        } else {
+            out.push_str(word);
+        }
+    }
+    out
+}
+
+/// Decodes a JWT's header and payload. The signature is not verified.
+fn jwt_decode(text: &str) -> Result<String> {
+    let mut parts = text.trim().split('.');
+    let (Some(header), Some(payload), Some(_signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(anyhow!("not a JWT"));
+    };
+    let decode = |part: &str| -> Result<serde_json::Value> {
+        Ok(serde_json::from_slice(&base64_decode(part)?)?)
+    };
+    let decoded = serde_json::json!({
+        "header": decode(header)?,
+        "payload": decode(payload)?,
+    });
+    Ok(serde_json::to_string_pretty(&decoded)?)
+}
@@ -0,0 +1,719 @@
+use crate::history::TEXT_MIME;
+use crate::owner::{self, OWNER_CLASS};
+use crate::privacy::Privacy;
+use anyhow::Result;
+use rustix::event::{PollFd, PollFlags};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock, mpsc};
+use std::time::{Duration, Instant};
+use x11rb::NONE;
+use x11rb::atom_manager;
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xfixes::{self, ConnectionExt as _, SelectionEvent, SelectionEventMask};
+use x11rb::protocol::xinput::{self, ConnectionExt as _, XIEventMask};
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+/// Longest property read per request, in 32-bit units.
+const CHUNK_LEN: u32 = 1 << 16;
+
+/// Longest wait for a PRIMARY drag to end before reading it anyway.
+const MAX_SETTLE: Duration = Duration::from_secs(5);
+
+atom_manager! {
+    pub Atoms: AtomsCookie {
+        CLIPBOARD,
+        UTF8_STRING,
+        TEXT,
+        TARGETS,
+        TIMESTAMP,
+        INCR,
+        MULTIPLE,
+        ATOM_PAIR,
+        SHIORI_SELECTION,
+        IMAGE_PNG: b"image/png",
+        TEXT_HTML: b"text/html",
+        TEXT_PLAIN: b"text/plain",
+        TEXT_PLAIN_UTF8: b"text/plain;charset=utf-8",
+        TEXT_URI_LIST: b"text/uri-list",
+        GNOME_COPIED_FILES: b"x-special/gnome-copied-files",
+        KDE_PASSWORD_MANAGER_HINT: b"x-kde-passwordManagerHint",
+        CONCEALED_TYPE: b"application/x-nspasteboard-concealed-type",
+    }
+}
+
+impl Atoms {
+    /// Targets captured besides text, with the MIME type they are stored
+    /// under.
+    pub fn rich_targets(&self) -> [(&'static str, Atom); 4] {
+        [
+            ("image/png", self.IMAGE_PNG),
+            ("text/html", self.TEXT_HTML),
+            ("text/uri-list", self.TEXT_URI_LIST),
+            ("x-special/gnome-copied-files", self.GNOME_COPIED_FILES),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Selection {
+    #[default]
+    Clipboard,
+    Primary,
+}
+
+impl Selection {
+    pub fn atom(self, atoms: &Atoms) -> Atom {
+        match self {
+            Selection::Clipboard => atoms.CLIPBOARD,
+            Selection::Primary => AtomEnum::PRIMARY.into(),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Selection::Clipboard => "clipboard",
+            Selection::Primary => "primary",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "clipboard" => Some(Selection::Clipboard),
+            "primary" => Some(Selection::Primary),
+            _ => None,
+        }
+    }
+}
+
+/// Whether PRIMARY (middle-click paste) and CLIPBOARD (Ctrl+V) share their
+/// contents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncMode {
+    /// Keep them separate.
+    #[default]
+    None,
+    PrimaryToClipboard,
+    ClipboardToPrimary,
+    Both,
+}
+
+impl SyncMode {
+    /// The selection that gets a copy of what was put in `from`.
+    pub fn target(self, from: Selection) -> Option<Selection> {
+        match (self, from) {
+            (SyncMode::PrimaryToClipboard | SyncMode::Both, Selection::Primary) => {
+                Some(Selection::Clipboard)
+            }
+            (SyncMode::ClipboardToPrimary | SyncMode::Both, Selection::Clipboard) => {
+                Some(Selection::Primary)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A new selection owner and what it holds.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardEvent {
+    pub selection: Selection,
+    /// Window that owns the selection.
+    pub owner: u32,
+    /// `WM_CLASS` class of the app that owns the selection, when known.
+    pub owner_class: Option<String>,
+    /// Server time at which the owner took the selection.
+    pub timestamp: u32,
+    /// The content as text: the text target if offered, else derived from
+    /// HTML or a file list.
+    pub text: Option<String>,
+    /// MIME types of `contents`.
+    pub mime_types: Vec<String>,
+    /// Every captured target, by MIME type.
+    #[serde(skip)]
+    pub contents: Vec<(String, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Watch PRIMARY (the mouse selection) as well as CLIPBOARD.
+    pub primary: bool,
+    /// How long to wait for the owner to answer a conversion request.
+    pub timeout: Duration,
+    /// Take ownership of the selection after each capture and serve the
+    /// captured content, so it outlives the app it was copied from.
+    pub take_over: bool,
+    /// Copies captured content to the other selection. PRIMARY is watched
+    /// for syncing even without `primary`, but then not reported.
+    pub sync: SyncMode,
+    /// How long PRIMARY must stay unchanged, with no mouse button held,
+    /// before it is read.
+    pub primary_debounce: Duration,
+    /// Exclusions and incognito mode, shared so they can change while the
+    /// watcher runs.
+    pub privacy: Arc<RwLock<Privacy>>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            primary: false,
+            timeout: Duration::from_secs(1),
+            take_over: false,
+            sync: SyncMode::None,
+            primary_debounce: Duration::from_millis(300),
+            privacy: Arc::default(),
+        }
+    }
+}
+
+/// A PRIMARY change waiting for the drag that makes it to end.
+struct Settling {
+    /// The latest owner change.
+    event: xfixes::SelectionNotifyEvent,
+    started: Instant,
+    /// When PRIMARY last changed.
+    changed: Instant,
+}
+
+/// Listens for selection owner changes through XFixes and reads the new
+/// content with `ConvertSelection`, including INCR transfers.
+pub struct Watcher {
+    conn: RustConnection,
+    root: Window,
+    window: Window,
+    atoms: Atoms,
+    options: WatchOptions,
+    /// Owner changes that arrived while waiting for a conversion.
+    pending: VecDeque<Event>,
+    settling: Option<Settling>,
+    /// Whether button presses and releases arrive as XInput raw events.
+    tracks_buttons: bool,
+    /// Buttons 1 to 3 held down, one bit each.
+    held_buttons: u8,
+}
+
+impl Watcher {
+    pub fn new(options: WatchOptions) -> Result<Self> {
+        let (conn, screen_num) = RustConnection::connect(None)?;
+        Self::with_connection(conn, screen_num, options)
+    }
+
+    pub fn with_connection(
+        conn: RustConnection,
+        screen_num: usize,
+        options: WatchOptions,
+    ) -> Result<Self> {
+        let root = conn.setup().roots[screen_num].root;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        // XFixes requires the version handshake before any other request.
+        conn.xfixes_query_version(5, 0)?.reply()?;
+
+        let window = conn.generate_id()?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+
+        let mut selections = vec![Selection::Clipboard];
+        if options.primary || options.sync.target(Selection::Primary).is_some() {
+            selections.push(Selection::Primary);
+        }
+        let watches_primary = selections.contains(&Selection::Primary);
+        for selection in selections {
+            conn.xfixes_select_selection_input(
+                window,
+                selection.atom(&atoms),
+                SelectionEventMask::SET_SELECTION_OWNER,
+            )?;
+        }
+
+        // Raw events reach us whichever window has the pointer, so a drag
+        // can be waited out without polling. Without XInput 2.1 PRIMARY is
+        // only debounced.
+        let tracks_buttons = watches_primary && select_raw_buttons(&conn, root).is_ok();
+        conn.flush()?;
+
+        Ok(Self {
+            conn,
+            root,
+            window,
+            atoms,
+            options,
+            pending: VecDeque::new(),
+            settling: None,
+            tracks_buttons,
+            held_buttons: 0,
+        })
+    }
+
+    pub fn atoms(&self) -> &Atoms {
+        &self.atoms
+    }
+
+    /// Blocks until a watched selection gets a new owner and returns its
+    /// content. A PRIMARY change is read once it has settled, without
+    /// holding up CLIPBOARD changes meanwhile.
+    pub fn next_event(&mut self) -> Result<ClipboardEvent> {
+        loop {
+            let event = match self.pending.pop_front() {
+                Some(event) => Some(event),
+                None => wait_event(&self.conn, self.settle_deadline())?,
+            };
+            let Some(event) = event else {
+                // The PRIMARY change has settled.
+                if let Some(settling) = self.settling.take()
+                    && let Some(event) = self.capture(Selection::Primary, settling.event)?
+                {
+                    return Ok(event);
+                }
+                continue;
+            };
+            let Event::XfixesSelectionNotify(ev) = event else {
+                self.track_buttons(&event);
+                continue;
+            };
+            if ev.subtype != SelectionEvent::SET_SELECTION_OWNER {
+                continue;
+            }
+            match self.selection_for(ev.selection) {
+                Some(Selection::Primary) => self.primary_changed(ev)?,
+                Some(selection) => {
+                    if let Some(event) = self.capture(selection, ev)? {
+                        return Ok(event);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Reads the selection `ev` reports a new owner of, takes it over and
+    /// syncs it as configured. `None` for changes that are not reported.
+    fn capture(
+        &mut self,
+        selection: Selection,
+        ev: xfixes::SelectionNotifyEvent,
+    ) -> Result<Option<ClipboardEvent>> {
+        if ev.owner == NONE || ev.owner == self.window {
+            return Ok(None);
+        }
+
+        // Our own owners hold content that was captured already.
+        let class = x_window::window::get_client_class(&self.conn, ev.owner)
+            .ok()
+            .flatten();
+        let owner_class = class.as_ref().map(|c| c.class.clone());
+        if owner_class.as_deref() == Some(OWNER_CLASS) {
+            return Ok(None);
+        }
+        {
+            let privacy = self.options.privacy.read().unwrap();
+            if privacy.incognito || privacy.excludes(class.as_ref()) {
+                return Ok(None);
+            }
+        }
+
+        let targets = self.read_targets(ev.selection, ev.selection_timestamp)?;
+        if self.is_concealed(ev.selection, ev.selection_timestamp, &targets)? {
+            return Ok(None);
+        }
+        let contents = self.read_offered(ev.selection, ev.selection_timestamp, &targets)?;
+        // On failure the source app simply keeps the selection, and the
+        // other one its content.
+        if !contents.is_empty() {
+            if self.options.take_over {
+                let _ = owner::spawn(selection, contents.clone());
+            }
+            if let Some(target) = self.options.sync.target(selection) {
+                let _ = owner::spawn(target, contents.clone());
+            }
+        }
+        if selection == Selection::Primary && !self.options.primary {
+            return Ok(None);
+        }
+        Ok(Some(ClipboardEvent {
+            selection,
+            owner: ev.owner,
+            owner_class,
+            timestamp: ev.selection_timestamp,
+            text: text_of(&contents),
+            mime_types: contents.iter().map(|(mime, _)| mime.clone()).collect(),
+            contents,
+        }))
+    }
+
+    /// Starts or restarts the wait for PRIMARY to settle: until it has not
+    /// changed for the debounce time and no mouse button is held, so a
+    /// selection still being dragged is read once it is final.
+    fn primary_changed(&mut self, event: xfixes::SelectionNotifyEvent) -> Result<()> {
+        let now = Instant::now();
+        match &mut self.settling {
+            Some(settling) => {
+                settling.event = event;
+                settling.changed = now;
+            }
+            None => {
+                // Seeds the button state; raw events keep it up to date.
+                if self.tracks_buttons {
+                    let mask = self.conn.query_pointer(self.root)?.reply()?.mask;
+                    self.held_buttons = [
+                        KeyButMask::BUTTON1,
+                        KeyButMask::BUTTON2,
+                        KeyButMask::BUTTON3,
+                    ]
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, button)| mask.contains(*button))
+                    .fold(0, |held, (i, _)| held | 1 << i);
+                }
+                self.settling = Some(Settling {
+                    event,
+                    started: now,
+                    changed: now,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// When the pending PRIMARY change is read: after the debounce time
+    /// once no button is held, and after [`MAX_SETTLE`] at the latest.
+    fn settle_deadline(&self) -> Option<Instant> {
+        let settling = self.settling.as_ref()?;
+        let latest = settling.started + MAX_SETTLE;
+        if self.held_buttons != 0 {
+            return Some(latest);
+        }
+        Some((settling.changed + self.options.primary_debounce).min(latest))
+    }
+
+    /// Follows the buttons a drag is made with. A release restarts the
+    /// debounce, since it is what ends the drag.
+    fn track_buttons(&mut self, event: &Event) {
+        let (detail, pressed) = match event {
+            Event::XinputRawButtonPress(ev) => (ev.detail, true),
+            Event::XinputRawButtonRelease(ev) => (ev.detail, false),
+            _ => return,
+        };
+        let Some(bit) = (1..=3).contains(&detail).then(|| 1 << (detail - 1)) else {
+            return;
+        };
+        if pressed {
+            self.held_buttons |= bit;
+        } else {
+            self.held_buttons &= !bit;
+            if let Some(settling) = &mut self.settling {
+                settling.changed = Instant::now();
+            }
+        }
+    }
+
+    fn selection_for(&self, atom: Atom) -> Option<Selection> {
+        [Selection::Clipboard, Selection::Primary]
+            .into_iter()
+            .find(|s| s.atom(&self.atoms) == atom)
+    }
+
+    /// The targets the owner of `selection` offers.
+    pub fn read_targets(&mut self, selection: Atom, time: u32) -> Result<Vec<Atom>> {
+        let property = self.atoms.SHIORI_SELECTION;
+        if !self.request(selection, self.atoms.TARGETS, time)? {
+            return Ok(Vec::new());
+        }
+        let reply = self
+            .conn
+            .get_property(true, self.window, property, AtomEnum::ANY, 0, CHUNK_LEN)?
+            .reply()?;
+        Ok(reply.value32().map(Iterator::collect).unwrap_or_default())
+    }
+
+    /// Whether the owner marks the content as a secret, the way password
+    /// managers do.
+    pub fn is_concealed(&mut self, selection: Atom, time: u32, targets: &[Atom]) -> Result<bool> {
+        if targets.contains(&self.atoms.CONCEALED_TYPE) {
+            return Ok(true);
+        }
+        if !targets.contains(&self.atoms.KDE_PASSWORD_MANAGER_HINT) {
+            return Ok(false);
+        }
+        let hint = self.convert(selection, self.atoms.KDE_PASSWORD_MANAGER_HINT, time)?;
+        Ok(hint.as_deref() == Some(b"secret"))
+    }
+
+    /// Reads every supported target the owner offers: the rich targets
+    /// and text, stored as [`TEXT_MIME`]. Owners that do not answer
+    /// `TARGETS` are asked for text only.
+    pub fn read_contents(&mut self, selection: Atom, time: u32) -> Result<Vec<(String, Vec<u8>)>> {
+        let targets = self.read_targets(selection, time)?;
+        self.read_offered(selection, time, &targets)
+    }
+
+    /// Reads the supported targets among `targets`.
+    fn read_offered(
+        &mut self,
+        selection: Atom,
+        time: u32,
+        targets: &[Atom],
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut contents = Vec::new();
+        for (mime, atom) in self.atoms.rich_targets() {
+            if targets.contains(&atom)
+                && let Some(data) = self.convert(selection, atom, time)?
+            {
+                contents.push((mime.to_string(), data));
+            }
+        }
+
+        let offers_text = targets.is_empty()
+            || targets.contains(&self.atoms.UTF8_STRING)
+            || targets.contains(&AtomEnum::STRING.into());
+        if offers_text && let Some(text) = self.read_text(selection, time)? {
+            contents.push((TEXT_MIME.to_string(), text.into_bytes()));
+        }
+        Ok(contents)
+    }
+
+    /// Reads `selection` as text, preferring `UTF8_STRING` over `STRING`,
+    /// which is Latin-1.
+    pub fn read_text(&mut self, selection: Atom, time: u32) -> Result<Option<String>> {
+        if let Some(data) = self.convert(selection, self.atoms.UTF8_STRING, time)? {
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+        let latin1 = self.convert(selection, AtomEnum::STRING.into(), time)?;
+        Ok(latin1.map(|data| data.iter().map(|&b| char::from(b)).collect()))
+    }
+
+    /// Asks the owner of `selection` to convert it to `target`. Returns
+    /// `None` when the owner refuses or does not answer in time.
+    pub fn convert(&mut self, selection: Atom, target: Atom, time: u32) -> Result<Option<Vec<u8>>> {
+        let property = self.atoms.SHIORI_SELECTION;
+        if !self.request(selection, target, time)? {
+            return Ok(None);
+        }
+
+        // Deleting the property tells the owner to start an INCR transfer.
+        let (type_, data) = self.read_property(property)?;
+        if type_ == self.atoms.INCR {
+            return self.read_incr(property);
+        }
+        Ok(Some(data))
+    }
+
+    /// Sends `ConvertSelection` and waits for the owner to store the result
+    /// in our property. Returns false when it refuses or does not answer.
+    fn request(&mut self, selection: Atom, target: Atom, time: u32) -> Result<bool> {
+        let property = self.atoms.SHIORI_SELECTION;
+        self.conn.delete_property(self.window, property)?;
+        self.conn
+            .convert_selection(self.window, selection, target, property, time)?;
+        self.conn.flush()?;
+
+        let window = self.window;
+        let notify = self.wait_for(|event| match event {
+            Event::SelectionNotify(ev) if ev.requestor == window && ev.selection == selection => {
+                Some(ev.property)
+            }
+            _ => None,
+        })?;
+        Ok(notify.is_some_and(|p| p != NONE))
+    }
+
+    /// Collects the chunks of an INCR transfer; a zero-length chunk ends it.
+    fn read_incr(&mut self, property: Atom) -> Result<Option<Vec<u8>>> {
+        let window = self.window;
+        let mut data = Vec::new();
+        loop {
+            let new_value = self.wait_for(|event| match event {
+                Event::PropertyNotify(ev)
+                    if ev.window == window
+                        && ev.atom == property
+                        && ev.state == Property::NEW_VALUE =>
+                {
+                    Some(())
+                }
+                _ => None,
+            })?;
+            if new_value.is_none() {
+                return Ok(None);
+            }
+
+            let (_, chunk) = self.read_property(property)?;
+            if chunk.is_empty() {
+                return Ok(Some(data));
+            }
+            data.extend_from_slice(&chunk);
+        }
+    }
+
+    /// Reads and deletes `property` on our window, in as many requests as
+    /// needed.
+    fn read_property(&self, property: Atom) -> Result<(Atom, Vec<u8>)> {
+        let mut data = Vec::new();
+        let mut offset = 0;
+        let type_ = loop {
+            let reply = self
+                .conn
+                .get_property(
+                    false,
+                    self.window,
+                    property,
+                    AtomEnum::ANY,
+                    offset,
+                    CHUNK_LEN,
+                )?
+                .reply()?;
+            data.extend_from_slice(&reply.value);
+            if reply.bytes_after == 0 {
+                break reply.type_;
+            }
+            offset += (reply.value.len() / 4) as u32;
+        };
+        self.conn.delete_property(self.window, property)?;
+        self.conn.flush()?;
+        Ok((type_, data))
+    }
+
+    /// Waits up to the configured timeout for an event `matches` accepts.
+    /// Owner changes seen meanwhile are kept for `next_event`.
+    fn wait_for<T>(&mut self, matches: impl Fn(&Event) -> Option<T>) -> Result<Option<T>> {
+        let deadline = Instant::now() + self.options.timeout;
+        while let Some(event) = wait_event(&self.conn, Some(deadline))? {
+            if let Some(found) = matches(&event) {
+                return Ok(Some(found));
+            }
+            match event {
+                Event::XfixesSelectionNotify(_) => self.pending.push_back(event),
+                _ => self.track_buttons(&event),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The next event on `conn`, or `None` once `deadline` passes without one.
+pub(crate) fn wait_event(
+    conn: &RustConnection,
+    deadline: Option<Instant>,
+) -> Result<Option<Event>> {
+    conn.flush()?;
+    loop {
+        if let Some(event) = conn.poll_for_event()? {
+            return Ok(Some(event));
+        }
+        let timeout = match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Ok(None);
+                }
+                i32::try_from(left.as_micros().div_ceil(1000)).unwrap_or(i32::MAX)
+            }
+            None => -1,
+        };
+        let mut fds = [PollFd::new(conn.stream(), PollFlags::IN)];
+        match rustix::event::poll(&mut fds, timeout) {
+            Ok(_) | Err(rustix::io::Errno::INTR) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Asks for raw button events on `root`, which XInput 2.1 delivers
+/// whichever window the pointer is in.
+fn select_raw_buttons(conn: &RustConnection, root: Window) -> Result<()> {
+    let version = conn.xinput_xi_query_version(2, 1)?.reply()?;
+    if (version.major_version, version.minor_version) < (2, 1) {
+        anyhow::bail!("XInput 2.1 is not available");
+    }
+    conn.xinput_xi_select_events(
+        root,
+        &[xinput::EventMask {
+            deviceid: xinput::Device::ALL_MASTER.into(),
+            mask: vec![XIEventMask::RAW_BUTTON_PRESS | XIEventMask::RAW_BUTTON_RELEASE],
+        }],
+    )?
+    .check()?;
+    Ok(())
+}
+
+/// Text for search and previews: the text target, else the text of the
+/// HTML, else the listed files.
+pub fn text_of(contents: &[(String, Vec<u8>)]) -> Option<String> {
+    let get = |mime: &str| {
+        contents
+            .iter()
+            .find(|(m, _)| m == mime)
+            .map(|(_, data)| String::from_utf8_lossy(data).into_owned())
+    };
+    if let Some(text) = get(TEXT_MIME) {
+        return Some(text);
+    }
+    if let Some(html) = get("text/html") {
+        return Some(html_to_text(&html));
+    }
+    let uris = get("text/uri-list")?;
+    let lines: Vec<&str> = uris
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect();
+    Some(lines.join("\n"))
+}
+
+/// Drops tags and decodes the common entities.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+/// Reads what `selection` holds right now, as [`Watcher::read_contents`]
+/// does, on a connection of its own.
+pub fn read_selection(selection: Selection) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut watcher = Watcher::new(WatchOptions::default())?;
+    let atom = selection.atom(watcher.atoms());
+    watcher.read_contents(atom, x11rb::CURRENT_TIME)
+}
+
+/// Runs a [`Watcher`] on its own thread. The receiver yields every change
+/// until the X connection fails.
+pub fn spawn(options: WatchOptions) -> Result<mpsc::Receiver<ClipboardEvent>> {
+    let mut watcher = Watcher::new(options)?;
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = watcher.next_event() {
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(rx)
+}
@@ -0,0 +1,408 @@
+//! Selection ownership against a real X server, e.g. under
+//! `xvfb-run cargo test`. Skipped when there is no display.
+
+use clipboard::history::{NewItem, TEXT_MIME};
+use clipboard::owner::Owner;
+use clipboard::watcher::{Atoms, Selection, SyncMode, WatchOptions, Watcher, read_selection};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use x11rb::NONE;
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+/// The tests share CLIPBOARD, so they take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// `None` without a display, so the test can be skipped.
+fn serial() -> Option<MutexGuard<'static, ()>> {
+    std::env::var_os("DISPLAY")?;
+    Some(SERIAL.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+fn connect() -> (RustConnection, usize) {
+    RustConnection::connect(None).expect("failed to connect to the display")
+}
+
+fn own(contents: Vec<(String, Vec<u8>)>) -> JoinHandle<anyhow::Result<()>> {
+    own_selection(Selection::Clipboard, contents)
+}
+
+fn own_selection(
+    selection: Selection,
+    contents: Vec<(String, Vec<u8>)>,
+) -> JoinHandle<anyhow::Result<()>> {
+    let (conn, screen_num) = connect();
+    let mut owner = Owner::with_connection(conn, screen_num, selection, contents).unwrap();
+    owner.acquire().unwrap();
+    std::thread::spawn(move || owner.run())
+}
+
+/// Like [`own_selection`], but as some other app: the watcher skips its
+/// own owners.
+fn copy_from_app(selection: Selection, text: &str) -> JoinHandle<anyhow::Result<()>> {
+    let (conn, screen_num) = connect();
+    let contents = vec![(TEXT_MIME.to_string(), text.as_bytes().to_vec())];
+    let mut owner = Owner::with_connection(conn, screen_num, selection, contents).unwrap();
+    let (conn, _) = connect();
+    conn.change_property8(
+        PropMode::REPLACE,
+        owner.window(),
+        AtomEnum::WM_CLASS,
+        AtomEnum::STRING,
+        b"editor\0Editor\0",
+    )
+    .unwrap();
+    conn.sync().unwrap();
+    owner.acquire().unwrap();
+    std::thread::spawn(move || owner.run())
+}
+
+fn watcher() -> Watcher {
+    watcher_with(WatchOptions::default())
+}
+
+fn watcher_with(options: WatchOptions) -> Watcher {
+    let (conn, screen_num) = connect();
+    Watcher::with_connection(conn, screen_num, options).unwrap()
+}
+
+fn text(contents: &[(String, Vec<u8>)]) -> Option<&str> {
+    contents
+        .iter()
+        .find(|(mime, _)| mime == TEXT_MIME)
+        .map(|(_, data)| std::str::from_utf8(data).unwrap())
+}
+
+fn window(conn: &RustConnection, screen_num: usize) -> Window {
+    let window = conn.generate_id().unwrap();
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        conn.setup().roots[screen_num].root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new(),
+    )
+    .unwrap();
+    window
+}
+
+/// Another client copies something; the owner must return.
+fn take_back(owner: JoinHandle<anyhow::Result<()>>) {
+    take_back_selection(Selection::Clipboard, owner);
+}
+
+fn take_back_selection(selection: Selection, owner: JoinHandle<anyhow::Result<()>>) {
+    let (conn, screen_num) = connect();
+    let atoms = Atoms::new(&conn).unwrap().reply().unwrap();
+    let window = window(&conn, screen_num);
+    conn.set_selection_owner(window, selection.atom(&atoms), x11rb::CURRENT_TIME)
+        .unwrap();
+    conn.flush().unwrap();
+    owner.join().unwrap().unwrap();
+}
+
+#[test]
+fn serves_every_target_and_hands_back() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let contents = vec![
+        ("text/html".to_string(), b"<b>hello</b>".to_vec()),
+        (TEXT_MIME.to_string(), b"hello".to_vec()),
+    ];
+    let owner = own(contents.clone());
+
+    let mut watcher = watcher();
+    let selection = watcher.atoms().CLIPBOARD;
+    assert_eq!(
+        watcher
+            .read_contents(selection, x11rb::CURRENT_TIME)
+            .unwrap(),
+        contents
+    );
+
+    take_back(owner);
+}
+
+#[test]
+fn captures_every_rich_target() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let contents = vec![
+        ("image/png".to_string(), b"\x89PNG".to_vec()),
+        ("text/html".to_string(), b"<i>a</i>".to_vec()),
+        ("text/uri-list".to_string(), b"file:///tmp/a\r\n".to_vec()),
+        (
+            "x-special/gnome-copied-files".to_string(),
+            b"copy\nfile:///tmp/a".to_vec(),
+        ),
+    ];
+    let owner = own(contents.clone());
+
+    let mut watcher = watcher();
+    let atoms = *watcher.atoms();
+    let targets = watcher
+        .read_targets(atoms.CLIPBOARD, x11rb::CURRENT_TIME)
+        .unwrap();
+    for (mime, atom) in atoms.rich_targets() {
+        assert!(targets.contains(&atom), "{} is not offered", mime);
+    }
+    assert_eq!(
+        watcher
+            .read_contents(atoms.CLIPBOARD, x11rb::CURRENT_TIME)
+            .unwrap(),
+        contents
+    );
+
+    take_back(owner);
+}
+
+#[test]
+fn offers_latin1_targets_only_when_the_text_fits() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let mut watcher = watcher();
+    let atoms = *watcher.atoms();
+    let string = Atom::from(AtomEnum::STRING);
+
+    let owner = own(vec![(TEXT_MIME.to_string(), "café".as_bytes().to_vec())]);
+    let read = |watcher: &mut Watcher, target| {
+        watcher
+            .convert(atoms.CLIPBOARD, target, x11rb::CURRENT_TIME)
+            .unwrap()
+    };
+    assert_eq!(read(&mut watcher, string).as_deref(), Some(&b"caf\xe9"[..]));
+    assert_eq!(
+        read(&mut watcher, atoms.TEXT).as_deref(),
+        Some("café".as_bytes())
+    );
+    take_back(owner);
+
+    let owner = own(vec![(TEXT_MIME.to_string(), "✓ done".as_bytes().to_vec())]);
+    let targets = watcher
+        .read_targets(atoms.CLIPBOARD, x11rb::CURRENT_TIME)
+        .unwrap();
+    assert!(targets.contains(&atoms.UTF8_STRING));
+    assert!(!targets.contains(&string));
+    assert!(!targets.contains(&atoms.TEXT_PLAIN));
+    assert_eq!(read(&mut watcher, string), None);
+    assert_eq!(
+        watcher
+            .read_text(atoms.CLIPBOARD, x11rb::CURRENT_TIME)
+            .unwrap()
+            .as_deref(),
+        Some("✓ done")
+    );
+    take_back(owner);
+}
+
+#[test]
+fn large_content_goes_through_incr() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let data: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let owner = own(vec![("image/png".to_string(), data.clone())]);
+
+    let mut watcher = watcher();
+    let atoms = *watcher.atoms();
+    let read = watcher
+        .convert(atoms.CLIPBOARD, atoms.IMAGE_PNG, x11rb::CURRENT_TIME)
+        .unwrap();
+    assert_eq!(read.as_deref(), Some(&data[..]));
+
+    take_back(owner);
+}
+
+#[test]
+fn multiple_converts_each_pair() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let owner = own(vec![(TEXT_MIME.to_string(), b"hello".to_vec())]);
+
+    let (conn, screen_num) = connect();
+    let atoms = Atoms::new(&conn).unwrap().reply().unwrap();
+    let requestor = window(&conn, screen_num);
+    let text_property = conn.intern_atom(false, b"TEXT_RESULT").unwrap();
+    let image_property = conn.intern_atom(false, b"IMAGE_RESULT").unwrap();
+    let (text_property, image_property) = (
+        text_property.reply().unwrap().atom,
+        image_property.reply().unwrap().atom,
+    );
+    conn.change_property32(
+        PropMode::REPLACE,
+        requestor,
+        atoms.SHIORI_SELECTION,
+        atoms.ATOM_PAIR,
+        &[
+            atoms.UTF8_STRING,
+            text_property,
+            atoms.IMAGE_PNG,
+            image_property,
+        ],
+    )
+    .unwrap();
+    conn.convert_selection(
+        requestor,
+        atoms.CLIPBOARD,
+        atoms.MULTIPLE,
+        atoms.SHIORI_SELECTION,
+        x11rb::CURRENT_TIME,
+    )
+    .unwrap();
+    conn.flush().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(1);
+    let notify = loop {
+        if let Some(Event::SelectionNotify(ev)) = conn.poll_for_event().unwrap() {
+            break ev;
+        }
+        assert!(Instant::now() < deadline, "no SelectionNotify");
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    assert_eq!(notify.property, atoms.SHIORI_SELECTION);
+
+    let get = |property| {
+        conn.get_property(false, requestor, property, AtomEnum::ANY, 0, 1024)
+            .unwrap()
+            .reply()
+            .unwrap()
+    };
+    let pairs: Vec<u32> = get(atoms.SHIORI_SELECTION).value32().unwrap().collect();
+    assert_eq!(
+        pairs,
+        vec![atoms.UTF8_STRING, text_property, atoms.IMAGE_PNG, NONE]
+    );
+    assert_eq!(get(text_property).value, b"hello");
+
+    take_back(owner);
+}
+
+/// Copies "copied" to `from` while the other selection holds "before",
+/// and returns what the other selection holds once the copy is captured.
+fn sync_from(sync: SyncMode, from: Selection) -> String {
+    let other = match from {
+        Selection::Clipboard => Selection::Primary,
+        Selection::Primary => Selection::Clipboard,
+    };
+    let before = own_selection(other, vec![(TEXT_MIME.to_string(), b"before".to_vec())]);
+    let mut watcher = watcher_with(WatchOptions {
+        primary: true,
+        sync,
+        primary_debounce: Duration::from_millis(50),
+        ..WatchOptions::default()
+    });
+
+    let source = copy_from_app(from, "copied");
+    let event = watcher.next_event().unwrap();
+    assert_eq!(event.selection, from);
+    assert_eq!(event.text.as_deref(), Some("copied"));
+
+    let held = read_selection(other).unwrap();
+    take_back_selection(from, source);
+    take_back_selection(other, before);
+    text(&held).unwrap().to_string()
+}
+
+#[test]
+fn syncs_only_the_configured_directions() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let cases = [
+        (SyncMode::None, Selection::Clipboard, "before"),
+        (SyncMode::None, Selection::Primary, "before"),
+        (SyncMode::PrimaryToClipboard, Selection::Primary, "copied"),
+        (SyncMode::PrimaryToClipboard, Selection::Clipboard, "before"),
+        (SyncMode::ClipboardToPrimary, Selection::Clipboard, "copied"),
+        (SyncMode::ClipboardToPrimary, Selection::Primary, "before"),
+        (SyncMode::Both, Selection::Clipboard, "copied"),
+        (SyncMode::Both, Selection::Primary, "copied"),
+    ];
+    for (sync, from, expected) in cases {
+        assert_eq!(
+            sync_from(sync, from),
+            expected,
+            "{:?} from {:?}",
+            sync,
+            from
+        );
+    }
+}
+
+#[test]
+fn debounce_collapses_primary_changes() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let mut watcher = watcher_with(WatchOptions {
+        primary: true,
+        primary_debounce: Duration::from_millis(300),
+        ..WatchOptions::default()
+    });
+
+    // A drag extends the selection a few times in quick succession.
+    let mut owners = Vec::new();
+    for text in ["s", "sel", "selection"] {
+        owners.push(copy_from_app(Selection::Primary, text));
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let event = watcher.next_event().unwrap();
+    assert_eq!(event.selection, Selection::Primary);
+    assert_eq!(event.text.as_deref(), Some("selection"));
+
+    // The earlier changes are gone: the next event is the next copy.
+    let clipboard = copy_from_app(Selection::Clipboard, "copied");
+    let event = watcher.next_event().unwrap();
+    assert_eq!(event.selection, Selection::Clipboard);
+    assert_eq!(event.text.as_deref(), Some("copied"));
+
+    take_back(clipboard);
+    let last = owners.pop().unwrap();
+    take_back_selection(Selection::Primary, last);
+    for owner in owners {
+        owner.join().unwrap().unwrap();
+    }
+}
+
+#[test]
+fn clipboard_changes_do_not_wait_for_primary() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let mut watcher = watcher_with(WatchOptions {
+        primary: true,
+        primary_debounce: Duration::from_secs(2),
+        ..WatchOptions::default()
+    });
+
+    let primary = copy_from_app(Selection::Primary, "selected");
+    let clipboard = copy_from_app(Selection::Clipboard, "copied");
+    let started = Instant::now();
+    let event = watcher.next_event().unwrap();
+    assert_eq!(event.selection, Selection::Clipboard);
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    let event = watcher.next_event().unwrap();
+    assert_eq!(event.selection, Selection::Primary);
+    // The item stored for it remembers where it came from.
+    let item = NewItem::from_event(&event);
+    assert_eq!(item.selection, Selection::Primary);
+    assert_eq!(item.text.as_deref(), Some("selected"));
+
+    take_back(clipboard);
+    take_back_selection(Selection::Primary, primary);
+}
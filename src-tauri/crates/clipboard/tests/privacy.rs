@@ -0,0 +1,89 @@
+//! Exclusions and redaction rules applied to captured items.
+
+use clipboard::history::{NewItem, TEXT_MIME};
+use clipboard::privacy::{Privacy, Redaction};
+use std::time::Duration;
+use x_window::window::WmClass;
+
+fn class(instance: &str, class: &str) -> WmClass {
+    WmClass {
+        instance: instance.to_string(),
+        class: class.to_string(),
+    }
+}
+
+fn privacy(redactions: Vec<Redaction>) -> Privacy {
+    Privacy {
+        redactions,
+        sensitive_ttl: Duration::from_secs(30),
+        ..Privacy::default()
+    }
+}
+
+#[test]
+fn excludes_classes_ignoring_case() {
+    let privacy = Privacy {
+        excluded_classes: vec!["keepassxc".to_string(), "Bitwarden".to_string()],
+        ..Privacy::default()
+    };
+    assert!(privacy.excludes(Some(&class("keepassxc", "KeePassXC"))));
+    assert!(privacy.excludes(Some(&class("KEEPASSXC", "Other"))));
+    assert!(privacy.excludes(Some(&class("bitwarden", "bitwarden"))));
+    assert!(!privacy.excludes(Some(&class("firefox", "firefox"))));
+    assert!(!privacy.excludes(None));
+}
+
+#[test]
+fn redacts_matching_text_and_drops_other_targets() {
+    let privacy = privacy(vec![
+        Redaction::new(r"ghp_[A-Za-z0-9]+", "[token]", false).unwrap(),
+    ]);
+    let mut item = NewItem::text("token ghp_abc123 here".to_string(), Some("term".into()));
+    item.contents
+        .push(("text/html".to_string(), b"<b>ghp_abc123</b>".to_vec()));
+
+    let item = privacy.apply(item);
+    assert_eq!(item.text.as_deref(), Some("token [token] here"));
+    assert_eq!(
+        item.contents,
+        vec![(TEXT_MIME.to_string(), b"token [token] here".to_vec())]
+    );
+    assert_eq!(item.source_class.as_deref(), Some("term"));
+    assert_eq!(item.expires_in, None);
+}
+
+#[test]
+fn sensitive_rules_keep_the_text_but_expire() {
+    let privacy = privacy(vec![Redaction::new(r"^\d{6}$", "", true).unwrap()]);
+    let item = privacy.apply(NewItem::text("123456".to_string(), None));
+    assert_eq!(item.text.as_deref(), Some("123456"));
+    assert_eq!(item.expires_in, Some(Duration::from_secs(30)));
+
+    let item = privacy.apply(NewItem::text("1234567".to_string(), None));
+    assert_eq!(item.expires_in, None);
+}
+
+#[test]
+fn redacted_sensitive_items_still_expire() {
+    let privacy = privacy(vec![
+        Redaction::new(r"password: \S+", "password: ***", false).unwrap(),
+        Redaction::new(r"password", "", true).unwrap(),
+    ]);
+    let item = privacy.apply(NewItem::text("password: hunter2".to_string(), None));
+    assert_eq!(item.text.as_deref(), Some("password: ***"));
+    assert_eq!(item.expires_in, Some(Duration::from_secs(30)));
+}
+
+#[test]
+fn leaves_items_without_text_alone() {
+    let privacy = privacy(vec![Redaction::new(".", "x", false).unwrap()]);
+    let item = NewItem {
+        contents: vec![("image/png".to_string(), vec![1, 2, 3])],
+        ..NewItem::default()
+    };
+    let item = privacy.apply(item);
+    assert_eq!(
+        item.contents,
+        vec![("image/png".to_string(), vec![1, 2, 3])]
+    );
+}
@@ -0,0 +1,96 @@
+//! Snippet templates and the snippets directory.
+
+use clipboard::snippet::{Snippet, Template, load_dir};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[test]
+fn expands_placeholders() {
+    let template = Template::parse("Hi {input:Name}, {{{clipboard}}} {cursor}!{cursor}").unwrap();
+    assert_eq!(template.inputs(), ["Name"]);
+    assert!(template.uses_clipboard());
+
+    let inputs = HashMap::from([("Name".to_string(), "Ann".to_string())]);
+    let expansion = template.expand("copied", &inputs).unwrap();
+    assert_eq!(expansion.text, "Hi Ann, {copied} !");
+    assert_eq!(expansion.cursor, Some(17));
+    assert!(template.expand("", &HashMap::new()).is_err());
+}
+
+#[test]
+fn formats_dates_and_uuids() {
+    let text = Template::parse("{date:%Y}|{date}|{uuid}")
+        .unwrap()
+        .expand("", &HashMap::new())
+        .unwrap()
+        .text;
+    let parts: Vec<&str> = text.split('|').collect();
+    assert_eq!(parts[0].len(), 4);
+    assert_eq!(parts[1].len(), "2024-01-01".len());
+    assert_eq!(parts[2].len(), 36);
+    assert_eq!(&parts[2][14..15], "4");
+}
+
+#[test]
+fn rejects_invalid_templates() {
+    for body in ["{nope}", "{date:%Q}", "{input:}", "{clipboard", "{uuid:x}"] {
+        assert!(Template::parse(body).is_err(), "{}", body);
+    }
+}
+
+/// Removes the snippets directory a test wrote, even when it fails.
+struct Scratch(PathBuf);
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn parses_the_body_once() {
+    let snippet = Snippet {
+        name: "Reply".to_string(),
+        keyword: Some("re".to_string()),
+        body: "Dear {input:Name}, re {input:Subject}: {input:Name}".to_string(),
+    };
+    let parsed = snippet.clone().parse().unwrap();
+    assert_eq!(parsed.snippet, snippet);
+    assert_eq!(parsed.inputs, ["Name", "Subject"]);
+    assert_eq!(parsed.template, snippet.template().unwrap());
+
+    let broken = Snippet {
+        body: "{nope}".to_string(),
+        ..snippet
+    };
+    let e = broken.parse().unwrap_err();
+    assert!(
+        format!("{:#}", e).starts_with("snippet \"Reply\""),
+        "{:#}",
+        e
+    );
+}
+
+#[test]
+fn loads_toml_files() {
+    let scratch =
+        Scratch(std::env::temp_dir().join(format!("shiori-snippets-{}", std::process::id())));
+    let dir = &scratch.0;
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(
+        dir.join("a.toml"),
+        "name = \"Sig\"\nkeyword = \"sig\"\nbody = \"--\\nAnn\"\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("b.toml"), "name = \"Broken\"\n").unwrap();
+    std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+    let snippets = load_dir(dir);
+    assert_eq!(snippets.len(), 2);
+    let sig = snippets[0].as_ref().unwrap();
+    assert_eq!(sig.keyword.as_deref(), Some("sig"));
+    assert_eq!(sig.body, "--\nAnn");
+    assert!(snippets[1].is_err());
+    assert!(load_dir(&dir.join("missing")).is_empty());
+}
@@ -0,0 +1,74 @@
+//! Transform results and when they are offered.
+
+use clipboard::transform::Transform;
+
+#[test]
+fn encodes_and_decodes() {
+    let text = "a b/ü?";
+    for (encode, decode) in [
+        (Transform::Base64Encode, Transform::Base64Decode),
+        (Transform::UrlEncode, Transform::UrlDecode),
+    ] {
+        let encoded = encode.apply(text).unwrap();
+        assert_eq!(decode.apply(&encoded).unwrap(), text);
+    }
+    assert_eq!(
+        Transform::UrlEncode.apply(text).unwrap(),
+        "a%20b%2F%C3%BC%3F"
+    );
+    assert_eq!(Transform::Base64Decode.apply("aGk_").unwrap(), "hi?");
+    assert!(Transform::UrlDecode.apply("100%").is_err());
+}
+
+#[test]
+fn formats_json() {
+    let pretty = Transform::JsonPretty.apply(r#"{"a":[1,2]}"#).unwrap();
+    assert_eq!(pretty, "{\n  \"a\": [\n    1,\n    2\n  ]\n}");
+    assert_eq!(
+        Transform::JsonMinify.apply(&pretty).unwrap(),
+        r#"{"a":[1,2]}"#
+    );
+    assert!(Transform::JsonPretty.apply("not json").is_err());
+}
+
+#[test]
+fn changes_case() {
+    let text = "parseHTTPHeader\nuser-id 2";
+    assert_eq!(
+        Transform::SnakeCase.apply(text).unwrap(),
+        "parse_http_header\nuser_id_2"
+    );
+    assert_eq!(
+        Transform::CamelCase.apply(text).unwrap(),
+        "parseHttpHeader\nuserId2"
+    );
+}
+
+#[test]
+fn sorts_and_dedupes_lines() {
+    let text = "b\na\nb\nc";
+    assert_eq!(Transform::SortLines.apply(text).unwrap(), "a\nb\nb\nc");
+    assert_eq!(Transform::UniqueLines.apply(text).unwrap(), "b\na\nc");
+    assert_eq!(Transform::Trim.apply("  x  \ny \n\n").unwrap(), "x\ny");
+}
+
+#[test]
+fn decodes_jwt() {
+    let token = "eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxMjMifQ.c2ln";
+    let decoded: serde_json::Value =
+        serde_json::from_str(&Transform::JwtDecode.apply(token).unwrap()).unwrap();
+    assert_eq!(decoded["header"]["alg"], "HS256");
+    assert_eq!(decoded["payload"]["sub"], "123");
+    assert!(Transform::JwtDecode.apply("a.b").is_err());
+}
+
+#[test]
+fn offers_only_what_changes_the_text() {
+    assert!(Transform::Upper.preview("ABC", false).is_none());
+    assert!(Transform::JsonMinify.preview("plain", false).is_none());
+    assert!(Transform::StripFormatting.preview("plain", false).is_none());
+    assert_eq!(
+        Transform::StripFormatting.preview("plain", true).as_deref(),
+        Some("plain")
+    );
+}
@@ -0,0 +1,54 @@
+//! Deriving text from captured selection contents.
+
+use clipboard::history::TEXT_MIME;
+use clipboard::watcher::text_of;
+
+fn contents(pairs: &[(&str, &str)]) -> Vec<(String, Vec<u8>)> {
+    pairs
+        .iter()
+        .map(|(mime, data)| (mime.to_string(), data.as_bytes().to_vec()))
+        .collect()
+}
+
+#[test]
+fn prefers_the_text_target() {
+    let contents = contents(&[("text/html", "<b>bold</b>"), (TEXT_MIME, "plain")]);
+    assert_eq!(text_of(&contents).as_deref(), Some("plain"));
+}
+
+#[test]
+fn strips_tags_from_html() {
+    let contents = contents(&[(
+        "text/html",
+        "<meta charset=\"utf-8\"><p>Hello <b>world</b></p>\n",
+    )]);
+    assert_eq!(text_of(&contents).as_deref(), Some("Hello world"));
+}
+
+#[test]
+fn decodes_html_entities() {
+    let contents = contents(&[(
+        "text/html",
+        "a&nbsp;&lt;b&gt; &quot;c&quot; &#39;d&#39; &amp;lt;",
+    )]);
+    // `&amp;` is decoded last, so `&amp;lt;` stays a literal `&lt;`.
+    assert_eq!(text_of(&contents).as_deref(), Some("a <b> \"c\" 'd' &lt;"));
+}
+
+#[test]
+fn lists_uris_without_comments() {
+    let contents = contents(&[(
+        "text/uri-list",
+        "# copied by a file manager\r\nfile:///tmp/a\r\n\r\nfile:///tmp/b\r\n",
+    )]);
+    assert_eq!(
+        text_of(&contents).as_deref(),
+        Some("file:///tmp/a\nfile:///tmp/b")
+    );
+}
+
+#[test]
+fn has_no_text_for_images() {
+    let contents = vec![("image/png".to_string(), vec![0x89, b'P', b'N', b'G'])];
+    assert_eq!(text_of(&contents), None);
+}
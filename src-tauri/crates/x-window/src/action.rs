@@ -0,0 +1,191 @@
+use crate::atom::Atoms;
+use crate::window::{
+    ALL_DESKTOPS, ICONIC_STATE, SOURCE_PAGER, get_cardinal, get_net_wm_state, send_message,
+    server_time, supported,
+};
+use anyhow::{Result, anyhow};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+/// A `_NET_WM_STATE` a window can be put in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowState {
+    MaximizedHorz,
+    MaximizedVert,
+    Fullscreen,
+    /// Always on top.
+    Above,
+    /// On every desktop.
+    Sticky,
+    /// Rolled up into the title bar.
+    Shaded,
+}
+
+impl WindowState {
+    pub const ALL: [WindowState; 6] = [
+        WindowState::MaximizedHorz,
+        WindowState::MaximizedVert,
+        WindowState::Fullscreen,
+        WindowState::Above,
+        WindowState::Sticky,
+        WindowState::Shaded,
+    ];
+
+    pub fn atom(self, atoms: &Atoms) -> Atom {
+        match self {
+            WindowState::MaximizedHorz => atoms.net_wm_state_maximized_horz,
+            WindowState::MaximizedVert => atoms.net_wm_state_maximized_vert,
+            WindowState::Fullscreen => atoms.net_wm_state_fullscreen,
+            WindowState::Above => atoms.net_wm_state_above,
+            WindowState::Sticky => atoms.net_wm_state_sticky,
+            WindowState::Shaded => atoms.net_wm_state_shaded,
+        }
+    }
+}
+
+/// How a `_NET_WM_STATE` request changes the states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateChange {
+    Remove = 0,
+    Add = 1,
+    Toggle = 2,
+}
+
+/// A connection to the window manager, for acting on its windows. Every
+/// request is sent the way a pager sends it, and fails when the window
+/// manager does not announce support for it.
+pub struct WindowManager {
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+    supported: Vec<Atom>,
+}
+
+impl WindowManager {
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = RustConnection::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        let atoms = Atoms::new(&conn)?;
+        let supported = supported(&conn, root, &atoms)?;
+        Ok(Self {
+            conn,
+            root,
+            atoms,
+            supported,
+        })
+    }
+
+    fn require(&self, atom: Atom, name: &str) -> Result<()> {
+        if self.supported.contains(&atom) {
+            Ok(())
+        } else {
+            Err(anyhow!("the window manager does not support {}", name))
+        }
+    }
+
+    fn send(&self, window: Window, message: Atom, data: [u32; 5]) -> Result<()> {
+        send_message(&self.conn, self.root, window, message, data)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Asks the window to close, as its close button would.
+    pub fn close(&self, window: Window) -> Result<()> {
+        self.require(self.atoms.net_close_window, "_NET_CLOSE_WINDOW")?;
+        let time = server_time()?;
+        self.send(
+            window,
+            self.atoms.net_close_window,
+            [time, SOURCE_PAGER, 0, 0, 0],
+        )
+    }
+
+    /// Iconifies the window with the ICCCM `WM_CHANGE_STATE` message,
+    /// which every window manager handles.
+    pub fn minimize(&self, window: Window) -> Result<()> {
+        self.send(
+            window,
+            self.atoms.wm_change_state,
+            [ICONIC_STATE, 0, 0, 0, 0],
+        )
+    }
+
+    /// Adds, removes or toggles one or two states at once, e.g. both
+    /// maximized states.
+    pub fn change_state(
+        &self,
+        window: Window,
+        change: StateChange,
+        first: WindowState,
+        second: Option<WindowState>,
+    ) -> Result<()> {
+        self.require(self.atoms.net_wm_state, "_NET_WM_STATE")?;
+        for state in std::iter::once(first).chain(second) {
+            self.require(state.atom(&self.atoms), &format!("{:?}", state))?;
+        }
+        self.send(
+            window,
+            self.atoms.net_wm_state,
+            [
+                change as u32,
+                first.atom(&self.atoms),
+                second.map_or(0, |state| state.atom(&self.atoms)),
+                SOURCE_PAGER,
+                0,
+            ],
+        )
+    }
+
+    pub fn states(&self, window: Window) -> Result<Vec<WindowState>> {
+        let atoms = get_net_wm_state(&self.conn, window)?;
+        Ok(WindowState::ALL
+            .into_iter()
+            .filter(|state| atoms.contains(&state.atom(&self.atoms)))
+            .collect())
+    }
+
+    /// Toggles `state`, which for `Fullscreen` and the like is one request;
+    /// see [`WindowManager::toggle_maximized`] for maximizing.
+    pub fn toggle(&self, window: Window, state: WindowState) -> Result<()> {
+        self.change_state(window, StateChange::Toggle, state, None)
+    }
+
+    /// Maximizes the window in both directions, or restores it when it is
+    /// maximized in both already.
+    pub fn toggle_maximized(&self, window: Window) -> Result<()> {
+        let states = self.states(window)?;
+        let maximized = states.contains(&WindowState::MaximizedHorz)
+            && states.contains(&WindowState::MaximizedVert);
+        let change = if maximized {
+            StateChange::Remove
+        } else {
+            StateChange::Add
+        };
+        self.change_state(
+            window,
+            change,
+            WindowState::MaximizedHorz,
+            Some(WindowState::MaximizedVert),
+        )
+    }
+
+    /// The number of desktops, if the window manager has any.
+    pub fn desktop_count(&self) -> Result<Option<u32>> {
+        get_cardinal(&self.conn, self.root, self.atoms.net_number_of_desktops)
+    }
+
+    /// Moves the window to `desktop`, or to every desktop with
+    /// [`ALL_DESKTOPS`].
+    pub fn move_to_desktop(&self, window: Window, desktop: u32) -> Result<()> {
+        self.require(self.atoms.net_wm_desktop, "_NET_WM_DESKTOP")?;
+        if desktop != ALL_DESKTOPS && self.desktop_count()?.is_some_and(|count| desktop >= count) {
+            return Err(anyhow!("there is no desktop {}", desktop));
+        }
+        self.send(
+            window,
+            self.atoms.net_wm_desktop,
+            [desktop, SOURCE_PAGER, 0, 0, 0],
+        )
+    }
+}
@@ -1,13 +1,36 @@
 use anyhow::Result;
-use once_cell::sync::Lazy;
-use std::sync::Mutex;
+use x11rb::atom_manager;
 use x11rb::connection::Connection;
-use x11rb::protocol::xproto::*;
 use x11rb::protocol::xproto::{Atom, AtomEnum};
-use x11rb::rust_connection::RustConnection;
 
-/// 全局缓存，每个 atom 只获取一次
-static ATOMS: Lazy<Mutex<Option<Atoms>>> = Lazy::new(|| Mutex::new(None));
+atom_manager! {
+    Interned: InternedCookie {
+        _NET_CLIENT_LIST,
+        _NET_WM_NAME,
+        UTF8_STRING,
+        _NET_WM_DESKTOP,
+        _NET_ACTIVE_WINDOW,
+        _NET_WM_PID,
+        _NET_CLIENT_LIST_STACKING,
+        _NET_CURRENT_DESKTOP,
+        _NET_WM_ICON,
+        _NET_WM_STATE,
+        _NET_WM_STATE_DEMANDS_ATTENTION,
+        _NET_SUPPORTED,
+        _NET_RESTACK_WINDOW,
+        WM_STATE,
+        WM_CHANGE_STATE,
+        _NET_CLOSE_WINDOW,
+        _NET_NUMBER_OF_DESKTOPS,
+        _NET_WM_STATE_HIDDEN,
+        _NET_WM_STATE_MAXIMIZED_HORZ,
+        _NET_WM_STATE_MAXIMIZED_VERT,
+        _NET_WM_STATE_FULLSCREEN,
+        _NET_WM_STATE_ABOVE,
+        _NET_WM_STATE_STICKY,
+        _NET_WM_STATE_SHADED,
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Atoms {
@@ -17,29 +40,59 @@ pub struct Atoms {
     pub wm_name: Atom,
     pub net_wm_desktop: Atom,
     pub net_active_window: Atom,
+    pub net_wm_pid: Atom,
+    pub net_client_list_stacking: Atom,
+    pub net_current_desktop: Atom,
+    pub net_wm_icon: Atom,
+    pub net_wm_state: Atom,
+    pub net_wm_state_demands_attention: Atom,
+    pub net_supported: Atom,
+    pub net_restack_window: Atom,
+    pub wm_state: Atom,
+    pub wm_change_state: Atom,
+    pub net_close_window: Atom,
+    pub net_number_of_desktops: Atom,
+    pub net_wm_state_hidden: Atom,
+    pub net_wm_state_maximized_horz: Atom,
+    pub net_wm_state_maximized_vert: Atom,
+    pub net_wm_state_fullscreen: Atom,
+    pub net_wm_state_above: Atom,
+    pub net_wm_state_sticky: Atom,
+    pub net_wm_state_shaded: Atom,
 }
 
 impl Atoms {
-    pub fn load<C: Connection>(conn: &C) -> Result<()> {
-        let mut lock = ATOMS.lock().unwrap();
-        if lock.is_none() {
-            *lock = Some(Self {
-                net_client_list: intern(conn, b"_NET_CLIENT_LIST")?,
-                net_wm_name: intern(conn, b"_NET_WM_NAME")?,
-                utf8_string: intern(conn, b"UTF8_STRING")?,
-                wm_name: AtomEnum::WM_NAME.into(),
-                net_wm_desktop: intern(conn, b"_NET_WM_DESKTOP")?,
-                net_active_window: intern(conn, b"_NET_ACTIVE_WINDOW")?,
-            });
-        }
-        Ok(())
-    }
-
-    pub fn get() -> Option<Atoms> {
-        ATOMS.lock().unwrap().clone()
+    /// Interns the atoms on `conn`. Atom ids are only valid on the server
+    /// they were interned on, so they are fetched per connection, in a
+    /// single round trip.
+    pub fn new<C: Connection>(conn: &C) -> Result<Self> {
+        let interned = Interned::new(conn)?.reply()?;
+        Ok(Self {
+            net_client_list: interned._NET_CLIENT_LIST,
+            net_wm_name: interned._NET_WM_NAME,
+            utf8_string: interned.UTF8_STRING,
+            wm_name: AtomEnum::WM_NAME.into(),
+            net_wm_desktop: interned._NET_WM_DESKTOP,
+            net_active_window: interned._NET_ACTIVE_WINDOW,
+            net_wm_pid: interned._NET_WM_PID,
+            net_client_list_stacking: interned._NET_CLIENT_LIST_STACKING,
+            net_current_desktop: interned._NET_CURRENT_DESKTOP,
+            net_wm_icon: interned._NET_WM_ICON,
+            net_wm_state: interned._NET_WM_STATE,
+            net_wm_state_demands_attention: interned._NET_WM_STATE_DEMANDS_ATTENTION,
+            net_supported: interned._NET_SUPPORTED,
+            net_restack_window: interned._NET_RESTACK_WINDOW,
+            wm_state: interned.WM_STATE,
+            wm_change_state: interned.WM_CHANGE_STATE,
+            net_close_window: interned._NET_CLOSE_WINDOW,
+            net_number_of_desktops: interned._NET_NUMBER_OF_DESKTOPS,
+            net_wm_state_hidden: interned._NET_WM_STATE_HIDDEN,
+            net_wm_state_maximized_horz: interned._NET_WM_STATE_MAXIMIZED_HORZ,
+            net_wm_state_maximized_vert: interned._NET_WM_STATE_MAXIMIZED_VERT,
+            net_wm_state_fullscreen: interned._NET_WM_STATE_FULLSCREEN,
+            net_wm_state_above: interned._NET_WM_STATE_ABOVE,
+            net_wm_state_sticky: interned._NET_WM_STATE_STICKY,
+            net_wm_state_shaded: interned._NET_WM_STATE_SHADED,
+        })
     }
 }
-
-fn intern<C: Connection>(conn: &C, name: &[u8]) -> Result<Atom> {
-    Ok(conn.intern_atom(false, name)?.reply()?.atom)
-}
@@ -1,3 +1,5 @@
+pub mod action;
 pub mod atom;
 pub mod connection;
+pub mod paste;
 pub mod window;
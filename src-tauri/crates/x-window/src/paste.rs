@@ -0,0 +1,209 @@
+use crate::window::{WmClass, activate_window_with, active_window, get_client_class};
+use anyhow::{Result, anyhow};
+use std::time::{Duration, Instant};
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xproto::*;
+use x11rb::protocol::xtest::ConnectionExt as _;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+const XK_SHIFT_L: Keysym = 0xffe1;
+const XK_CONTROL_L: Keysym = 0xffe3;
+const XK_INSERT: Keysym = 0xff63;
+const XK_LEFT: Keysym = 0xff51;
+const XK_RETURN: Keysym = 0xff0d;
+const XK_TAB: Keysym = 0xff09;
+const XK_V: Keysym = 0x0076;
+
+/// How long the window manager gets to focus the target window.
+const FOCUS_TIMEOUT: Duration = Duration::from_millis(500);
+/// Lets clients see a keymap change before keys are sent through it.
+const REMAP_DELAY: Duration = Duration::from_millis(20);
+
+/// The key combination that pastes the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keystroke {
+    CtrlV,
+    /// What terminals use, where Ctrl+V is a control character.
+    CtrlShiftV,
+    ShiftInsert,
+}
+
+impl Keystroke {
+    fn keysyms(self) -> &'static [Keysym] {
+        match self {
+            Keystroke::CtrlV => &[XK_CONTROL_L, XK_V],
+            Keystroke::CtrlShiftV => &[XK_CONTROL_L, XK_SHIFT_L, XK_V],
+            Keystroke::ShiftInsert => &[XK_SHIFT_L, XK_INSERT],
+        }
+    }
+}
+
+/// Synthesizes key presses with the XTEST extension.
+pub struct Keyboard {
+    conn: RustConnection,
+    root: Window,
+    min_keycode: Keycode,
+    keysyms_per_keycode: usize,
+    /// The keymap, `keysyms_per_keycode` entries per keycode.
+    keysyms: Vec<Keysym>,
+    /// A keycode without symbols, borrowed for keysyms the keymap lacks.
+    spare: Option<Keycode>,
+}
+
+impl Keyboard {
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = RustConnection::connect(None)?;
+        if conn
+            .extension_information(x11rb::protocol::xtest::X11_EXTENSION_NAME)?
+            .is_none()
+        {
+            return Err(anyhow!("the X server has no XTEST extension"));
+        }
+        conn.xtest_get_version(2, 2)?.reply()?;
+
+        let root = conn.setup().roots[screen_num].root;
+        let min_keycode = conn.setup().min_keycode;
+        let count = conn.setup().max_keycode - min_keycode + 1;
+        let mapping = conn.get_keyboard_mapping(min_keycode, count)?.reply()?;
+        let keysyms_per_keycode = mapping.keysyms_per_keycode as usize;
+        let spare = mapping
+            .keysyms
+            .chunks(keysyms_per_keycode)
+            .rposition(|syms| syms.iter().all(|&sym| sym == 0))
+            .map(|i| min_keycode + i as u8);
+        Ok(Self {
+            conn,
+            root,
+            min_keycode,
+            keysyms_per_keycode,
+            keysyms: mapping.keysyms,
+            spare,
+        })
+    }
+
+    pub fn active_window(&self) -> Result<Option<Window>> {
+        active_window(&self.conn, self.root)
+    }
+
+    /// `WM_CLASS` of the application behind `window`.
+    pub fn window_class(&self, window: Window) -> Result<Option<WmClass>> {
+        get_client_class(&self.conn, window)
+    }
+
+    /// Focuses `window` and waits until the window manager reports it
+    /// active.
+    pub fn focus(&self, window: Window) -> Result<()> {
+        if self.active_window()? == Some(window) {
+            return Ok(());
+        }
+        activate_window_with(&self.conn, self.root, window)?;
+        let start = Instant::now();
+        while start.elapsed() < FOCUS_TIMEOUT {
+            if self.active_window()? == Some(window) {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        Err(anyhow!("window {:#x} did not get the focus", window))
+    }
+
+    /// Presses and releases `keystroke`.
+    pub fn press(&self, keystroke: Keystroke) -> Result<()> {
+        self.press_keysyms(keystroke.keysyms())
+    }
+
+    /// Moves the text cursor `count` characters to the left.
+    pub fn move_left(&self, count: usize) -> Result<()> {
+        for _ in 0..count {
+            self.press_keysyms(&[XK_LEFT])?;
+        }
+        Ok(())
+    }
+
+    /// Types `text` key by key, for clients that ignore the paste
+    /// keystroke. Characters missing from the keymap are typed through a
+    /// spare keycode.
+    pub fn type_text(&self, text: &str) -> Result<()> {
+        let result = text
+            .chars()
+            .try_for_each(|c| self.press_keysyms(&[keysym(c)]));
+        if let Some(spare) = self.spare {
+            self.remap(spare, 0)?;
+        }
+        result
+    }
+
+    /// Holds the keysyms down in order, then releases them in reverse.
+    fn press_keysyms(&self, keysyms: &[Keysym]) -> Result<()> {
+        let mut keycodes = Vec::new();
+        for &sym in keysyms {
+            match self.find(sym) {
+                // The second column is the shifted symbol.
+                Some((keycode, 1)) if !keysyms.contains(&XK_SHIFT_L) => {
+                    keycodes.push(self.find(XK_SHIFT_L).map_or(0, |(code, _)| code));
+                    keycodes.push(keycode);
+                }
+                Some((keycode, 0)) => keycodes.push(keycode),
+                _ => {
+                    let spare = self
+                        .spare
+                        .ok_or_else(|| anyhow!("no keycode for keysym {:#x}", sym))?;
+                    self.remap(spare, sym)?;
+                    keycodes.push(spare);
+                }
+            }
+        }
+        keycodes.retain(|&code| code != 0);
+
+        for &keycode in &keycodes {
+            self.fake(KEY_PRESS_EVENT, keycode)?;
+        }
+        for &keycode in keycodes.iter().rev() {
+            self.fake(KEY_RELEASE_EVENT, keycode)?;
+        }
+        self.conn.sync()?;
+        Ok(())
+    }
+
+    /// The keycode producing `sym` and its column, preferring unshifted.
+    fn find(&self, sym: Keysym) -> Option<(Keycode, usize)> {
+        for column in 0..self.keysyms_per_keycode.min(2) {
+            let found = self
+                .keysyms
+                .chunks(self.keysyms_per_keycode)
+                .position(|syms| syms[column] == sym);
+            if let Some(i) = found {
+                return Some((self.min_keycode + i as u8, column));
+            }
+        }
+        None
+    }
+
+    /// Binds `keycode` to `sym` in every column, or unbinds it with 0.
+    fn remap(&self, keycode: Keycode, sym: Keysym) -> Result<()> {
+        let syms = vec![sym; self.keysyms_per_keycode];
+        self.conn
+            .change_keyboard_mapping(1, keycode, self.keysyms_per_keycode as u8, &syms)?;
+        self.conn.sync()?;
+        std::thread::sleep(REMAP_DELAY);
+        Ok(())
+    }
+
+    fn fake(&self, kind: u8, keycode: Keycode) -> Result<()> {
+        self.conn
+            .xtest_fake_input(kind, keycode, x11rb::CURRENT_TIME, self.root, 0, 0, 0)?;
+        Ok(())
+    }
+}
+
+/// The keysym typing `c`: Latin-1 maps directly, the rest of Unicode
+/// lives at `0x0100_0000 + code point`.
+fn keysym(c: char) -> Keysym {
+    match c {
+        '\n' => XK_RETURN,
+        '\t' => XK_TAB,
+        ' '..='~' | '\u{a0}'..='\u{ff}' => c as Keysym,
+        c => 0x0100_0000 + c as Keysym,
+    }
+}
@@ -1,16 +1,203 @@
+use crate::action::WindowState;
 use crate::atom::Atoms;
-use anyhow::{anyhow, Result};
-use x11rb::atom_manager;
+use anyhow::{Result, anyhow};
 use x11rb::connection::Connection;
+use x11rb::errors::ReplyError;
 use x11rb::protocol::xproto::*;
-use x11rb::protocol::Event;
 use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
 
-#[derive(Debug)]
+/// `_NET_WM_DESKTOP` of windows shown on every desktop.
+pub const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+
+/// `UrgencyHint` in the `WM_HINTS` flags.
+const URGENCY_HINT: u32 = 1 << 8;
+
+/// `IconicState` in `WM_STATE`.
+pub(crate) const ICONIC_STATE: u32 = 3;
+
+/// The source indication of requests on behalf of the user, as pagers and
+/// task lists send them.
+pub(crate) const SOURCE_PAGER: u32 = 2;
+
+#[derive(Debug, Clone)]
 pub struct WindowInfo {
     pub id: u32,
+    /// [`ALL_DESKTOPS`] for sticky windows.
     pub desktop: u32,
     pub title: String,
+    pub class: Option<WmClass>,
+    pub pid: Option<u32>,
+    /// Set through `WM_HINTS` or `_NET_WM_STATE_DEMANDS_ATTENTION`.
+    pub urgent: bool,
+    /// Iconic, or hidden by the window manager.
+    pub minimized: bool,
+    /// The `_NET_WM_STATE`s the window is in.
+    pub states: Vec<WindowState>,
+}
+
+/// The two parts of `WM_CLASS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WmClass {
+    pub instance: String,
+    pub class: String,
+}
+
+/// `WM_CLASS` of `window` (`instance\0class\0`), if it has one.
+pub fn get_wm_class<C: Connection>(conn: &C, window: u32) -> Result<Option<WmClass>> {
+    let reply = conn
+        .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?
+        .reply()?;
+    let mut parts = reply
+        .value
+        .split(|&b| b == 0)
+        .map(|part| String::from_utf8_lossy(part).into_owned());
+    let instance = parts.next().unwrap_or_default();
+    let class = parts.next().unwrap_or_default();
+    if instance.is_empty() && class.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(WmClass { instance, class }))
+}
+
+/// `WM_CLASS` of the application behind `window`. Windows that own a
+/// selection are often hidden helpers without one, so this falls back to a
+/// managed window (`_NET_CLIENT_LIST`) created by the same X client.
+pub fn get_client_class<C: Connection>(conn: &C, window: u32) -> Result<Option<WmClass>> {
+    if let Some(class) = get_wm_class(conn, window)? {
+        return Ok(Some(class));
+    }
+
+    let atoms = Atoms::new(conn)?;
+    // Resource ids of one client share the bits outside the id mask.
+    let base_mask = !conn.setup().resource_id_mask;
+    for screen in &conn.setup().roots {
+        let reply = conn
+            .get_property(
+                false,
+                screen.root,
+                atoms.net_client_list,
+                AtomEnum::WINDOW,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+        let Some(clients) = reply.value32() else {
+            continue;
+        };
+        for client in clients.filter(|c| c & base_mask == window & base_mask) {
+            if let Some(class) = get_wm_class(conn, client)? {
+                return Ok(Some(class));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The window `_NET_ACTIVE_WINDOW` names, if any.
+pub fn active_window<C: Connection>(conn: &C, root: Window) -> Result<Option<Window>> {
+    let atoms = Atoms::new(conn)?;
+    let reply = conn
+        .get_property(false, root, atoms.net_active_window, AtomEnum::WINDOW, 0, 1)?
+        .reply()?;
+    Ok(reply
+        .value32()
+        .and_then(|mut ids| ids.next())
+        .filter(|&id| id != x11rb::NONE))
+}
+
+/// `_NET_WM_PID` of `window`, if it sets one.
+pub fn get_pid<C: Connection>(conn: &C, window: Window) -> Result<Option<u32>> {
+    let atoms = Atoms::new(conn)?;
+    let reply = conn
+        .get_property(false, window, atoms.net_wm_pid, AtomEnum::CARDINAL, 0, 1)?
+        .reply()?;
+    Ok(reply.value32().and_then(|mut pids| pids.next()))
+}
+
+/// The active window and its `_NET_WM_PID`, on a connection of its own.
+pub fn get_active_window() -> Result<Option<(Window, Option<u32>)>> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let Some(window) = active_window(&conn, root)? else {
+        return Ok(None);
+    };
+    Ok(Some((window, get_pid(&conn, window)?)))
+}
+
+/// Sends an EWMH client message about `window` to the window manager.
+pub(crate) fn send_message<C: Connection>(
+    conn: &C,
+    root: Window,
+    window: Window,
+    message: Atom,
+    data: [u32; 5],
+) -> Result<()> {
+    let event = ClientMessageEvent::new(32, window, message, data);
+    conn.send_event(
+        false,
+        root,
+        EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+        event,
+    )?;
+    Ok(())
+}
+
+/// The atoms in the window manager's `_NET_SUPPORTED`.
+pub(crate) fn supported<C: Connection>(conn: &C, root: Window, atoms: &Atoms) -> Result<Vec<Atom>> {
+    let reply = conn
+        .get_property(
+            false,
+            root,
+            atoms.net_supported,
+            AtomEnum::ATOM,
+            0,
+            u32::MAX,
+        )?
+        .reply()?;
+    Ok(reply.value32().map(Iterator::collect).unwrap_or_default())
+}
+
+/// A current server timestamp, read from the `PropertyNotify` of a
+/// zero-length append to a scratch window. Window managers reject focus
+/// requests stamped `CurrentTime` or older than the last user input.
+///
+/// The event is awaited on a connection of its own, so events queued on
+/// the callers' connections are left for them.
+pub(crate) fn server_time() -> Result<Timestamp> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let window = conn.generate_id()?;
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        root,
+        -1,
+        -1,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+    )?;
+    conn.change_property8(
+        PropMode::APPEND,
+        window,
+        AtomEnum::WM_NAME,
+        AtomEnum::STRING,
+        &[],
+    )?;
+    conn.flush()?;
+    let time = loop {
+        if let x11rb::protocol::Event::PropertyNotify(ev) = conn.wait_for_event()?
+            && ev.window == window
+        {
+            break ev.time;
+        }
+    };
+    // The scratch window goes away with the connection.
+    Ok(time)
 }
 
 fn get_window_title<C: Connection>(
@@ -40,52 +227,285 @@ fn get_window_title<C: Connection>(
     Ok(title)
 }
 
+/// The managed windows, topmost first when the window manager publishes
+/// the stacking order.
 pub fn list_windows() -> Result<Vec<WindowInfo>> {
     let (conn, screen_num) = RustConnection::connect(None)?;
-    let screen = &conn.setup().roots[screen_num];
-    let root = screen.root;
+    let root = conn.setup().roots[screen_num].root;
+    list_windows_with(&conn, root)
+}
 
-    Atoms::load(&conn)?;
-    let atoms = Atoms::get().expect("Atoms should be initialized");
+pub fn list_windows_with<C: Connection>(conn: &C, root: Window) -> Result<Vec<WindowInfo>> {
+    let atoms = Atoms::new(conn)?;
 
-    // Intern atoms
-    let net_client_list = atoms.net_client_list;
-    let net_wm_name = conn.intern_atom(false, b"_NET_WM_NAME")?.reply()?.atom;
-    let utf8_string = conn.intern_atom(false, b"UTF8_STRING")?.reply()?.atom;
-    println!("-> {}", utf8_string);
-    let wm_name = AtomEnum::WM_NAME;
-    let net_wm_desktop = conn.intern_atom(false, b"_NET_WM_DESKTOP")?.reply()?.atom;
+    let stacking = get_windows(conn, root, atoms.net_client_list_stacking)?;
+    let window_ids = match stacking {
+        Some(ids) => ids.into_iter().rev().collect(),
+        None => get_windows(conn, root, atoms.net_client_list)?
+            .ok_or(anyhow!("Failed to parse window list"))?,
+    };
+
+    let mut result = Vec::new();
+    for window in window_ids {
+        match get_window_info(conn, window, &atoms) {
+            Ok(info) => result.push(info),
+            // Closed since the list was read.
+            Err(e) if is_x11_error(&e) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(result)
+}
 
+fn get_window_info<C: Connection>(conn: &C, window: Window, atoms: &Atoms) -> Result<WindowInfo> {
+    let desktop = get_cardinal(conn, window, atoms.net_wm_desktop)?.unwrap_or(0);
+    let title = get_window_title(
+        conn,
+        window,
+        atoms.net_wm_name,
+        atoms.utf8_string,
+        atoms.wm_name,
+    )
+    .unwrap_or_else(|_| String::from("<Unknown>"));
+
+    let net_states = get_net_wm_state(conn, window)?;
+    Ok(WindowInfo {
+        id: window,
+        desktop,
+        title,
+        class: get_wm_class(conn, window)?,
+        pid: get_pid(conn, window)?,
+        urgent: has_urgency_hint(conn, window)?
+            || net_states.contains(&atoms.net_wm_state_demands_attention),
+        minimized: get_wm_state(conn, window)? == Some(ICONIC_STATE)
+            || net_states.contains(&atoms.net_wm_state_hidden),
+        states: WindowState::ALL
+            .into_iter()
+            .filter(|state| net_states.contains(&state.atom(atoms)))
+            .collect(),
+    })
+}
+
+/// Whether the server refused a request, e.g. with `BadWindow`, as
+/// opposed to the connection failing.
+fn is_x11_error(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<ReplyError>(),
+        Some(ReplyError::X11Error(_))
+    )
+}
+
+/// A window list property of the root window, `None` when it is unset.
+fn get_windows<C: Connection>(conn: &C, root: Window, property: Atom) -> Result<Option<Vec<u32>>> {
     let reply = conn
-        .get_property(false, root, net_client_list, AtomEnum::WINDOW, 0, u32::MAX)?
+        .get_property(false, root, property, AtomEnum::WINDOW, 0, u32::MAX)?
         .reply()?;
+    Ok(reply.value32().map(Iterator::collect))
+}
 
-    if reply.format != 32 {
-        return Err(anyhow!("Unexpected format from _NET_CLIENT_LIST"));
-    }
+/// The first value of a `CARDINAL` property.
+pub(crate) fn get_cardinal<C: Connection>(
+    conn: &C,
+    window: Window,
+    property: Atom,
+) -> Result<Option<u32>> {
+    let reply = conn
+        .get_property(false, window, property, AtomEnum::CARDINAL, 0, 1)?
+        .reply()?;
+    Ok(reply.value32().and_then(|mut values| values.next()))
+}
 
-    let window_ids = reply
+fn has_urgency_hint<C: Connection>(conn: &C, window: Window) -> Result<bool> {
+    let hints = conn
+        .get_property(false, window, AtomEnum::WM_HINTS, AtomEnum::WM_HINTS, 0, 1)?
+        .reply()?;
+    Ok(hints
         .value32()
-        .ok_or(anyhow!("Failed to parse window list"))?;
-    let mut result = Vec::new();
+        .and_then(|mut flags| flags.next())
+        .is_some_and(|flags| flags & URGENCY_HINT != 0))
+}
 
-    for window in window_ids {
-        // Get desktop
-        let desktop_reply = conn
-            .get_property(false, window, net_wm_desktop, AtomEnum::CARDINAL, 0, 1)?
-            .reply()?;
-        let desktop = desktop_reply.value32().unwrap().next().unwrap_or(0);
+/// The atoms in `_NET_WM_STATE` of `window`.
+pub(crate) fn get_net_wm_state<C: Connection>(conn: &C, window: Window) -> Result<Vec<Atom>> {
+    let atoms = Atoms::new(conn)?;
+    let reply = conn
+        .get_property(
+            false,
+            window,
+            atoms.net_wm_state,
+            AtomEnum::ATOM,
+            0,
+            u32::MAX,
+        )?
+        .reply()?;
+    Ok(reply.value32().map(Iterator::collect).unwrap_or_default())
+}
 
-        // Get title
-        let title = get_window_title(&conn, window, net_wm_name, utf8_string, wm_name.into())
-            .unwrap_or_else(|_| String::from("<Unknown>"));
+/// `_NET_WM_ICON` of `window` as PNG, in the smallest size at least
+/// `size` pixels wide, else the largest.
+pub fn get_icon_png<C: Connection>(conn: &C, window: Window, size: u32) -> Result<Option<Vec<u8>>> {
+    let atoms = Atoms::new(conn)?;
+    let reply = conn
+        .get_property(
+            false,
+            window,
+            atoms.net_wm_icon,
+            AtomEnum::CARDINAL,
+            0,
+            u32::MAX,
+        )?
+        .reply()?;
+    let Some(data) = reply.value32() else {
+        return Ok(None);
+    };
+    let data: Vec<u32> = data.collect();
 
-        result.push(WindowInfo {
-            id: window,
-            desktop,
-            title,
-        });
+    // The property holds width, height and width * height ARGB pixels per
+    // icon.
+    let mut icons = Vec::new();
+    let mut rest = &data[..];
+    while let [width, height, pixels @ ..] = rest {
+        let len = (*width as usize).saturating_mul(*height as usize);
+        if len == 0 || pixels.len() < len {
+            break;
+        }
+        icons.push((*width, *height, &pixels[..len]));
+        rest = &pixels[len..];
     }
+    let best = icons
+        .iter()
+        .filter(|(width, _, _)| *width >= size)
+        .min_by_key(|(width, _, _)| *width)
+        .or_else(|| icons.iter().max_by_key(|(width, _, _)| *width));
+    let Some(&(width, height, pixels)) = best else {
+        return Ok(None);
+    };
 
-    Ok(result)
+    let rgba = pixels
+        .iter()
+        .flat_map(|argb| {
+            let [a, r, g, b] = argb.to_be_bytes();
+            [r, g, b, a]
+        })
+        .collect();
+    let image =
+        image::RgbaImage::from_raw(width, height, rgba).ok_or(anyhow!("Invalid icon size"))?;
+    let mut png = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut png, image::ImageFormat::Png)?;
+    Ok(Some(png.into_inner()))
+}
+
+/// [`get_icon_png`] on a connection of its own.
+pub fn read_icon_png(window: Window, size: u32) -> Result<Option<Vec<u8>>> {
+    let (conn, _) = RustConnection::connect(None)?;
+    get_icon_png(&conn, window, size)
+}
+
+/// Calls `on_change` with every window that becomes active, until the
+/// connection fails.
+pub fn watch_active_window(mut on_change: impl FnMut(Window)) -> Result<()> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let atoms = Atoms::new(&conn)?;
+
+    conn.change_window_attributes(
+        root,
+        &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+    )?;
+    conn.flush()?;
+    if let Some(window) = active_window(&conn, root)? {
+        on_change(window);
+    }
+    loop {
+        if let x11rb::protocol::Event::PropertyNotify(ev) = conn.wait_for_event()?
+            && ev.atom == atoms.net_active_window
+            && let Some(window) = active_window(&conn, root)?
+        {
+            on_change(window);
+        }
+    }
+}
+
+/// Activates `window`: switches to its desktop when it is on another
+/// one, restores it when minimized, raises it and focuses it.
+pub fn activate_window(window: Window) -> Result<()> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    activate_window_with(&conn, root, window)
+}
+
+/// [`activate_window`] on an existing connection. Fails when the window
+/// manager does not support `_NET_ACTIVE_WINDOW`, or `_NET_CURRENT_DESKTOP`
+/// when the desktop has to change. Requests are sent the way a pager
+/// sends them, since they come from the user.
+pub fn activate_window_with<C: Connection>(conn: &C, root: Window, window: Window) -> Result<()> {
+    let atoms = Atoms::new(conn)?;
+    let supported = supported(conn, root, &atoms)?;
+    if !supported.contains(&atoms.net_active_window) {
+        return Err(anyhow!(
+            "the window manager does not support _NET_ACTIVE_WINDOW"
+        ));
+    }
+    let time = server_time()?;
+
+    let desktop = get_cardinal(conn, window, atoms.net_wm_desktop)?;
+    let current = get_cardinal(conn, root, atoms.net_current_desktop)?;
+    if let (Some(desktop), Some(current)) = (desktop, current)
+        && desktop != ALL_DESKTOPS
+        && desktop != current
+    {
+        if !supported.contains(&atoms.net_current_desktop) {
+            return Err(anyhow!(
+                "the window manager does not support _NET_CURRENT_DESKTOP"
+            ));
+        }
+        send_message(
+            conn,
+            root,
+            root,
+            atoms.net_current_desktop,
+            [desktop, time, 0, 0, 0],
+        )?;
+    }
+
+    // Mapping an iconic window asks the window manager to restore it.
+    if get_wm_state(conn, window)? == Some(ICONIC_STATE) {
+        conn.map_window(window)?;
+    }
+
+    if supported.contains(&atoms.net_restack_window) {
+        send_message(
+            conn,
+            root,
+            window,
+            atoms.net_restack_window,
+            [SOURCE_PAGER, x11rb::NONE, u32::from(StackMode::ABOVE), 0, 0],
+        )?;
+    } else {
+        conn.configure_window(
+            window,
+            &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+        )?;
+    }
+
+    let current = active_window(conn, root)?.unwrap_or(x11rb::NONE);
+    send_message(
+        conn,
+        root,
+        window,
+        atoms.net_active_window,
+        [SOURCE_PAGER, time, current, 0, 0],
+    )?;
+    conn.flush()?;
+    Ok(())
+}
+
+/// The ICCCM `WM_STATE` of `window`, set by the window manager.
+pub(crate) fn get_wm_state<C: Connection>(conn: &C, window: Window) -> Result<Option<u32>> {
+    let atoms = Atoms::new(conn)?;
+    let reply = conn
+        .get_property(false, window, atoms.wm_state, atoms.wm_state, 0, 2)?
+        .reply()?;
+    Ok(reply.value32().and_then(|mut state| state.next()))
 }
@@ -0,0 +1,106 @@
+//! Window management actions against a minimal EWMH window manager.
+//! Skipped when there is no display.
+
+mod common;
+
+use common::{ICONIC_STATE, Wm, atom, client, connect, property, serial, wait_until};
+use x_window::action::{WindowManager, WindowState};
+use x11rb::protocol::xproto::*;
+
+#[test]
+fn closes_the_window() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let wm = Wm::start_full();
+    let (conn, root) = connect();
+    let window = client(&conn, root, 0);
+
+    WindowManager::connect().unwrap().close(window).unwrap();
+    wait_until("the window to be destroyed", || {
+        conn.get_window_attributes(window).unwrap().reply().is_err()
+    });
+    let close = atom(&conn, "_NET_CLOSE_WINDOW");
+    let message = wm
+        .messages()
+        .into_iter()
+        .find(|m| m.type_ == close && m.window == window)
+        .unwrap();
+    assert_ne!(message.data[0], x11rb::CURRENT_TIME);
+    assert_eq!(message.data[1], 2);
+}
+
+#[test]
+fn minimizes_the_window() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let _wm = Wm::start_full();
+    let (conn, root) = connect();
+    let window = client(&conn, root, 0);
+
+    WindowManager::connect().unwrap().minimize(window).unwrap();
+    wait_until("the window to be iconic", || {
+        property(&conn, window, "WM_STATE").first() == Some(&ICONIC_STATE)
+    });
+}
+
+#[test]
+fn toggles_states() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let _wm = Wm::start_full();
+    let (conn, root) = connect();
+    let window = client(&conn, root, 0);
+    let manager = WindowManager::connect().unwrap();
+
+    manager.toggle_maximized(window).unwrap();
+    wait_until("the window to be maximized", || {
+        manager.states(window).unwrap() == [WindowState::MaximizedHorz, WindowState::MaximizedVert]
+    });
+    manager.toggle(window, WindowState::Above).unwrap();
+    wait_until("the window to be above", || {
+        manager
+            .states(window)
+            .unwrap()
+            .contains(&WindowState::Above)
+    });
+    manager.toggle_maximized(window).unwrap();
+    wait_until("the window to be restored", || {
+        manager.states(window).unwrap() == [WindowState::Above]
+    });
+}
+
+#[test]
+fn moves_the_window_to_another_desktop() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let _wm = Wm::start_full();
+    let (conn, root) = connect();
+    let window = client(&conn, root, 0);
+    let manager = WindowManager::connect().unwrap();
+
+    manager.move_to_desktop(window, 1).unwrap();
+    wait_until("the window to move", || {
+        property(&conn, window, "_NET_WM_DESKTOP") == [1]
+    });
+    let count = manager.desktop_count().unwrap().unwrap();
+    assert!(manager.move_to_desktop(window, count).is_err());
+}
+
+#[test]
+fn fails_when_the_window_manager_lacks_support() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let (conn, root) = connect();
+    let _wm = Wm::start(&["_NET_WM_STATE"]);
+    let window = client(&conn, root, 0);
+    let manager = WindowManager::connect().unwrap();
+
+    assert!(manager.close(window).is_err());
+    assert!(manager.toggle(window, WindowState::Fullscreen).is_err());
+    assert!(manager.move_to_desktop(window, 0).is_err());
+}
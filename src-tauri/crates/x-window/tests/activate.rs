@@ -0,0 +1,106 @@
+//! Window activation against a minimal EWMH window manager. Skipped when
+//! there is no display.
+
+mod common;
+
+use common::{Wm, atom, client, connect, iconify, property, serial, wait_until};
+use x_window::window::activate_window;
+use x11rb::protocol::xproto::*;
+
+#[test]
+fn switches_to_the_desktop_of_the_window() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let wm = Wm::start_full();
+    let (conn, root) = connect();
+    let window = client(&conn, root, 1);
+
+    activate_window(window).unwrap();
+    wait_until("the window to be active", || {
+        property(&conn, root, "_NET_ACTIVE_WINDOW") == [window]
+    });
+    assert_eq!(property(&conn, root, "_NET_CURRENT_DESKTOP"), [1]);
+
+    let messages = wm.messages();
+    let desktop = atom(&conn, "_NET_CURRENT_DESKTOP");
+    assert!(
+        messages
+            .iter()
+            .any(|m| m.type_ == desktop && m.data[0] == 1)
+    );
+    let active = atom(&conn, "_NET_ACTIVE_WINDOW");
+    let activation = messages
+        .iter()
+        .find(|m| m.type_ == active && m.window == window)
+        .unwrap();
+    // Sent as a pager, with a real timestamp.
+    assert_eq!(activation.data[0], 2);
+    assert_ne!(activation.data[1], x11rb::CURRENT_TIME);
+}
+
+#[test]
+fn stays_on_the_current_desktop() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let wm = Wm::start_full();
+    let (conn, root) = connect();
+    let window = client(&conn, root, 0);
+
+    activate_window(window).unwrap();
+    wait_until("the window to be active", || {
+        property(&conn, root, "_NET_ACTIVE_WINDOW") == [window]
+    });
+    let desktop = atom(&conn, "_NET_CURRENT_DESKTOP");
+    assert!(wm.messages().iter().all(|m| m.type_ != desktop));
+}
+
+#[test]
+fn restores_and_raises_a_minimized_window() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let wm = Wm::start_full();
+    let (conn, root) = connect();
+    let window = client(&conn, root, 0);
+    let other = client(&conn, root, 0);
+    iconify(&conn, root, window);
+
+    activate_window(window).unwrap();
+    wait_until("the window to be mapped", || {
+        conn.get_window_attributes(window)
+            .unwrap()
+            .reply()
+            .unwrap()
+            .map_state
+            == MapState::VIEWABLE
+    });
+    wait_until("the window to be on top", || {
+        let children = conn.query_tree(root).unwrap().reply().unwrap().children;
+        let position = |w| children.iter().position(|&c| c == w);
+        position(window) > position(other)
+    });
+    let restack = atom(&conn, "_NET_RESTACK_WINDOW");
+    assert!(
+        wm.messages()
+            .iter()
+            .any(|m| m.type_ == restack && m.window == window)
+    );
+}
+
+#[test]
+fn fails_when_the_window_manager_lacks_support() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let (conn, root) = connect();
+    {
+        let _wm = Wm::start(&["_NET_ACTIVE_WINDOW"]);
+        let window = client(&conn, root, 1);
+        assert!(activate_window(window).is_err());
+    }
+    let _wm = Wm::start(&[]);
+    let window = client(&conn, root, 0);
+    assert!(activate_window(window).is_err());
+}
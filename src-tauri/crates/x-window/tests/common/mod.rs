@@ -0,0 +1,398 @@
+//! A minimal EWMH window manager and test clients, for running the window
+//! tests under Xvfb, e.g. `xvfb-run cargo test`.
+
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+const NORMAL_STATE: u32 = 1;
+pub const ICONIC_STATE: u32 = 3;
+pub const DESKTOPS: u32 = 2;
+
+/// The tests share the root window and its window manager, so they take
+/// turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// `None` without a display, so the test can be skipped.
+pub fn serial() -> Option<MutexGuard<'static, ()>> {
+    std::env::var_os("DISPLAY")?;
+    Some(SERIAL.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+pub fn connect() -> (RustConnection, Window) {
+    let (conn, screen_num) = RustConnection::connect(None).expect("failed to connect");
+    let root = conn.setup().roots[screen_num].root;
+    (conn, root)
+}
+
+pub fn atom(conn: &RustConnection, name: &str) -> Atom {
+    conn.intern_atom(false, name.as_bytes())
+        .unwrap()
+        .reply()
+        .unwrap()
+        .atom
+}
+
+pub fn property(conn: &RustConnection, window: Window, name: &str) -> Vec<u32> {
+    let name = atom(conn, name);
+    conn.get_property(false, window, name, AtomEnum::ANY, 0, u32::MAX)
+        .unwrap()
+        .reply()
+        .unwrap()
+        .value32()
+        .map(Iterator::collect)
+        .unwrap_or_default()
+}
+
+/// Polls `condition` for up to two seconds.
+pub fn wait_until(what: &str, mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// A client message the window manager received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Message {
+    pub window: Window,
+    pub type_: Atom,
+    pub data: [u32; 5],
+}
+
+/// A window manager on its own thread, running until dropped. It manages
+/// mapped windows, keeps `_NET_CLIENT_LIST`, two desktops and the active
+/// window, and logs every client message.
+pub struct Wm {
+    conn: RustConnection,
+    check: Window,
+    stop: Atom,
+    messages: Arc<Mutex<Vec<Message>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Wm {
+    /// Starts a window manager announcing the `supported` EWMH atoms.
+    pub fn start(supported: &[&str]) -> Self {
+        let (conn, root) = connect();
+        let supported: Vec<Atom> = supported.iter().map(|name| atom(&conn, name)).collect();
+        let stop = atom(&conn, "SHIORI_TEST_WM_STOP");
+        let check = conn.generate_id().unwrap();
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            check,
+            root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )
+        .unwrap();
+        conn.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new()
+                .event_mask(EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY),
+        )
+        .unwrap()
+        .check()
+        .expect("another window manager is running");
+
+        let set = |window: Window, name: &str, kind: AtomEnum, values: &[u32]| {
+            conn.change_property32(PropMode::REPLACE, window, atom(&conn, name), kind, values)
+                .unwrap();
+        };
+        set(root, "_NET_SUPPORTED", AtomEnum::ATOM, &supported);
+        set(root, "_NET_SUPPORTING_WM_CHECK", AtomEnum::WINDOW, &[check]);
+        set(
+            check,
+            "_NET_SUPPORTING_WM_CHECK",
+            AtomEnum::WINDOW,
+            &[check],
+        );
+        set(
+            root,
+            "_NET_NUMBER_OF_DESKTOPS",
+            AtomEnum::CARDINAL,
+            &[DESKTOPS],
+        );
+        set(root, "_NET_CURRENT_DESKTOP", AtomEnum::CARDINAL, &[0]);
+        set(root, "_NET_CLIENT_LIST", AtomEnum::WINDOW, &[]);
+        set(root, "_NET_CLIENT_LIST_STACKING", AtomEnum::WINDOW, &[]);
+        set(root, "_NET_ACTIVE_WINDOW", AtomEnum::WINDOW, &[]);
+        conn.flush().unwrap();
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let log = messages.clone();
+        let thread = std::thread::spawn(move || run(conn, root, stop, log));
+        let (conn, _) = connect();
+        Self {
+            conn,
+            check,
+            stop,
+            messages,
+            thread: Some(thread),
+        }
+    }
+
+    /// Every EWMH atom the window manager handles.
+    pub fn start_full() -> Self {
+        Self::start(&[
+            "_NET_ACTIVE_WINDOW",
+            "_NET_CURRENT_DESKTOP",
+            "_NET_WM_DESKTOP",
+            "_NET_RESTACK_WINDOW",
+            "_NET_CLOSE_WINDOW",
+            "_NET_WM_STATE",
+            "_NET_WM_STATE_MAXIMIZED_HORZ",
+            "_NET_WM_STATE_MAXIMIZED_VERT",
+            "_NET_WM_STATE_FULLSCREEN",
+            "_NET_WM_STATE_ABOVE",
+            "_NET_WM_STATE_STICKY",
+            "_NET_WM_STATE_SHADED",
+        ])
+    }
+
+    pub fn messages(&self) -> Vec<Message> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Wm {
+    fn drop(&mut self) {
+        let event = ClientMessageEvent::new(32, self.check, self.stop, [0; 5]);
+        self.conn
+            .send_event(false, self.check, EventMask::NO_EVENT, event)
+            .unwrap();
+        self.conn.flush().unwrap();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(conn: RustConnection, root: Window, stop: Atom, log: Arc<Mutex<Vec<Message>>>) {
+    let atom = |name: &str| atom(&conn, name);
+    let (wm_state, net_active_window, net_current_desktop, net_wm_desktop) = (
+        atom("WM_STATE"),
+        atom("_NET_ACTIVE_WINDOW"),
+        atom("_NET_CURRENT_DESKTOP"),
+        atom("_NET_WM_DESKTOP"),
+    );
+    let (net_restack_window, wm_change_state, net_close_window, net_wm_state) = (
+        atom("_NET_RESTACK_WINDOW"),
+        atom("WM_CHANGE_STATE"),
+        atom("_NET_CLOSE_WINDOW"),
+        atom("_NET_WM_STATE"),
+    );
+    let (net_client_list, net_client_list_stacking) =
+        (atom("_NET_CLIENT_LIST"), atom("_NET_CLIENT_LIST_STACKING"));
+    let mut clients: Vec<Window> = Vec::new();
+
+    let set_state = |window: Window, state: u32| {
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            wm_state,
+            wm_state,
+            &[state, x11rb::NONE],
+        )
+        .unwrap();
+    };
+    let publish = |clients: &[Window]| {
+        for list in [net_client_list, net_client_list_stacking] {
+            conn.change_property32(PropMode::REPLACE, root, list, AtomEnum::WINDOW, clients)
+                .unwrap();
+        }
+    };
+
+    loop {
+        let Ok(event) = conn.wait_for_event() else {
+            return;
+        };
+        match event {
+            Event::MapRequest(ev) => {
+                conn.map_window(ev.window).unwrap();
+                set_state(ev.window, NORMAL_STATE);
+                if !clients.contains(&ev.window) {
+                    clients.push(ev.window);
+                    publish(&clients);
+                }
+                let desktop = conn
+                    .get_property(false, ev.window, net_wm_desktop, AtomEnum::CARDINAL, 0, 1)
+                    .unwrap()
+                    .reply()
+                    .unwrap();
+                if desktop.value_len == 0 {
+                    conn.change_property32(
+                        PropMode::REPLACE,
+                        ev.window,
+                        net_wm_desktop,
+                        AtomEnum::CARDINAL,
+                        &[0],
+                    )
+                    .unwrap();
+                }
+            }
+            Event::ConfigureRequest(ev) => {
+                conn.configure_window(ev.window, &ConfigureWindowAux::from_configure_request(&ev))
+                    .unwrap();
+            }
+            Event::DestroyNotify(ev) => {
+                clients.retain(|&w| w != ev.window);
+                publish(&clients);
+            }
+            Event::ClientMessage(ev) => {
+                if ev.type_ == stop {
+                    return;
+                }
+                let data = ev.data.as_data32();
+                log.lock().unwrap().push(Message {
+                    window: ev.window,
+                    type_: ev.type_,
+                    data,
+                });
+                let window = ev.window;
+                if ev.type_ == net_current_desktop {
+                    conn.change_property32(
+                        PropMode::REPLACE,
+                        root,
+                        net_current_desktop,
+                        AtomEnum::CARDINAL,
+                        &[data[0]],
+                    )
+                    .unwrap();
+                } else if ev.type_ == net_active_window {
+                    conn.map_window(window).unwrap();
+                    set_state(window, NORMAL_STATE);
+                    conn.change_property32(
+                        PropMode::REPLACE,
+                        root,
+                        net_active_window,
+                        AtomEnum::WINDOW,
+                        &[window],
+                    )
+                    .unwrap();
+                    let _ = conn.set_input_focus(InputFocus::PARENT, window, x11rb::CURRENT_TIME);
+                } else if ev.type_ == net_restack_window {
+                    conn.configure_window(
+                        window,
+                        &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+                    )
+                    .unwrap();
+                } else if ev.type_ == wm_change_state && data[0] == ICONIC_STATE {
+                    conn.unmap_window(window).unwrap();
+                    set_state(window, ICONIC_STATE);
+                } else if ev.type_ == net_close_window {
+                    conn.destroy_window(window).unwrap();
+                } else if ev.type_ == net_wm_desktop {
+                    conn.change_property32(
+                        PropMode::REPLACE,
+                        window,
+                        net_wm_desktop,
+                        AtomEnum::CARDINAL,
+                        &[data[0]],
+                    )
+                    .unwrap();
+                } else if ev.type_ == net_wm_state {
+                    let mut states: Vec<Atom> = conn
+                        .get_property(false, window, net_wm_state, AtomEnum::ATOM, 0, u32::MAX)
+                        .unwrap()
+                        .reply()
+                        .unwrap()
+                        .value32()
+                        .map(Iterator::collect)
+                        .unwrap_or_default();
+                    for state in [data[1], data[2]].into_iter().filter(|&s| s != 0) {
+                        let has = states.contains(&state);
+                        // _NET_WM_STATE_REMOVE, _ADD and _TOGGLE.
+                        let want = match data[0] {
+                            0 => false,
+                            1 => true,
+                            _ => !has,
+                        };
+                        states.retain(|&s| s != state);
+                        if want {
+                            states.push(state);
+                        }
+                    }
+                    conn.change_property32(
+                        PropMode::REPLACE,
+                        window,
+                        net_wm_state,
+                        AtomEnum::ATOM,
+                        &states,
+                    )
+                    .unwrap();
+                }
+            }
+            _ => {}
+        }
+        conn.flush().unwrap();
+    }
+}
+
+/// A mapped, managed window on `desktop`.
+pub fn client(conn: &RustConnection, root: Window, desktop: u32) -> Window {
+    let window = conn.generate_id().unwrap();
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        root,
+        0,
+        0,
+        100,
+        100,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new(),
+    )
+    .unwrap();
+    conn.change_property32(
+        PropMode::REPLACE,
+        window,
+        atom(conn, "_NET_WM_DESKTOP"),
+        AtomEnum::CARDINAL,
+        &[desktop],
+    )
+    .unwrap();
+    conn.map_window(window).unwrap();
+    conn.flush().unwrap();
+    wait_until("the window to be managed", || {
+        property(conn, window, "WM_STATE").first() == Some(&NORMAL_STATE)
+    });
+    window
+}
+
+/// Minimizes `window` the way clients do, with `WM_CHANGE_STATE`.
+pub fn iconify(conn: &RustConnection, root: Window, window: Window) {
+    let event = ClientMessageEvent::new(
+        32,
+        window,
+        atom(conn, "WM_CHANGE_STATE"),
+        [ICONIC_STATE, 0, 0, 0, 0],
+    );
+    conn.send_event(
+        false,
+        root,
+        EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+        event,
+    )
+    .unwrap();
+    conn.flush().unwrap();
+    wait_until("the window to be iconic", || {
+        property(conn, window, "WM_STATE").first() == Some(&ICONIC_STATE)
+    });
+}
@@ -0,0 +1,134 @@
+//! Synthesized key presses against a real X server, e.g. under
+//! `xvfb-run cargo test`. Skipped when there is no display.
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use x_window::paste::{Keyboard, Keystroke};
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+const XK_CONTROL_L: Keysym = 0xffe3;
+const XK_SHIFT_L: Keysym = 0xffe1;
+
+/// The tests share the keyboard focus, so they take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// A focused window that reports key presses, or `None` without a
+/// display, so the test can be skipped.
+fn client() -> Option<(MutexGuard<'static, ()>, RustConnection)> {
+    std::env::var_os("DISPLAY")?;
+    let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    let (conn, screen_num) = RustConnection::connect(None).expect("failed to connect");
+    let window = conn.generate_id().unwrap();
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        conn.setup().roots[screen_num].root,
+        0,
+        0,
+        100,
+        100,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new().event_mask(EventMask::KEY_PRESS | EventMask::STRUCTURE_NOTIFY),
+    )
+    .unwrap();
+    conn.map_window(window).unwrap();
+    conn.flush().unwrap();
+    loop {
+        if let Event::MapNotify(_) = conn.wait_for_event().unwrap() {
+            break;
+        }
+    }
+    conn.set_input_focus(InputFocus::PARENT, window, x11rb::CURRENT_TIME)
+        .unwrap();
+    conn.flush().unwrap();
+    Some((serial, conn))
+}
+
+/// The key presses that arrive within a second.
+fn presses(conn: &RustConnection) -> Vec<KeyPressEvent> {
+    let mut presses = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while Instant::now() < deadline {
+        match conn.poll_for_event().unwrap() {
+            Some(Event::KeyPress(ev)) => presses.push(ev),
+            Some(_) => {}
+            None => std::thread::sleep(Duration::from_millis(10)),
+        }
+    }
+    presses
+}
+
+/// The keycode whose unshifted symbol is `sym`.
+fn keycode(conn: &RustConnection, sym: Keysym) -> Keycode {
+    let min = conn.setup().min_keycode;
+    let mapping = conn
+        .get_keyboard_mapping(min, conn.setup().max_keycode - min + 1)
+        .unwrap()
+        .reply()
+        .unwrap();
+    let i = mapping
+        .keysyms
+        .chunks(mapping.keysyms_per_keycode as usize)
+        .position(|syms| syms[0] == sym)
+        .unwrap();
+    min + i as u8
+}
+
+#[test]
+fn presses_the_paste_keystroke() {
+    let Some((_serial, conn)) = client() else {
+        return;
+    };
+    let keyboard = Keyboard::connect().unwrap();
+    keyboard.press(Keystroke::CtrlShiftV).unwrap();
+
+    let presses = presses(&conn);
+    let codes: Vec<Keycode> = presses.iter().map(|ev| ev.detail).collect();
+    assert_eq!(
+        codes,
+        [
+            keycode(&conn, XK_CONTROL_L),
+            keycode(&conn, XK_SHIFT_L),
+            keycode(&conn, 'v' as Keysym)
+        ]
+    );
+    let state = presses[2].state;
+    assert!(state.contains(KeyButMask::CONTROL) && state.contains(KeyButMask::SHIFT));
+}
+
+#[test]
+fn types_text_missing_from_the_keymap() {
+    let Some((_serial, conn)) = client() else {
+        return;
+    };
+    let keyboard = Keyboard::connect().unwrap();
+    keyboard.type_text("Hi ✓").unwrap();
+
+    let presses = presses(&conn);
+    let codes: Vec<Keycode> = presses.iter().map(|ev| ev.detail).collect();
+    assert_eq!(
+        codes[..4],
+        [
+            keycode(&conn, XK_SHIFT_L),
+            keycode(&conn, 'h' as Keysym),
+            keycode(&conn, 'i' as Keysym),
+            keycode(&conn, ' ' as Keysym)
+        ]
+    );
+    assert!(presses[1].state.contains(KeyButMask::SHIFT));
+    // ✓ went through a spare keycode, which is unbound again.
+    assert_eq!(presses.len(), 5);
+    let min = conn.setup().min_keycode;
+    let spare = conn
+        .get_keyboard_mapping(presses[4].detail, 1)
+        .unwrap()
+        .reply()
+        .unwrap();
+    assert!(presses[4].detail >= min);
+    assert!(spare.keysyms.iter().all(|&sym| sym == 0));
+}
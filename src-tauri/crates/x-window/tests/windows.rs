@@ -0,0 +1,65 @@
+//! Listing windows against a minimal EWMH window manager. Skipped when
+//! there is no display.
+
+mod common;
+
+use common::{Wm, atom, client, connect, iconify, property, serial, wait_until};
+use x_window::window::list_windows_with;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::wrapper::ConnectionExt as _;
+
+#[test]
+fn lists_clients_topmost_first() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let _wm = Wm::start_full();
+    let (conn, root) = connect();
+    let first = client(&conn, root, 0);
+    let second = client(&conn, root, 1);
+    iconify(&conn, root, first);
+
+    let windows = list_windows_with(&conn, root).unwrap();
+    let ids: Vec<u32> = windows.iter().map(|w| w.id).collect();
+    assert_eq!(ids, [second, first]);
+    assert_eq!(windows[0].desktop, 1);
+    assert!(!windows[0].minimized);
+    assert!(windows[1].minimized);
+}
+
+#[test]
+fn skips_windows_closed_since_the_list_was_read() {
+    let Some(_serial) = serial() else {
+        return;
+    };
+    let _wm = Wm::start_full();
+    let (conn, root) = connect();
+    let window = client(&conn, root, 0);
+    let closed = client(&conn, root, 0);
+    conn.destroy_window(closed).unwrap();
+    conn.flush().unwrap();
+    wait_until("the window to leave the client list", || {
+        !property(&conn, root, "_NET_CLIENT_LIST").contains(&closed)
+    });
+
+    // Lists that still name it, as when it closes while being listed.
+    for list in ["_NET_CLIENT_LIST", "_NET_CLIENT_LIST_STACKING"] {
+        conn.change_property32(
+            PropMode::REPLACE,
+            root,
+            atom(&conn, list),
+            AtomEnum::WINDOW,
+            &[window, closed],
+        )
+        .unwrap();
+    }
+    conn.flush().unwrap();
+
+    let ids: Vec<u32> = list_windows_with(&conn, root)
+        .unwrap()
+        .iter()
+        .map(|w| w.id)
+        .collect();
+    assert_eq!(ids, [window]);
+}
@@ -1,11 +1,31 @@
+use crate::config::{Config, ConfigError, HistoryConfig, HistoryKey, PasteConfig, WindowConfig};
+use crate::search::{
+    match_entry, match_snippet, match_transform, rank, rank_windows, SearchResult, SnippetResult,
+    TransformResult, WindowResult,
+};
+use application::autostart::{self, AutostartEntry};
+use application::dbus::DBusLauncher;
+use application::overrides::EntryChanges;
+use application::validate;
 use application::ApplicationService;
 use application::DesktopEntry;
+use clipboard::history::{History, HistoryItem, NewItem, TEXT_MIME};
+use clipboard::key::{Key, KeySource};
+use clipboard::privacy::Privacy;
+use clipboard::snippet::{Expansion, ParsedSnippet};
+use clipboard::transform::Transform;
+use clipboard::watcher::{ClipboardEvent, Selection};
 use fuzzy_matcher::skim::SkimMatcherV2;
-use fuzzy_matcher::FuzzyMatcher;
-use std::sync::Mutex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Instant;
-use tauri::State;
-use tracing::{info, instrument, warn};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tracing::level_filters::LevelFilter;
+use tracing::{info, info_span, instrument, warn};
+use tracing_subscriber::{reload, Registry};
+use x_window::action::{WindowManager, WindowState};
+use x_window::paste::Keyboard;
 
 use base64::{engine::general_purpose, Engine as _};
 use std::path::Path;
@@ -33,6 +53,233 @@ pub async fn read_icon_as_base64(path: String) -> Option<String> {
 
 pub(crate) struct AppState {
     pub(crate) service: Mutex<ApplicationService>,
+    /// The session bus, for `DBusActivatable` entries.
+    pub(crate) dbus: Option<DBusLauncher>,
+    pub(crate) config: Mutex<Config>,
+    pub(crate) log_level: reload::Handle<LevelFilter, Registry>,
+    /// `None` when the history database could not be opened.
+    pub(crate) history: Mutex<Option<History>>,
+    /// Shared with the clipboard watcher.
+    pub(crate) privacy: Arc<RwLock<Privacy>>,
+    /// The configured snippets and those in the snippets directory.
+    pub(crate) snippets: Mutex<Vec<ParsedSnippet>>,
+    /// The window that was active when shiori opened, where picked items
+    /// are pasted.
+    pub(crate) previous_window: Mutex<Option<u32>>,
+    /// Windows in the order they were last active, most recent first.
+    pub(crate) recent_windows: Mutex<Vec<u32>>,
+    /// What transforms were last searched for, kept while the same item
+    /// stays selected.
+    pub(crate) transform_source: Mutex<Option<TransformSource>>,
+}
+
+/// Applies a (re)loaded configuration to the running app and notifies the
+/// frontend with a `config-changed` event.
+pub(crate) fn apply_config(app: &AppHandle, config: Config) {
+    let state = app.state::<AppState>();
+
+    if let Err(e) = state.log_level.reload(LevelFilter::from(config.log_level)) {
+        warn!("failed to update log level: {}", e);
+    }
+    let resize = state.config.lock().unwrap().window != config.window;
+    if resize {
+        resize_window(app, &config.window);
+    }
+    let locales = if config.locales.is_empty() {
+        application::locale::from_env()
+    } else {
+        config.locales.clone()
+    };
+    let relocalized = {
+        let mut service = state.service.lock().unwrap();
+        service.set_custom_entries(config.custom_entries.clone());
+        let changed = service.locales != locales;
+        if changed {
+            service.set_locales(&locales);
+        }
+        changed
+    };
+    if relocalized {
+        let _ = app.emit("applications-changed", ());
+    }
+
+    if let Some(history) = state.history.lock().unwrap().as_mut() {
+        if let Err(e) = history.set_retention(config.history.retention()) {
+            warn!("failed to prune clipboard history: {}", e);
+        }
+    }
+    match config.privacy.privacy() {
+        Ok(mut privacy) => {
+            let mut current = state.privacy.write().unwrap();
+            privacy.incognito = current.incognito;
+            *current = privacy;
+        }
+        Err(e) => warn!("invalid privacy settings: {}", e),
+    }
+
+    *state.snippets.lock().unwrap() = config.all_snippets();
+
+    *state.config.lock().unwrap() = config.clone();
+    let _ = app.emit("config-changed", &config);
+}
+
+/// Sizes the main window as `[window]` asks.
+pub(crate) fn resize_window(app: &AppHandle, window: &WindowConfig) {
+    if let Some(main) = app.get_webview_window("main") {
+        let size = tauri::LogicalSize::new(window.width.get(), window.height.get());
+        if let Err(e) = main.set_size(size) {
+            warn!("failed to resize window: {}", e);
+        }
+    }
+}
+
+/// Rebuilds the entry index when the application directories changed since
+/// the entries were loaded, e.g. from a stale cache, and emits
+/// `applications-changed` so the frontend can refresh its results.
+pub(crate) fn revalidate_applications(app: &AppHandle) {
+    let _span = info_span!("revalidate_applications").entered();
+    let start = Instant::now();
+    let state = app.state::<AppState>();
+
+    let (stamps, locales) = {
+        let service = state.service.lock().unwrap();
+        (service.stamps.clone(), service.locales.clone())
+    };
+    if stamps == application::cache::collect_stamps() {
+        info!("entries are fresh, took {:?}", start.elapsed());
+        return;
+    }
+
+    let fresh = ApplicationService::new(&locales);
+    {
+        let mut service = state.service.lock().unwrap();
+        // A refresh in the meantime, e.g. from `update_entry`, has the
+        // edits `fresh` may have missed, and is at least as recent.
+        if service.stamps == stamps {
+            service.adopt_entries(fresh);
+        }
+        if let Err(e) = service.save_cache() {
+            warn!("failed to write entry cache: {}", e);
+        }
+    }
+
+    info!("entries reloaded, took {:?}", start.elapsed());
+    let _ = app.emit("applications-changed", ());
+}
+
+/// Opens the encrypted history with the configured key, or with
+/// `passphrase` when given. Without a usable key, e.g. when the keyring is
+/// unavailable, the history stays locked (`None`) until a passphrase is
+/// entered.
+pub(crate) fn open_history(config: &HistoryConfig, passphrase: Option<&str>) -> Option<History> {
+    let key = match passphrase {
+        Some(passphrase) => Key::passphrase(passphrase),
+        None => match config.key_source().load_or_create() {
+            Ok(key) => key,
+            Err(e) => {
+                warn!("clipboard history locked: {}", e);
+                return None;
+            }
+        },
+    };
+    match History::open(&History::path(), key, config.retention()) {
+        Ok(history) => Some(history),
+        Err(e) => {
+            warn!("failed to open clipboard history: {}", e);
+            None
+        }
+    }
+}
+
+/// Stores a clipboard change in the history, redacted by the privacy
+/// rules, and forwards it to the frontend as `clipboard-changed`.
+pub(crate) fn record_clipboard(app: &AppHandle, event: &ClipboardEvent) {
+    if !event.contents.is_empty() {
+        let state = app.state::<AppState>();
+        let item = state
+            .privacy
+            .read()
+            .unwrap()
+            .apply(NewItem::from_event(event));
+        let mut history = state.history.lock().unwrap();
+        if let Some(history) = history.as_mut() {
+            if let Err(e) = history.add(&item) {
+                warn!("failed to store clipboard item: {}", e);
+            }
+        }
+    }
+    forget_clipboard_source(&app.state::<AppState>());
+    let _ = app.emit("clipboard-changed", event);
+}
+
+/// Remembers the active window as the one to paste into, unless it is
+/// one of shiori's own.
+pub(crate) fn record_active_window(app: &AppHandle) {
+    match x_window::window::get_active_window() {
+        Ok(Some((_, Some(pid)))) if pid == std::process::id() => {}
+        Ok(window) => {
+            *app.state::<AppState>().previous_window.lock().unwrap() = window.map(|(id, _)| id)
+        }
+        Err(e) => warn!("failed to read the active window: {}", e),
+    }
+}
+
+/// Moves a window that became active to the front of the recently used
+/// ones.
+pub(crate) fn record_window_activation(app: &AppHandle, window: u32) {
+    let state = app.state::<AppState>();
+    let mut recent = state.recent_windows.lock().unwrap();
+    recent.retain(|&id| id != window);
+    recent.insert(0, window);
+}
+
+/// Focuses `window` and pastes into it: with the paste keystroke, or by
+/// typing `text` (else the clipboard text) for apps that ignore it. With
+/// `cursor`, a char index into `text`, the text cursor is moved back
+/// there afterwards.
+fn paste_into(
+    config: &PasteConfig,
+    window: u32,
+    text: Option<String>,
+    cursor: Option<usize>,
+) -> anyhow::Result<()> {
+    let keyboard = Keyboard::connect()?;
+    keyboard.focus(window)?;
+    let class = keyboard.window_class(window)?;
+    match config.keystroke(class.as_ref()) {
+        Some(keystroke) => keyboard.press(keystroke)?,
+        None => {
+            let text = match &text {
+                Some(text) => text.clone(),
+                None => clipboard::watcher::text_of(&clipboard::watcher::read_selection(
+                    Selection::Clipboard,
+                )?)
+                .unwrap_or_default(),
+            };
+            keyboard.type_text(&text)?;
+        }
+    }
+    if let (Some(text), Some(cursor)) = (&text, cursor) {
+        keyboard.move_left(text.chars().count().saturating_sub(cursor))?;
+    }
+    Ok(())
+}
+
+/// Removes the sensitive history items whose time is up and emits
+/// `history-changed` when there were any.
+pub(crate) fn expire_history(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let expired = match state.history.lock().unwrap().as_ref().map(|h| h.expire()) {
+        Some(Ok(expired)) => expired,
+        Some(Err(e)) => {
+            warn!("failed to expire clipboard items: {}", e);
+            return;
+        }
+        None => return,
+    };
+    if expired > 0 {
+        let _ = app.emit("history-changed", ());
+    }
 }
 
 fn list_applications_impl(
@@ -40,8 +287,10 @@ fn list_applications_impl(
     query: Option<&str>,
     offset: usize,
     limit: usize,
-) -> Vec<DesktopEntry> {
+) -> Vec<SearchResult> {
     // println!("list_applications is called in Rust {:?}", query);
+    let search = state.config.lock().unwrap().search.clone();
+    let limit = limit.min(search.max_results.get());
     let service = state.service.lock().unwrap();
     let matcher = SkimMatcherV2::default();
 
@@ -49,30 +298,31 @@ fn list_applications_impl(
         return service
             .entries
             .values()
+            .filter(|entry| !entry.no_display)
             .skip(offset)
             .take(limit)
-            .map(|entry| entry.clone())
+            .map(|entry| SearchResult::unmatched(entry.clone()))
             .collect();
     }
 
-    let mut matches: Vec<(i64, &DesktopEntry)> = service
-        .entries
-        .values()
-        .filter_map(|entry| {
-            matcher
-                .fuzzy_match(&entry.name, query.as_ref().unwrap())
-                .map(|score| (score, entry))
-        })
-        .collect();
-
-    matches.sort_by(|a, b| b.0.cmp(&a.0));
-
-    matches
-        .into_iter()
-        .skip(offset)
-        .take(limit)
-        .map(|(_, entry)| entry.clone())
-        .collect()
+    let query = query.unwrap();
+    rank(
+        service.entries.values().filter(|entry| !entry.no_display),
+        query,
+        |entry| {
+            match_entry(&matcher, entry, query, search.match_keywords)
+                .filter(|m| search.min_score.is_none_or(|min| m.score >= min))
+        },
+        |entry, indices| Some((entry, indices)),
+    )
+    .into_iter()
+    .skip(offset)
+    .take(limit)
+    .map(|(entry, indices)| SearchResult {
+        entry: entry.clone(),
+        matched_indices: indices,
+    })
+    .collect()
 }
 
 #[tauri::command]
@@ -82,7 +332,7 @@ pub(crate) fn list_applications(
     query: Option<&str>,
     offset: usize,
     limit: usize,
-) -> Vec<DesktopEntry> {
+) -> Vec<SearchResult> {
     let start = Instant::now();
     let res = list_applications_impl(state, query, offset, limit);
 
@@ -93,11 +343,166 @@ pub(crate) fn list_applications(
 
 #[tauri::command]
 #[instrument(skip(state))]
-pub(crate) fn launch_application(state: State<AppState>, app_id: &str) -> bool {
+pub(crate) fn launch_application(
+    state: State<AppState>,
+    app_id: &str,
+    activation_token: Option<&str>,
+) -> bool {
+    let start = Instant::now();
+    let service = state.service.lock().unwrap();
+
+    match service.launch_with_token(state.dbus.as_ref(), app_id, &[], activation_token) {
+        Ok(_) => {
+            info!("took {:?}", start.elapsed());
+            true
+        }
+        Err(e) => {
+            warn!("error: {}, took {:?}", e, start.elapsed());
+            false
+        }
+    }
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn launch_application_action(
+    state: State<AppState>,
+    app_id: &str,
+    action: &str,
+    activation_token: Option<&str>,
+) -> bool {
+    let start = Instant::now();
+    let service = state.service.lock().unwrap();
+
+    match service.launch_action(state.dbus.as_ref(), app_id, action, activation_token) {
+        Ok(_) => {
+            info!("took {:?}", start.elapsed());
+            true
+        }
+        Err(e) => {
+            warn!("error: {}, took {:?}", e, start.elapsed());
+            false
+        }
+    }
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn list_applications_for(state: State<AppState>, target: &str) -> Vec<DesktopEntry> {
+    let service = state.service.lock().unwrap();
+    service
+        .applications_for(target)
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn default_application_for(
+    state: State<AppState>,
+    target: &str,
+) -> Option<DesktopEntry> {
+    let service = state.service.lock().unwrap();
+    service.default_application_for(target).cloned()
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn open_with(
+    state: State<AppState>,
+    target: &str,
+    app_id: Option<&str>,
+    activation_token: Option<&str>,
+) -> bool {
     let start = Instant::now();
     let service = state.service.lock().unwrap();
 
-    match service.launch(app_id, None) {
+    match service.open_with_token(state.dbus.as_ref(), target, app_id, activation_token) {
+        Ok(_) => {
+            info!("took {:?}", start.elapsed());
+            true
+        }
+        Err(e) => {
+            warn!("error: {}, took {:?}", e, start.elapsed());
+            false
+        }
+    }
+}
+
+fn update_association(
+    state: State<AppState>,
+    mime: &str,
+    app_id: &str,
+    f: fn(&mut ApplicationService, &str, &str) -> std::io::Result<()>,
+) -> bool {
+    let start = Instant::now();
+    let mut service = state.service.lock().unwrap();
+
+    match f(&mut service, mime, app_id) {
+        Ok(_) => {
+            info!("took {:?}", start.elapsed());
+            true
+        }
+        Err(e) => {
+            warn!("error: {}, took {:?}", e, start.elapsed());
+            false
+        }
+    }
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn set_default_application(state: State<AppState>, mime: &str, app_id: &str) -> bool {
+    update_association(
+        state,
+        mime,
+        app_id,
+        ApplicationService::set_default_application,
+    )
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn add_association(state: State<AppState>, mime: &str, app_id: &str) -> bool {
+    update_association(state, mime, app_id, ApplicationService::add_association)
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn remove_association(state: State<AppState>, mime: &str, app_id: &str) -> bool {
+    update_association(state, mime, app_id, ApplicationService::remove_association)
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn update_application(
+    state: State<AppState>,
+    app_id: &str,
+    changes: EntryChanges,
+) -> bool {
+    let start = Instant::now();
+    let mut service = state.service.lock().unwrap();
+
+    match service.update_entry(app_id, &changes) {
+        Ok(_) => {
+            info!("took {:?}", start.elapsed());
+            true
+        }
+        Err(e) => {
+            warn!("error: {}, took {:?}", e, start.elapsed());
+            false
+        }
+    }
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn reset_application(state: State<AppState>, app_id: &str) -> bool {
+    let start = Instant::now();
+    let mut service = state.service.lock().unwrap();
+
+    match service.reset_entry(app_id) {
         Ok(_) => {
             info!("took {:?}", start.elapsed());
             true
@@ -109,6 +514,703 @@ pub(crate) fn launch_application(state: State<AppState>, app_id: &str) -> bool {
     }
 }
 
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn promote_custom_entry(state: State<AppState>, app_id: &str) -> Option<String> {
+    let mut service = state.service.lock().unwrap();
+
+    match service.promote_custom_entry(app_id) {
+        Ok(path) => Some(path.to_string_lossy().into_owned()),
+        Err(e) => {
+            warn!("error: {}", e);
+            None
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) fn get_config(state: State<AppState>) -> Config {
+    state.config.lock().unwrap().clone()
+}
+
+/// Persists `config` to the config file, keeping its comments, and applies
+/// it right away.
+#[tauri::command]
+#[instrument(skip(app, config))]
+pub(crate) fn set_config(app: AppHandle, config: Config) -> Result<(), ConfigError> {
+    config.privacy.privacy()?;
+    config.save(&Config::path())?;
+    apply_config(&app, config);
+    Ok(())
+}
+
+/// Switches the preferred locales at runtime and remembers them in the
+/// config file. An empty list goes back to the environment's locales.
+#[tauri::command]
+#[instrument(skip(app, state))]
+pub(crate) fn set_locales(
+    app: AppHandle,
+    state: State<AppState>,
+    locales: Vec<String>,
+) -> Result<(), ConfigError> {
+    let mut config = state.config.lock().unwrap().clone();
+    config.locales = locales;
+    config.save(&Config::path())?;
+    apply_config(&app, config);
+    Ok(())
+}
+
+/// Validates one application's desktop file, or every desktop file when
+/// `app_id` is `None`.
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn validate_applications(
+    state: State<AppState>,
+    app_id: Option<&str>,
+) -> Vec<validate::Issue> {
+    match app_id {
+        Some(app_id) => {
+            let service = state.service.lock().unwrap();
+            service.validate(app_id).unwrap_or_else(|e| {
+                warn!("error: {}", e);
+                Vec::new()
+            })
+        }
+        None => validate::validate_all(),
+    }
+}
+
+#[tauri::command]
+pub(crate) fn list_autostart() -> Vec<AutostartEntry> {
+    autostart::list()
+}
+
+#[tauri::command]
+#[instrument]
+pub(crate) fn set_autostart_enabled(id: &str, enabled: bool) -> bool {
+    match autostart::set_enabled(id, enabled) {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("error: {}", e);
+            false
+        }
+    }
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn add_autostart(state: State<AppState>, app_id: &str) -> bool {
+    let service = state.service.lock().unwrap();
+
+    match service.add_autostart(app_id) {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("error: {}", e);
+            false
+        }
+    }
+}
+
+#[tauri::command]
+#[instrument]
+pub(crate) fn remove_autostart(id: &str) -> bool {
+    match autostart::remove(id) {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("error: {}", e);
+            false
+        }
+    }
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn search_history(
+    state: State<AppState>,
+    query: Option<&str>,
+    limit: usize,
+) -> Vec<HistoryItem> {
+    let start = Instant::now();
+    let history = state.history.lock().unwrap();
+    let Some(history) = history.as_ref() else {
+        return Vec::new();
+    };
+
+    match history.search(query.unwrap_or_default(), limit) {
+        Ok(items) => {
+            info!("took {:?}", start.elapsed());
+            items
+        }
+        Err(e) => {
+            warn!("error: {}, took {:?}", e, start.elapsed());
+            Vec::new()
+        }
+    }
+}
+
+/// Puts a history item back on the clipboard with all its original
+/// targets, and moves it to the top. With a sync mode, the synced
+/// selection gets it too.
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn restore_history_item(state: State<AppState>, id: i64) -> bool {
+    let contents = match state
+        .history
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|h| h.contents(id))
+    {
+        Some(Ok(contents)) if !contents.is_empty() => contents,
+        Some(Err(e)) => {
+            warn!("error: {}", e);
+            return false;
+        }
+        _ => return false,
+    };
+    if !set_clipboard(&state, contents) {
+        return false;
+    }
+    if let Some(history) = state.history.lock().unwrap().as_ref() {
+        if let Err(e) = history.touch(id) {
+            warn!("error: {}", e);
+        }
+    }
+    true
+}
+
+/// Puts `contents` on the clipboard, and on the selection the config syncs
+/// with it.
+fn set_clipboard(state: &AppState, contents: Vec<(String, Vec<u8>)>) -> bool {
+    let sync = state.config.lock().unwrap().history.sync;
+    if let Some(target) = sync.target(Selection::Clipboard) {
+        if let Err(e) = clipboard::owner::spawn(target, contents.clone()) {
+            warn!("error: {}", e);
+        }
+    }
+    if let Err(e) = clipboard::owner::spawn(Selection::Clipboard, contents) {
+        warn!("error: {}", e);
+        return false;
+    }
+    forget_clipboard_source(state);
+    true
+}
+
+/// The text transforms work on, read once per selected item, and the
+/// previews computed for it so far.
+pub(crate) struct TransformSource {
+    /// The history item, or `None` for the current clipboard.
+    id: Option<i64>,
+    text: String,
+    /// Whether the text comes with rich targets.
+    rich: bool,
+    previews: HashMap<Transform, Option<String>>,
+}
+
+impl TransformSource {
+    /// Reads the history item `id`, or the current clipboard without one.
+    fn read(state: &AppState, id: Option<i64>) -> Option<Self> {
+        let item = match id {
+            Some(id) => state.history.lock().unwrap().as_ref()?.get(id).map(|item| {
+                item.map(|item| {
+                    let rich = item.mime_types.iter().any(|mime| mime != TEXT_MIME);
+                    (item.text, rich)
+                })
+            }),
+            None => clipboard::watcher::read_selection(Selection::Clipboard).map(|contents| {
+                let rich = contents.iter().any(|(mime, _)| mime != TEXT_MIME);
+                Some((clipboard::watcher::text_of(&contents), rich))
+            }),
+        };
+        match item {
+            Ok(Some((Some(text), rich))) => Some(Self {
+                id,
+                text,
+                rich,
+                previews: HashMap::new(),
+            }),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("error: {}", e);
+                None
+            }
+        }
+    }
+
+    /// What `transform` would produce, or `None` when it fails or changes
+    /// nothing.
+    fn preview(&mut self, transform: Transform) -> Option<String> {
+        self.previews
+            .entry(transform)
+            .or_insert_with(|| transform.preview(&self.text, self.rich))
+            .clone()
+    }
+}
+
+/// Runs `f` on the source for `id`, reading it unless it is the one read
+/// last.
+fn with_transform_source<T>(
+    state: &AppState,
+    id: Option<i64>,
+    f: impl FnOnce(&mut TransformSource) -> T,
+) -> Option<T> {
+    let mut cached = state.transform_source.lock().unwrap();
+    if cached.as_ref().map(|source| source.id) != Some(id) {
+        *cached = TransformSource::read(state, id);
+    }
+    cached.as_mut().map(f)
+}
+
+/// Drops the cached clipboard contents once the clipboard changes.
+fn forget_clipboard_source(state: &AppState) {
+    let mut cached = state.transform_source.lock().unwrap();
+    if cached.as_ref().is_some_and(|source| source.id.is_none()) {
+        *cached = None;
+    }
+}
+
+/// The transforms that apply to the history item `id`, or to the current
+/// clipboard without one, with a preview of each result.
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn search_transforms(
+    state: State<AppState>,
+    id: Option<i64>,
+    query: Option<&str>,
+) -> Vec<TransformResult> {
+    let query = query.unwrap_or_default().trim();
+    let matcher = SkimMatcherV2::default();
+
+    with_transform_source(&state, id, |source| {
+        rank(
+            Transform::ALL,
+            query,
+            |&transform| match_transform(&matcher, transform, query),
+            // Only transforms that match get a preview.
+            |transform, indices| {
+                Some(TransformResult {
+                    transform,
+                    title: transform.title(),
+                    preview: source.preview(transform)?,
+                    matched_indices: indices,
+                })
+            },
+        )
+    })
+    .unwrap_or_default()
+}
+
+/// Transforms the history item `id`, or the current clipboard without one,
+/// and puts the result on the clipboard. The history gets it as a new item,
+/// redacted, unless incognito mode is on.
+#[tauri::command]
+#[instrument(skip(app, state))]
+pub(crate) fn apply_transform(
+    app: AppHandle,
+    state: State<AppState>,
+    id: Option<i64>,
+    transform: Transform,
+) -> bool {
+    let Some(result) = with_transform_source(&state, id, |source| transform.apply(&source.text))
+    else {
+        return false;
+    };
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => {
+            warn!("error: {}", e);
+            return false;
+        }
+    };
+
+    let item = {
+        let privacy = state.privacy.read().unwrap();
+        (!privacy.incognito).then(|| privacy.apply(NewItem::text(result.clone(), None)))
+    };
+    if let Some(item) = item {
+        if let Some(history) = state.history.lock().unwrap().as_mut() {
+            if let Err(e) = history.add(&item) {
+                warn!("failed to store clipboard item: {}", e);
+            }
+        }
+    }
+
+    if !set_clipboard(&state, vec![(TEXT_MIME.to_string(), result.into_bytes())]) {
+        return false;
+    }
+    let _ = app.emit("history-changed", ());
+    true
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn search_snippets(state: State<AppState>, query: Option<&str>) -> Vec<SnippetResult> {
+    let query = query.unwrap_or_default().trim();
+    let matcher = SkimMatcherV2::default();
+    let snippets = state.snippets.lock().unwrap();
+
+    rank(
+        snippets.iter(),
+        query,
+        |parsed| match_snippet(&matcher, &parsed.snippet, query),
+        |parsed, indices| {
+            Some(SnippetResult {
+                snippet: parsed.snippet.clone(),
+                inputs: parsed.inputs.clone(),
+                matched_indices: indices,
+            })
+        },
+    )
+}
+
+/// Expands the snippet `name` with the user's answers to its prompts and
+/// puts the result on the clipboard. With `paste`, it is also pasted into
+/// the window that was active when shiori opened. Returns the expansion,
+/// whose cursor position tells where `{cursor}` was.
+#[tauri::command]
+#[instrument(skip(app, state, inputs))]
+pub(crate) fn expand_snippet(
+    app: AppHandle,
+    state: State<AppState>,
+    name: &str,
+    inputs: HashMap<String, String>,
+    paste: Option<bool>,
+) -> Option<Expansion> {
+    let template = {
+        let snippets = state.snippets.lock().unwrap();
+        snippets
+            .iter()
+            .find(|s| s.snippet.name == name)?
+            .template
+            .clone()
+    };
+
+    let clipboard = if template.uses_clipboard() {
+        match clipboard::watcher::read_selection(Selection::Clipboard) {
+            Ok(contents) => clipboard::watcher::text_of(&contents).unwrap_or_default(),
+            Err(e) => {
+                warn!("error: {}", e);
+                String::new()
+            }
+        }
+    } else {
+        String::new()
+    };
+    let expansion = match template.expand(&clipboard, &inputs) {
+        Ok(expansion) => expansion,
+        Err(e) => {
+            warn!("error: {}", e);
+            return None;
+        }
+    };
+
+    let contents = NewItem::text(expansion.text.clone(), None).contents;
+    if !set_clipboard(&state, contents) {
+        return None;
+    }
+    if paste.unwrap_or(false) {
+        paste_to_previous(&app, &state, Some(expansion.text.clone()), expansion.cursor);
+    }
+    Some(expansion)
+}
+
+/// Remembers the active window before shiori shows itself again.
+#[tauri::command]
+#[instrument(skip(app))]
+pub(crate) fn remember_active_window(app: AppHandle) {
+    record_active_window(&app);
+}
+
+/// Hides shiori and pastes the clipboard into the window that was active
+/// when it opened. `text` and `cursor` are the expansion of a snippet,
+/// for typing it and placing the cursor. Returns whether there was a
+/// window to paste into; pasting itself happens in the background.
+#[tauri::command]
+#[instrument(skip(app, state, text))]
+pub(crate) fn paste_to_previous_window(
+    app: AppHandle,
+    state: State<AppState>,
+    text: Option<String>,
+    cursor: Option<usize>,
+) -> bool {
+    paste_to_previous(&app, &state, text, cursor)
+}
+
+fn paste_to_previous(
+    app: &AppHandle,
+    state: &AppState,
+    text: Option<String>,
+    cursor: Option<usize>,
+) -> bool {
+    let config = state.config.lock().unwrap().paste.clone();
+    if !config.auto_paste {
+        return false;
+    }
+    let Some(window) = *state.previous_window.lock().unwrap() else {
+        return false;
+    };
+
+    if let Some(main) = app.get_webview_window("main") {
+        if let Err(e) = main.hide() {
+            warn!("failed to hide window: {}", e);
+        }
+    }
+    std::thread::spawn(move || {
+        if let Err(e) = paste_into(&config, window, text, cursor) {
+            warn!("failed to paste: {}", e);
+        }
+    });
+    true
+}
+
+/// The open windows other than shiori's, most recently used first, or
+/// the ones matching `query` by title or class.
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn search_windows(state: State<AppState>, query: Option<&str>) -> Vec<WindowResult> {
+    let start = Instant::now();
+    let windows = match x_window::window::list_windows() {
+        Ok(windows) => windows,
+        Err(e) => {
+            warn!("error: {}", e);
+            return Vec::new();
+        }
+    };
+    // Closed windows are forgotten.
+    let recent = {
+        let mut recent = state.recent_windows.lock().unwrap();
+        recent.retain(|id| windows.iter().any(|window| window.id == *id));
+        recent.clone()
+    };
+    let own_pid = std::process::id();
+    let windows = windows
+        .into_iter()
+        .filter(|window| window.pid != Some(own_pid))
+        .collect();
+
+    let results = rank_windows(
+        &SkimMatcherV2::default(),
+        windows,
+        &recent,
+        query.unwrap_or_default().trim(),
+    );
+    info!("took {:?}", start.elapsed());
+    results
+}
+
+/// Focuses a window, switching to its desktop when needed.
+#[tauri::command]
+#[instrument]
+pub(crate) fn activate_window(id: u32) -> bool {
+    match x_window::window::activate_window(id) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("error: {}", e);
+            false
+        }
+    }
+}
+
+/// What `window_action` does to a window.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum WindowAction {
+    Close,
+    Minimize,
+    Maximize,
+    Fullscreen,
+    AlwaysOnTop,
+    Sticky,
+    Shade,
+    /// Needs the `desktop` argument.
+    MoveToDesktop,
+}
+
+/// Closes, minimizes or moves a window, or toggles one of its states.
+#[tauri::command]
+#[instrument]
+pub(crate) fn window_action(id: u32, action: WindowAction, desktop: Option<u32>) -> bool {
+    let result = WindowManager::connect().and_then(|manager| match action {
+        WindowAction::Close => manager.close(id),
+        WindowAction::Minimize => manager.minimize(id),
+        WindowAction::Maximize => manager.toggle_maximized(id),
+        WindowAction::Fullscreen => manager.toggle(id, WindowState::Fullscreen),
+        WindowAction::AlwaysOnTop => manager.toggle(id, WindowState::Above),
+        WindowAction::Sticky => manager.toggle(id, WindowState::Sticky),
+        WindowAction::Shade => manager.toggle(id, WindowState::Shaded),
+        WindowAction::MoveToDesktop => match desktop {
+            Some(desktop) => manager.move_to_desktop(id, desktop),
+            None => Err(anyhow::anyhow!("no desktop to move to")),
+        },
+    });
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("error: {}", e);
+            false
+        }
+    }
+}
+
+/// The window's `_NET_WM_ICON`, as a data URL.
+#[tauri::command]
+#[instrument]
+pub(crate) fn read_window_icon(id: u32, size: u32) -> Option<String> {
+    match x_window::window::read_icon_png(id, size) {
+        Ok(png) => png.map(|png| {
+            format!(
+                "data:image/png;base64,{}",
+                general_purpose::STANDARD.encode(png)
+            )
+        }),
+        Err(e) => {
+            warn!("error: {}", e);
+            None
+        }
+    }
+}
+
+/// The thumbnail of an image item, as a data URL.
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn read_history_thumbnail(state: State<AppState>, id: i64) -> Option<String> {
+    let history = state.history.lock().unwrap();
+    match history.as_ref()?.thumbnail(id) {
+        Ok(thumbnail) => thumbnail.map(|png| {
+            format!(
+                "data:image/png;base64,{}",
+                general_purpose::STANDARD.encode(png)
+            )
+        }),
+        Err(e) => {
+            warn!("error: {}", e);
+            None
+        }
+    }
+}
+
+/// Marks an item sensitive, so it expires like copied secrets do, or
+/// makes it an ordinary item again.
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn set_history_item_sensitive(state: State<AppState>, id: i64, sensitive: bool) -> bool {
+    let ttl = sensitive.then(|| state.privacy.read().unwrap().sensitive_ttl);
+    let history = state.history.lock().unwrap();
+    match history.as_ref().map(|h| h.set_expiry(id, ttl)) {
+        Some(Ok(updated)) => updated,
+        Some(Err(e)) => {
+            warn!("error: {}", e);
+            false
+        }
+        None => false,
+    }
+}
+
+/// Pauses or resumes clipboard capture and emits `incognito-changed`.
+#[tauri::command]
+#[instrument(skip(app, state))]
+pub(crate) fn set_incognito(app: AppHandle, state: State<AppState>, enabled: bool) {
+    state.privacy.write().unwrap().incognito = enabled;
+    let _ = app.emit("incognito-changed", enabled);
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn is_incognito(state: State<AppState>) -> bool {
+    state.privacy.read().unwrap().incognito
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn remove_history_item(state: State<AppState>, id: i64) -> bool {
+    let history = state.history.lock().unwrap();
+    match history.as_ref().map(|h| h.remove(id)) {
+        Some(Ok(removed)) => removed,
+        Some(Err(e)) => {
+            warn!("error: {}", e);
+            false
+        }
+        None => false,
+    }
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn clear_history(state: State<AppState>) -> bool {
+    let history = state.history.lock().unwrap();
+    match history.as_ref().map(|h| h.clear()) {
+        Some(Ok(())) => true,
+        Some(Err(e)) => {
+            warn!("error: {}", e);
+            false
+        }
+        None => false,
+    }
+}
+
+#[tauri::command]
+#[instrument(skip(state))]
+pub(crate) fn is_history_locked(state: State<AppState>) -> bool {
+    state.history.lock().unwrap().is_none()
+}
+
+/// Opens a history that is locked by a passphrase and emits
+/// `history-changed` on success.
+#[tauri::command]
+#[instrument(skip(app, state, passphrase))]
+pub(crate) fn unlock_history(app: AppHandle, state: State<AppState>, passphrase: String) -> bool {
+    let mut history = state.history.lock().unwrap();
+    if history.is_some() {
+        return true;
+    }
+    let config = state.config.lock().unwrap().history.clone();
+    *history = open_history(&config, Some(&passphrase));
+    if history.is_none() {
+        return false;
+    }
+    let _ = app.emit("history-changed", ());
+    true
+}
+
+/// Re-encrypts the history: under `passphrase` when given, else with a
+/// fresh key kept where the config says. Switching to a passphrase deletes
+/// the stored key, which no longer opens anything, and remembers the
+/// switch in the config file.
+#[tauri::command]
+#[instrument(skip(state, passphrase))]
+pub(crate) fn rotate_history_key(state: State<AppState>, passphrase: Option<String>) -> bool {
+    let source = state.config.lock().unwrap().history.key_source();
+    let mut guard = state.history.lock().unwrap();
+    let Some(history) = guard.as_mut() else {
+        return false;
+    };
+
+    let Some(passphrase) = passphrase else {
+        return match history.rotate_key(&source) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("error: {}", e);
+                false
+            }
+        };
+    };
+    if let Err(e) = history.rekey(Key::passphrase(&passphrase), &KeySource::Passphrase) {
+        warn!("error: {}", e);
+        return false;
+    }
+    drop(guard);
+
+    if let Err(e) = source.delete() {
+        warn!("failed to delete the old history key: {}", e);
+    }
+    let mut config = state.config.lock().unwrap();
+    config.history.key = HistoryKey::Passphrase;
+    if let Err(e) = config.save(&Config::path()) {
+        warn!("error: {}", e);
+    }
+    true
+}
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 pub(crate) fn greet(name: &str) -> String {
@@ -0,0 +1,746 @@
+use application::custom::CustomEntry;
+use clipboard::history::{History, Retention};
+use clipboard::key::KeySource;
+use clipboard::privacy::{Privacy, Redaction};
+use clipboard::snippet::{ParsedSnippet, Snippet};
+use clipboard::watcher::{SyncMode, WatchOptions};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
+use tracing::warn;
+use x_window::paste::Keystroke;
+use x_window::window::WmClass;
+
+/// shiori's user configuration, read from `$XDG_CONFIG_HOME/shiori/config.toml`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) log_level: LogLevel,
+    /// Preferred locales for names and keywords, e.g. `["ja_JP", "en"]`.
+    /// Empty means the environment's (`$LANGUAGE`, `$LANG`, ...).
+    pub(crate) locales: Vec<String>,
+    pub(crate) window: WindowConfig,
+    pub(crate) search: SearchConfig,
+    pub(crate) history: HistoryConfig,
+    pub(crate) privacy: PrivacyConfig,
+    pub(crate) paste: PasteConfig,
+    pub(crate) custom_entries: Vec<CustomEntry>,
+    /// `[[snippets]]`, on top of the files in [`Config::snippets_dir`].
+    pub(crate) snippets: Vec<Snippet>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for tracing::level_filters::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => Self::ERROR,
+            LogLevel::Warn => Self::WARN,
+            LogLevel::Info => Self::INFO,
+            LogLevel::Debug => Self::DEBUG,
+            LogLevel::Trace => Self::TRACE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct WindowConfig {
+    pub(crate) width: NonZeroU32,
+    pub(crate) height: NonZeroU32,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: NonZeroU32::new(1200).unwrap(),
+            height: NonZeroU32::new(600).unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct SearchConfig {
+    /// Upper bound for the number of results a single query returns.
+    pub(crate) max_results: NonZeroUsize,
+    /// Whether `Keywords=` take part in matching, not only names.
+    pub(crate) match_keywords: bool,
+    /// Matches scoring below this are dropped; unset keeps every match.
+    /// Exact matches score above 1000, prefix matches above 500 and
+    /// acronyms above 300, while weak fuzzy matches on long names can score
+    /// below zero.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) min_score: Option<i64>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            max_results: NonZeroUsize::new(50).unwrap(),
+            match_keywords: true,
+            min_score: None,
+        }
+    }
+}
+
+/// Retention limits of the clipboard history; 0 disables a limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct HistoryConfig {
+    pub(crate) max_items: usize,
+    pub(crate) max_age_days: u64,
+    /// Total size of the stored contents, in bytes.
+    pub(crate) max_bytes: u64,
+    /// Own the clipboard after each copy so the content stays available
+    /// when the source app exits. Read at startup.
+    pub(crate) take_over: bool,
+    /// Record the mouse selection (PRIMARY) too. Read at startup.
+    pub(crate) primary: bool,
+    /// Share contents between PRIMARY and CLIPBOARD: `none`,
+    /// `primary-to-clipboard`, `clipboard-to-primary` or `both`. Read at
+    /// startup.
+    pub(crate) sync: SyncMode,
+    /// How long a mouse selection must stay unchanged before it is read,
+    /// in milliseconds.
+    pub(crate) primary_debounce_ms: u64,
+    /// Where the key that encrypts the history is kept. Read at startup.
+    pub(crate) key: HistoryKey,
+    /// Key file for `key = "file"`; defaults to
+    /// `$XDG_DATA_HOME/shiori/history.key`.
+    pub(crate) key_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HistoryKey {
+    /// The Secret Service; without one, a passphrase is asked for.
+    #[default]
+    Keyring,
+    File,
+    Passphrase,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        let retention = Retention::default();
+        Self {
+            max_items: retention.max_items.unwrap_or_default(),
+            max_age_days: retention
+                .max_age
+                .map(|age| age.as_secs() / (24 * 60 * 60))
+                .unwrap_or_default(),
+            max_bytes: retention.max_bytes.unwrap_or_default(),
+            take_over: false,
+            primary: false,
+            sync: SyncMode::default(),
+            primary_debounce_ms: WatchOptions::default().primary_debounce.as_millis() as u64,
+            key: HistoryKey::default(),
+            key_file: None,
+        }
+    }
+}
+
+impl HistoryConfig {
+    pub(crate) fn retention(&self) -> Retention {
+        Retention {
+            max_items: (self.max_items > 0).then_some(self.max_items),
+            max_age: (self.max_age_days > 0)
+                .then(|| Duration::from_secs(self.max_age_days * 24 * 60 * 60)),
+            max_bytes: (self.max_bytes > 0).then_some(self.max_bytes),
+        }
+    }
+
+    /// Watcher options, sharing `privacy` with the app.
+    pub(crate) fn watch_options(&self, privacy: Arc<RwLock<Privacy>>) -> WatchOptions {
+        WatchOptions {
+            primary: self.primary,
+            take_over: self.take_over,
+            sync: self.sync,
+            primary_debounce: Duration::from_millis(self.primary_debounce_ms),
+            privacy,
+            ..WatchOptions::default()
+        }
+    }
+
+    pub(crate) fn key_source(&self) -> KeySource {
+        match self.key {
+            HistoryKey::Keyring => KeySource::Keyring,
+            HistoryKey::File => {
+                KeySource::File(self.key_file.clone().unwrap_or_else(History::key_path))
+            }
+            HistoryKey::Passphrase => KeySource::Passphrase,
+        }
+    }
+}
+
+/// What the clipboard history leaves out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct PrivacyConfig {
+    /// `WM_CLASS` instance or class names of apps whose copies are never
+    /// captured.
+    pub(crate) exclude_classes: Vec<String>,
+    pub(crate) redact: Vec<RedactRule>,
+    /// How long items marked sensitive are kept, in seconds.
+    pub(crate) sensitive_ttl_secs: u64,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            exclude_classes: ["KeePassXC", "1Password", "Bitwarden", "Enpass"]
+                .map(String::from)
+                .to_vec(),
+            redact: Vec::new(),
+            sensitive_ttl_secs: Privacy::default().sensitive_ttl.as_secs(),
+        }
+    }
+}
+
+/// A `[[privacy.redact]]` rule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RedactRule {
+    /// Regular expression matched against the copied text.
+    pub(crate) pattern: String,
+    #[serde(default = "default_replacement")]
+    pub(crate) replacement: String,
+    /// Keep matching items unredacted, but remove them after
+    /// `sensitive_ttl_secs`.
+    #[serde(default)]
+    pub(crate) sensitive: bool,
+}
+
+fn default_replacement() -> String {
+    "[redacted]".to_string()
+}
+
+impl PrivacyConfig {
+    /// The watcher's privacy settings, with incognito mode off.
+    pub(crate) fn privacy(&self) -> Result<Privacy, ConfigError> {
+        let redactions = self
+            .redact
+            .iter()
+            .map(|rule| {
+                Redaction::new(&rule.pattern, &rule.replacement, rule.sensitive).map_err(|e| {
+                    ConfigError::new(format!("invalid pattern {:?}: {}", rule.pattern, e))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Privacy {
+            excluded_classes: self.exclude_classes.clone(),
+            redactions,
+            sensitive_ttl: Duration::from_secs(self.sensitive_ttl_secs),
+            incognito: false,
+        })
+    }
+}
+
+/// Pasting picked items into the window that was active when shiori
+/// opened.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct PasteConfig {
+    /// Send the paste keystroke after putting an item on the clipboard.
+    pub(crate) auto_paste: bool,
+    /// `WM_CLASS` instance or class names of terminals, which paste with
+    /// Ctrl+Shift+V.
+    pub(crate) terminal_classes: Vec<String>,
+    /// Apps that ignore the paste keystroke and get the text typed
+    /// instead.
+    pub(crate) type_classes: Vec<String>,
+}
+
+impl Default for PasteConfig {
+    fn default() -> Self {
+        Self {
+            auto_paste: true,
+            terminal_classes: [
+                "Alacritty",
+                "kitty",
+                "org.wezfurlong.wezterm",
+                "foot",
+                "Gnome-terminal",
+                "konsole",
+                "XTerm",
+                "URxvt",
+                "Tilix",
+                "Xfce4-terminal",
+                "Terminator",
+            ]
+            .map(String::from)
+            .to_vec(),
+            type_classes: Vec::new(),
+        }
+    }
+}
+
+impl PasteConfig {
+    /// How to paste into a window of `class`: a keystroke, or `None` to
+    /// type the text.
+    pub(crate) fn keystroke(&self, class: Option<&WmClass>) -> Option<Keystroke> {
+        let listed = |names: &[String]| {
+            class.is_some_and(|class| {
+                names.iter().any(|name| {
+                    name.eq_ignore_ascii_case(&class.instance)
+                        || name.eq_ignore_ascii_case(&class.class)
+                })
+            })
+        };
+        if listed(&self.type_classes) {
+            None
+        } else if listed(&self.terminal_classes) {
+            Some(Keystroke::CtrlShiftV)
+        } else {
+            Some(Keystroke::CtrlV)
+        }
+    }
+}
+
+/// A configuration problem, located in the file where possible.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ConfigError {
+    pub(crate) message: String,
+    pub(crate) line: Option<usize>,
+    pub(crate) column: Option<usize>,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}:{}: {}", line, column, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl ConfigError {
+    fn new(message: impl ToString) -> Self {
+        Self {
+            message: message.to_string(),
+            line: None,
+            column: None,
+        }
+    }
+
+    fn from_toml(content: &str, e: toml::de::Error) -> Self {
+        let (line, column) = match e.span() {
+            Some(span) => {
+                let (line, column) = line_column(content, span.start);
+                (Some(line), Some(column))
+            }
+            None => (None, None),
+        };
+        Self {
+            message: e.message().to_string(),
+            line,
+            column,
+        }
+    }
+}
+
+/// 1-based line and column of a byte offset.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+/// What shiori itself last wrote to the config file. The watcher ignores
+/// the file while it still holds this, so saving from the app does not
+/// apply the configuration a second time.
+static SAVED: Mutex<Option<String>> = Mutex::new(None);
+
+impl Config {
+    pub(crate) fn path() -> PathBuf {
+        application::xdg::config_home()
+            .join("shiori")
+            .join("config.toml")
+    }
+
+    pub(crate) fn parse(content: &str) -> Result<Self, ConfigError> {
+        let config: Self =
+            toml::from_str(content).map_err(|e| ConfigError::from_toml(content, e))?;
+        config.privacy.privacy()?;
+        let mut names = HashSet::new();
+        for snippet in &config.snippets {
+            snippet
+                .template()
+                .map_err(|e| ConfigError::new(format!("{:#}", e)))?;
+            if !names.insert(snippet.name.as_str()) {
+                return Err(ConfigError::new(format!(
+                    "duplicate snippet name {:?}",
+                    snippet.name
+                )));
+            }
+        }
+        let mut ids = HashSet::new();
+        for entry in &config.custom_entries {
+            let id = entry.id();
+            if id == application::custom::ID_PREFIX {
+                return Err(ConfigError::new(format!(
+                    "custom entry {:?} needs a name or id with letters or digits",
+                    entry.name
+                )));
+            }
+            if entry.command.trim().is_empty() {
+                return Err(ConfigError::new(format!(
+                    "custom entry {:?} has no command",
+                    entry.name
+                )));
+            }
+            if !ids.insert(id) {
+                return Err(ConfigError::new(format!(
+                    "duplicate custom entry id {:?}",
+                    entry.id()
+                )));
+            }
+        }
+        Ok(config)
+    }
+
+    /// `$XDG_CONFIG_HOME/shiori/snippets`, holding one snippet per
+    /// `*.toml` file.
+    pub(crate) fn snippets_dir() -> PathBuf {
+        application::xdg::config_home()
+            .join("shiori")
+            .join("snippets")
+    }
+
+    /// The configured snippets followed by those in the snippets
+    /// directory, parsed. Invalid snippets are skipped with a warning.
+    pub(crate) fn all_snippets(&self) -> Vec<ParsedSnippet> {
+        let from_dir = clipboard::snippet::load_dir(&Self::snippets_dir())
+            .into_iter()
+            .filter_map(|snippet| match snippet {
+                Ok(snippet) => Some(snippet),
+                Err(e) => {
+                    warn!("{:#}", e);
+                    None
+                }
+            });
+        parse_snippets(self.snippets.iter().cloned().chain(from_dir))
+    }
+
+    /// Reads the configuration at `path`; a missing file yields defaults.
+    pub(crate) fn read(path: &Path) -> Result<Self, ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ConfigError::new(e)),
+        }
+    }
+
+    /// Loads the configuration, falling back to defaults when the file is
+    /// invalid.
+    pub(crate) fn load() -> Self {
+        let path = Self::path();
+        Self::read(&path).unwrap_or_else(|e| {
+            warn!("invalid config {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    /// Writes the configuration back to `path`, keeping comments, ordering
+    /// and formatting of the keys that already exist in the file.
+    pub(crate) fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let existing = std::fs::read_to_string(path).unwrap_or_default();
+        let mut doc: DocumentMut = existing.parse().unwrap_or_default();
+
+        let updated: DocumentMut = toml::to_string(self)
+            .map_err(ConfigError::new)?
+            .parse()
+            .map_err(ConfigError::new)?;
+        merge_table(doc.as_table_mut(), updated.as_table());
+
+        let content = doc.to_string();
+        application::keyfile::write_atomic(path, content.as_bytes()).map_err(ConfigError::new)?;
+        *SAVED.lock().unwrap() = Some(content);
+        Ok(())
+    }
+}
+
+/// Whether the file at `path` still holds what [`Config::save`] last wrote.
+fn is_own_save(path: &Path) -> bool {
+    let saved = SAVED.lock().unwrap();
+    saved.is_some() && std::fs::read_to_string(path).ok() == *saved
+}
+
+fn merge_table(dst: &mut Table, src: &Table) {
+    let stale: Vec<String> = dst
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !src.contains_key(key))
+        .collect();
+    for key in stale {
+        dst.remove(&key);
+    }
+
+    for (key, item) in src.iter() {
+        if let Some(existing) = dst.get_mut(key) {
+            if let (Item::Table(d), Item::Table(s)) = (&mut *existing, item) {
+                merge_table(d, s);
+                continue;
+            }
+            if let (Item::ArrayOfTables(d), Item::ArrayOfTables(s)) = (&mut *existing, item) {
+                merge_array(d, s);
+                continue;
+            }
+            if let (Item::Value(d), Item::Value(s)) = (&mut *existing, item) {
+                let decor = d.decor().clone();
+                *d = s.clone();
+                *d.decor_mut() = decor;
+                continue;
+            }
+        }
+        dst.insert(key, item.clone());
+    }
+}
+
+/// Merges `[[custom_entries]]` and other arrays of tables entry by entry,
+/// so the comments of entries that keep their position survive.
+fn merge_array(dst: &mut ArrayOfTables, src: &ArrayOfTables) {
+    while dst.len() > src.len() {
+        dst.remove(dst.len() - 1);
+    }
+    for (i, table) in src.iter().enumerate() {
+        match dst.get_mut(i) {
+            Some(existing) => merge_table(existing, table),
+            None => dst.push(table.clone()),
+        }
+    }
+}
+
+/// Parses `snippets`. Names identify snippets, so one whose name is taken
+/// already is skipped with a warning, as are invalid ones.
+fn parse_snippets(snippets: impl Iterator<Item = Snippet>) -> Vec<ParsedSnippet> {
+    let mut parsed: Vec<ParsedSnippet> = Vec::new();
+    for snippet in snippets {
+        if parsed.iter().any(|p| p.snippet.name == snippet.name) {
+            warn!("snippet {:?} is defined twice, skipping one", snippet.name);
+            continue;
+        }
+        match snippet.parse() {
+            Ok(snippet) => parsed.push(snippet),
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+    parsed
+}
+
+/// Watches the configuration file and the snippets directory and calls
+/// `on_change` with the reloaded configuration whenever either changes.
+/// The parent directory is watched so editors that replace the file on
+/// save are picked up too. Changes written by [`Config::save`] are skipped.
+pub(crate) fn watch<F>(on_change: F) -> notify::Result<RecommendedWatcher>
+where
+    F: Fn(Result<Config, ConfigError>) + Send + 'static,
+{
+    let path = Config::path();
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    std::fs::create_dir_all(&dir)?;
+
+    let snippets = Config::snippets_dir();
+    std::fs::create_dir_all(&snippets)?;
+
+    let target = path.clone();
+    let snippets_dir = snippets.clone();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event)
+                if event
+                    .paths
+                    .iter()
+                    .any(|p| p == &target || p.starts_with(&snippets_dir)) =>
+            {
+                let changed =
+                    event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove();
+                let snippet = event.paths.iter().any(|p| p.starts_with(&snippets_dir));
+                if changed && (snippet || !is_own_save(&target)) {
+                    on_change(Config::read(&target));
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("config watcher error: {}", e),
+        })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    watcher.watch(&snippets, RecursiveMode::NonRecursive)?;
+
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("shiori-config-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn locates_parse_errors() {
+        let e = Config::parse("log_level = \"info\"\n\n[window]\nwidth = \"wide\"\n").unwrap_err();
+        assert_eq!((e.line, e.column), (Some(4), Some(9)));
+        assert!(e.to_string().starts_with("4:9: "), "{}", e);
+
+        let e = Config::parse("[search]\n  colour = 1\n").unwrap_err();
+        assert_eq!((e.line, e.column), (Some(2), Some(3)));
+
+        // Errors found after parsing have no location.
+        let e = Config::parse("[[privacy.redact]]\npattern = \"(\"\n").unwrap_err();
+        assert_eq!((e.line, e.column), (None, None));
+        assert!(e.message.starts_with("invalid pattern \"(\""), "{}", e);
+    }
+
+    #[test]
+    fn rejects_duplicate_snippet_names() {
+        let snippet = "[[snippets]]\nname = \"Sig\"\nbody = \"--\"\n";
+        let e = Config::parse(&snippet.repeat(2)).unwrap_err();
+        assert_eq!(e.message, "duplicate snippet name \"Sig\"");
+        assert_eq!(Config::parse(snippet).unwrap().snippets.len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_custom_entries() {
+        let entry = |name: &str, command: &str| {
+            format!(
+                "[[custom_entries]]\nname = {:?}\ncommand = {:?}\n",
+                name, command
+            )
+        };
+        let e = Config::parse(&(entry("Deploy", "make") + &entry("deploy!", "ls"))).unwrap_err();
+        assert_eq!(e.message, "duplicate custom entry id \"custom:deploy\"");
+        let e = Config::parse(&entry("--", "make")).unwrap_err();
+        assert_eq!(
+            e.message,
+            "custom entry \"--\" needs a name or id with letters or digits"
+        );
+        let e = Config::parse(&entry("Deploy", " ")).unwrap_err();
+        assert_eq!(e.message, "custom entry \"Deploy\" has no command");
+        let e = Config::parse(&(entry("Deploy", "make") + "comand = \"make\"\n")).unwrap_err();
+        assert!(
+            e.message.contains("unknown field `comand`"),
+            "{}",
+            e.message
+        );
+
+        let config = Config::parse(&(entry("Deploy", "make") + &entry("Build", "make"))).unwrap();
+        assert_eq!(config.custom_entries.len(), 2);
+    }
+
+    #[test]
+    fn parses_each_snippet_name_once() {
+        let snippet = |name: &str, body: &str| Snippet {
+            name: name.to_string(),
+            keyword: None,
+            body: body.to_string(),
+        };
+        let parsed = parse_snippets(
+            [
+                snippet("Greeting", "Hi {input:Name}"),
+                snippet("Broken", "{nope}"),
+                snippet("Greeting", "Hello"),
+                snippet("Date", "{date}"),
+            ]
+            .into_iter(),
+        );
+        let names: Vec<&str> = parsed.iter().map(|p| p.snippet.name.as_str()).collect();
+        assert_eq!(names, ["Greeting", "Date"]);
+        assert_eq!(parsed[0].snippet.body, "Hi {input:Name}");
+        assert_eq!(parsed[0].inputs, ["Name"]);
+    }
+
+    #[test]
+    fn line_column_counts_characters() {
+        assert_eq!(line_column("ab\ncdé f", 0), (1, 1));
+        assert_eq!(line_column("ab\ncdé f", 3), (2, 1));
+        assert_eq!(line_column("ab\ncdé f", 7), (2, 4));
+        assert_eq!(line_column("ab", 10), (1, 3));
+    }
+
+    #[test]
+    fn save_keeps_comments() {
+        let dir = scratch("comments");
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            concat!(
+                "# shiori\n",
+                "log_level = \"debug\" # for now\n",
+                "\n",
+                "[window]\n",
+                "# Wide enough for two columns.\n",
+                "width = 1400\n",
+                "\n",
+                "# Launchers not worth a desktop file.\n",
+                "[[custom_entries]]\n",
+                "# Staging only.\n",
+                "name = \"Deploy\"\n",
+                "command = \"make deploy\"\n",
+                "\n",
+                "[[custom_entries]]\n",
+                "name = \"Backup\"\n",
+                "command = \"restic backup\"\n",
+            ),
+        )
+        .unwrap();
+
+        let mut config = Config::read(&path).unwrap();
+        config.window.width = NonZeroU32::new(1600).unwrap();
+        config.custom_entries[0].command = "make deploy ENV=staging".to_string();
+        config.custom_entries.pop();
+        config.save(&path).unwrap();
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            saved.starts_with("# shiori\nlog_level = \"debug\" # for now\n"),
+            "{}",
+            saved
+        );
+        assert!(
+            saved.contains("# Wide enough for two columns.\nwidth = 1600\n"),
+            "{}",
+            saved
+        );
+        assert!(
+            saved.contains(concat!(
+                "# Launchers not worth a desktop file.\n",
+                "[[custom_entries]]\n",
+                "# Staging only.\n",
+                "name = \"Deploy\"\n",
+                "command = \"make deploy ENV=staging\"\n",
+            )),
+            "{}",
+            saved
+        );
+        assert!(!saved.contains("Backup"), "{}", saved);
+        assert_eq!(Config::parse(&saved).unwrap().custom_entries.len(), 1);
+
+        // The watcher skips the save until someone else edits the file.
+        assert!(is_own_save(&path));
+        std::fs::write(&path, saved.replace("1600", "1700")).unwrap();
+        assert!(!is_own_save(&path));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
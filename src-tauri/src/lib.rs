@@ -1,21 +1,89 @@
 use application::ApplicationService;
-use std::sync::Mutex;
-use tracing_subscriber::FmtSubscriber;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::level_filters::LevelFilter;
+use tracing::{info, info_span, warn};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{fmt, reload};
 
 use log;
-use tauri::Manager;
-use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri::webview::PageLoadEvent;
+use tauri::{Emitter, Manager};
 
 mod commands;
+mod config;
+mod search;
+
+/// How often expired sensitive clipboard items are removed.
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// `shiori validate [FILE...]`: validates the given desktop files, or every
+/// installed one, printing each issue. Returns the process exit code.
+pub fn validate(paths: &[String]) -> i32 {
+    let issues = if paths.is_empty() {
+        application::validate::validate_all()
+    } else {
+        paths
+            .iter()
+            .flat_map(|p| application::validate::validate_file(std::path::Path::new(p)))
+            .collect()
+    };
+
+    for issue in &issues {
+        println!("{}", issue);
+    }
+
+    let failed = issues
+        .iter()
+        .any(|i| i.severity == application::validate::Severity::Error);
+    if failed {
+        1
+    } else {
+        0
+    }
+}
+
+/// Loads entries from the on-disk cache when there is one, so the window can
+/// appear before every desktop file has been parsed.
+fn load_applications() -> ApplicationService {
+    if let Some(service) = ApplicationService::from_cache() {
+        return service;
+    }
+
+    let service = ApplicationService::default();
+    if let Err(e) = service.save_cache() {
+        warn!("failed to write entry cache: {}", e);
+    }
+    service
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(tracing::Level::TRACE)
-        .finish();
+    let startup = Instant::now();
+    let config = config::Config::load();
+
+    // The level is behind a reload layer so config changes apply without a
+    // restart.
+    let (level, log_level) = reload::Layer::new(LevelFilter::from(config.log_level));
+    let subscriber = tracing_subscriber::registry()
+        .with(level)
+        .with(fmt::layer());
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
+    let mut service = info_span!("load_applications").in_scope(load_applications);
+    service.set_custom_entries(config.custom_entries.clone());
+    info!("applications loaded at {:?}", startup.elapsed());
+
+    let history = commands::open_history(&config.history, None);
+
+    // One connection for every D-Bus activation.
+    let dbus = application::dbus::DBusLauncher::session()
+        .inspect_err(|e| warn!("failed to connect to the session bus: {}", e))
+        .ok();
+
+    let privacy = Arc::new(RwLock::new(config.privacy.privacy().unwrap_or_default()));
+
     tauri::Builder::default()
         .plugin(tauri_plugin_fs::init())
         .invoke_handler(tauri::generate_handler![
@@ -23,9 +91,57 @@ pub fn run() {
             commands::list_applications,
             commands::read_icon_as_base64,
             commands::launch_application,
+            commands::launch_application_action,
+            commands::list_applications_for,
+            commands::default_application_for,
+            commands::open_with,
+            commands::set_default_application,
+            commands::add_association,
+            commands::remove_association,
+            commands::update_application,
+            commands::reset_application,
+            commands::promote_custom_entry,
+            commands::get_config,
+            commands::set_config,
+            commands::set_locales,
+            commands::validate_applications,
+            commands::list_autostart,
+            commands::set_autostart_enabled,
+            commands::add_autostart,
+            commands::remove_autostart,
+            commands::search_history,
+            commands::restore_history_item,
+            commands::search_transforms,
+            commands::apply_transform,
+            commands::search_snippets,
+            commands::expand_snippet,
+            commands::remember_active_window,
+            commands::paste_to_previous_window,
+            commands::search_windows,
+            commands::activate_window,
+            commands::read_window_icon,
+            commands::window_action,
+            commands::read_history_thumbnail,
+            commands::set_history_item_sensitive,
+            commands::set_incognito,
+            commands::is_incognito,
+            commands::remove_history_item,
+            commands::clear_history,
+            commands::is_history_locked,
+            commands::unlock_history,
+            commands::rotate_history_key,
         ])
         .manage(commands::AppState {
-            service: Mutex::new(ApplicationService::default()),
+            service: Mutex::new(service),
+            dbus,
+            config: Mutex::new(config),
+            log_level,
+            history: Mutex::new(history),
+            privacy: privacy.clone(),
+            snippets: Mutex::new(Vec::new()),
+            previous_window: Mutex::new(None),
+            recent_windows: Mutex::new(Vec::new()),
+            transform_source: Mutex::new(None),
         })
         .plugin(
             tauri_plugin_log::Builder::new()
@@ -35,17 +151,83 @@ pub fn run() {
                 .level(log::LevelFilter::Info)
                 .build(),
         )
-        .setup(|app| {
-            // Read content from clipboard
-            let content = app.clipboard().read_text();
-            if let Ok(content) = content {
-                println!("{:?}", content);
-            } else {
-                println!(
-                    "Failed to read content from clipboard {}",
-                    content.unwrap_err()
-                );
+        .on_page_load(move |_, payload| {
+            if matches!(payload.event(), PageLoadEvent::Finished) {
+                info!("page loaded at {:?}", startup.elapsed());
+            }
+        })
+        .setup(move |app| {
+            info!("setup at {:?}", startup.elapsed());
+
+            // The window shows once the frontend has loaded, so whatever is
+            // active now is where picked items get pasted.
+            commands::record_active_window(app.handle());
+
+            let handle = app.handle().clone();
+            std::thread::spawn(move || commands::revalidate_applications(&handle));
+
+            // Forward clipboard changes to the frontend
+            let options = app
+                .state::<commands::AppState>()
+                .config
+                .lock()
+                .unwrap()
+                .history
+                .watch_options(privacy);
+            match clipboard::watcher::spawn(options) {
+                Ok(events) => {
+                    let handle = app.handle().clone();
+                    std::thread::spawn(move || {
+                        for event in events {
+                            commands::record_clipboard(&handle, &event);
+                        }
+                        warn!("clipboard watcher stopped");
+                    });
+                }
+                Err(e) => warn!("failed to watch the clipboard: {}", e),
             }
+
+            // The window switcher lists windows by when they were last used.
+            let handle = app.handle().clone();
+            std::thread::spawn(move || {
+                let result = x_window::window::watch_active_window(|window| {
+                    commands::record_window_activation(&handle, window)
+                });
+                if let Err(e) = result {
+                    warn!("failed to watch the active window: {}", e);
+                }
+            });
+
+            // Sensitive items go when they expire, not on the next copy.
+            let handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(EXPIRY_INTERVAL);
+                commands::expire_history(&handle);
+            });
+
+            let config = app
+                .state::<commands::AppState>()
+                .config
+                .lock()
+                .unwrap()
+                .clone();
+            commands::resize_window(app.handle(), &config.window);
+            commands::apply_config(app.handle(), config);
+
+            let handle = app.handle().clone();
+            match config::watch(move |result| match result {
+                Ok(config) => commands::apply_config(&handle, config),
+                Err(e) => {
+                    warn!("invalid config: {}", e);
+                    let _ = handle.emit("config-error", &e);
+                }
+            }) {
+                Ok(watcher) => {
+                    app.manage(Mutex::new(watcher));
+                }
+                Err(e) => warn!("failed to watch config: {}", e),
+            }
+
             // Prints "Tauri is awesome!" to the terminal
             Ok(())
         })
@@ -0,0 +1,568 @@
+use application::DesktopEntry;
+use clipboard::snippet::Snippet;
+use clipboard::transform::Transform;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::Serialize;
+use x_window::action::WindowState;
+use x_window::window::WindowInfo;
+
+/// A search hit, serialized as the entry plus the matched positions.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SearchResult {
+    #[serde(flatten)]
+    pub(crate) entry: DesktopEntry,
+    /// Char indices of the displayed name that matched the query, also when
+    /// it matched through a transliteration. Empty when the match came from
+    /// another field.
+    pub(crate) matched_indices: Vec<usize>,
+}
+
+impl SearchResult {
+    pub(crate) fn unmatched(entry: DesktopEntry) -> Self {
+        Self {
+            entry,
+            matched_indices: Vec::new(),
+        }
+    }
+}
+
+pub(crate) struct Match {
+    pub(crate) score: i64,
+    pub(crate) indices: Vec<usize>,
+}
+
+/// The query equals the whole text.
+const EXACT_BONUS: i64 = 1000;
+/// The text starts with the query.
+const PREFIX_BONUS: i64 = 500;
+/// On top of `PREFIX_BONUS` when the prefix is a whole word.
+const WORD_PREFIX_BONUS: i64 = 100;
+/// The query picks word starts only, e.g. `vsc` for "Visual Studio Code".
+const ACRONYM_BONUS: i64 = 300;
+/// For every other matched char on a word start.
+const WORD_START_BONUS: i64 = 20;
+
+/// Scores `query` against `text`: the fuzzy score, boosted for exact,
+/// prefix and acronym matches and for matches on word starts. Among equal
+/// matches, shorter texts score higher. Returns the matched char indices.
+pub(crate) fn score(matcher: &SkimMatcherV2, text: &str, query: &str) -> Option<(i64, Vec<usize>)> {
+    let (base, fuzzy_indices) = matcher.fuzzy_indices(text, query)?;
+    let text_chars: Vec<char> = text.chars().collect();
+    let query_chars: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    let starts = word_starts(&text_chars);
+    let length_penalty = text_chars.len() as i64;
+
+    let prefix_len = query.chars().count();
+    if prefix_len <= text_chars.len()
+        && text_chars
+            .iter()
+            .zip(query.chars())
+            .all(|(&t, q)| eq_ignore_case(t, q))
+    {
+        let indices = (0..prefix_len).collect();
+        if prefix_len == text_chars.len() {
+            return Some((base + EXACT_BONUS, indices));
+        }
+        let mut bonus = PREFIX_BONUS;
+        let next = text_chars[prefix_len];
+        if !next.is_alphanumeric() || starts.contains(&prefix_len) {
+            bonus += WORD_PREFIX_BONUS;
+        }
+        return Some((base + bonus - length_penalty, indices));
+    }
+
+    if let Some(indices) = acronym(&text_chars, &starts, &query_chars) {
+        return Some((base + ACRONYM_BONUS - length_penalty, indices));
+    }
+
+    let on_starts = fuzzy_indices.iter().filter(|i| starts.contains(i)).count() as i64;
+    Some((
+        base + on_starts * WORD_START_BONUS - length_penalty,
+        fuzzy_indices,
+    ))
+}
+
+/// Char indices that begin a word: the first char, chars after a separator
+/// and the upper-case char of a camelCase boundary.
+fn word_starts(chars: &[char]) -> Vec<usize> {
+    (0..chars.len())
+        .filter(|&i| {
+            let c = chars[i];
+            if !c.is_alphanumeric() {
+                return false;
+            }
+            match i.checked_sub(1).map(|p| chars[p]) {
+                None => true,
+                Some(prev) if !prev.is_alphanumeric() => true,
+                Some(prev) => prev.is_lowercase() && c.is_uppercase(),
+            }
+        })
+        .collect()
+}
+
+/// The better of a match on `title`, whose matched chars are highlighted,
+/// and the best match among `others`, scored through `weight`. The title
+/// wins ties.
+pub(crate) fn match_fields<'a>(
+    matcher: &SkimMatcherV2,
+    title: &str,
+    others: impl IntoIterator<Item = &'a str>,
+    query: &str,
+    weight: impl Fn(i64) -> i64,
+) -> Option<Match> {
+    let title = score(matcher, title, query).map(|(score, indices)| Match { score, indices });
+    let other = others
+        .into_iter()
+        .filter_map(|text| score(matcher, text, query))
+        .map(|(score, _)| weight(score))
+        .max()
+        .map(|score| Match {
+            score,
+            indices: Vec::new(),
+        });
+    match (title, other) {
+        (Some(title), Some(other)) if other.score > title.score => Some(other),
+        (title, other) => title.or(other),
+    }
+}
+
+/// The items that match `query`, best first, turned into results by
+/// `result`, which may drop some. An empty query matches every item.
+/// Items that score the same keep their order.
+pub(crate) fn rank<T, R>(
+    items: impl IntoIterator<Item = T>,
+    query: &str,
+    mut matches: impl FnMut(&T) -> Option<Match>,
+    mut result: impl FnMut(T, Vec<usize>) -> Option<R>,
+) -> Vec<R> {
+    let mut results: Vec<(i64, R)> = items
+        .into_iter()
+        .filter_map(|item| {
+            let matched = if query.is_empty() {
+                Match {
+                    score: 0,
+                    indices: Vec::new(),
+                }
+            } else {
+                matches(&item)?
+            };
+            Some((matched.score, result(item, matched.indices)?))
+        })
+        .collect();
+    results.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Matches every query char, in order, against word starts.
+fn acronym(chars: &[char], starts: &[usize], query: &[char]) -> Option<Vec<usize>> {
+    let mut starts = starts.iter();
+    query
+        .iter()
+        .map(|&q| starts.find(|&&i| eq_ignore_case(chars[i], q)).copied())
+        .collect()
+}
+
+fn eq_ignore_case(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+/// Matches `query` against the entry's name, its translations, their
+/// pinyin/romaji transliterations and, with `match_keywords`, its keywords.
+pub(crate) fn match_entry(
+    matcher: &SkimMatcherV2,
+    entry: &DesktopEntry,
+    query: &str,
+    match_keywords: bool,
+) -> Option<Match> {
+    let mut best: Option<Match> = None;
+    let mut consider = |score: i64, indices: Vec<usize>| {
+        if best.as_ref().is_none_or(|b| score > b.score) {
+            best = Some(Match { score, indices });
+        }
+    };
+
+    // Only positions in the displayed name can be highlighted.
+    if let Some((score, indices)) = score(matcher, &entry.name, query) {
+        consider(score, indices);
+    }
+    for form in entry.transliterations_of(&entry.name) {
+        if let Some((score, indices)) = score(matcher, &form.text, query) {
+            consider(score, form.source_indices(&indices));
+        }
+    }
+
+    for name in entry.names().skip(1) {
+        let best = std::iter::once(score(matcher, name, query))
+            .chain(
+                entry
+                    .transliterations_of(name)
+                    .iter()
+                    .map(|form| score(matcher, &form.text, query)),
+            )
+            .flatten()
+            .map(|(score, _)| score)
+            .max();
+        if let Some(best) = best {
+            consider(best, Vec::new());
+        }
+    }
+    // Keywords describe the application rather than name it, so they count
+    // for less.
+    if match_keywords {
+        for keyword in entry.all_keywords() {
+            if let Some((score, _)) = score(matcher, keyword, query) {
+                consider(score / 2, Vec::new());
+            }
+        }
+    }
+
+    best
+}
+
+/// A transform offered for a clipboard item, with what it would produce.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TransformResult {
+    pub(crate) transform: Transform,
+    pub(crate) title: &'static str,
+    pub(crate) preview: String,
+    /// Char indices of the title that matched the query.
+    pub(crate) matched_indices: Vec<usize>,
+}
+
+/// Matches `query` against the transform's title and, for less, its
+/// keywords.
+pub(crate) fn match_transform(
+    matcher: &SkimMatcherV2,
+    transform: Transform,
+    query: &str,
+) -> Option<Match> {
+    match_fields(
+        matcher,
+        transform.title(),
+        transform.keywords().iter().copied(),
+        query,
+        |score| score / 2,
+    )
+}
+
+/// A snippet hit, with the prompts to fill in before expanding it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SnippetResult {
+    #[serde(flatten)]
+    pub(crate) snippet: Snippet,
+    pub(crate) inputs: Vec<String>,
+    /// Char indices of the name that matched the query.
+    pub(crate) matched_indices: Vec<usize>,
+}
+
+/// Matches `query` against the snippet's name and keyword. Typing the
+/// whole keyword puts the snippet first.
+pub(crate) fn match_snippet(
+    matcher: &SkimMatcherV2,
+    snippet: &Snippet,
+    query: &str,
+) -> Option<Match> {
+    if let Some(keyword) = &snippet.keyword {
+        if keyword.eq_ignore_ascii_case(query.trim()) {
+            return Some(Match {
+                score: i64::MAX,
+                indices: Vec::new(),
+            });
+        }
+    }
+    match_fields(
+        matcher,
+        &snippet.name,
+        snippet.keyword.as_deref(),
+        query,
+        |score| score,
+    )
+}
+
+/// A window hit.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WindowResult {
+    pub(crate) id: u32,
+    pub(crate) title: String,
+    /// The `WM_CLASS` class, e.g. "firefox".
+    pub(crate) class: Option<String>,
+    pub(crate) pid: Option<u32>,
+    /// `None` for windows shown on every desktop.
+    pub(crate) desktop: Option<u32>,
+    pub(crate) urgent: bool,
+    pub(crate) minimized: bool,
+    /// Maximized in both directions.
+    pub(crate) maximized: bool,
+    pub(crate) fullscreen: bool,
+    /// Always on top.
+    pub(crate) above: bool,
+    pub(crate) sticky: bool,
+    pub(crate) shaded: bool,
+    /// Char indices of the title that matched the query.
+    pub(crate) matched_indices: Vec<usize>,
+}
+
+impl WindowResult {
+    pub(crate) fn new(window: WindowInfo, matched_indices: Vec<usize>) -> Self {
+        let has = |state| window.states.contains(&state);
+        Self {
+            id: window.id,
+            title: window.title,
+            class: window.class.map(|class| class.class),
+            pid: window.pid,
+            desktop: (window.desktop != x_window::window::ALL_DESKTOPS).then_some(window.desktop),
+            urgent: window.urgent,
+            minimized: window.minimized,
+            maximized: has(WindowState::MaximizedHorz) && has(WindowState::MaximizedVert),
+            fullscreen: has(WindowState::Fullscreen),
+            above: has(WindowState::Above),
+            sticky: has(WindowState::Sticky),
+            shaded: has(WindowState::Shaded),
+            matched_indices,
+        }
+    }
+}
+
+/// Matches `query` against the window's title and its `WM_CLASS` names.
+pub(crate) fn match_window(
+    matcher: &SkimMatcherV2,
+    window: &WindowInfo,
+    query: &str,
+) -> Option<Match> {
+    let classes = window
+        .class
+        .iter()
+        .flat_map(|class| [class.class.as_str(), class.instance.as_str()]);
+    match_fields(matcher, &window.title, classes, query, |score| score)
+}
+
+/// The windows that match `query`, best first. Windows that score the same
+/// follow `recent`, most recently active first, and the windows not seen
+/// active since startup follow in stacking order.
+pub(crate) fn rank_windows(
+    matcher: &SkimMatcherV2,
+    mut windows: Vec<WindowInfo>,
+    recent: &[u32],
+    query: &str,
+) -> Vec<WindowResult> {
+    // The sort is stable, so the rest keep their stacking order.
+    windows.sort_by_key(|window| {
+        recent
+            .iter()
+            .position(|&id| id == window.id)
+            .unwrap_or(recent.len())
+    });
+    rank(
+        windows,
+        query,
+        |window| match_window(matcher, window, query),
+        |window, indices| Some(WindowResult::new(window, indices)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x_window::window::WmClass;
+
+    const NAMES: &[&str] = &[
+        "Calculator",
+        "Calendar",
+        "Characters",
+        "Chromium",
+        "Clocks",
+        "Color Picker",
+        "Console",
+        "Contacts",
+        "Disk Usage Analyzer",
+        "Disks",
+        "File Roller",
+        "Files",
+        "Firefox",
+        "GIMP",
+        "GNOME Terminal",
+        "Google Chrome",
+        "Image Viewer",
+        "LibreOffice",
+        "LibreOffice Calc",
+        "LibreOffice Writer",
+        "Maps",
+        "Music",
+        "Nextcloud Desktop Synchronization Client Preferences",
+        "OBS Studio",
+        "Obsidian",
+        "Settings",
+        "Software",
+        "Spotify",
+        "System Monitor",
+        "Text Editor",
+        "Thunderbird",
+        "VLC media player",
+        "Videos",
+        "Visual Studio Code",
+        "Weather",
+    ];
+
+    /// Query and the name expected on top.
+    const CORPUS: &[(&str, &str)] = &[
+        ("vsc", "Visual Studio Code"),
+        ("code", "Visual Studio Code"),
+        ("lo", "LibreOffice"),
+        ("low", "LibreOffice Writer"),
+        ("calc", "Calculator"),
+        ("gimp", "GIMP"),
+        ("files", "Files"),
+        ("sm", "System Monitor"),
+        ("dua", "Disk Usage Analyzer"),
+        ("obs", "OBS Studio"),
+        ("chr", "Chromium"),
+        ("chrome", "Google Chrome"),
+        ("vlc", "VLC media player"),
+        ("term", "GNOME Terminal"),
+        ("ed", "Text Editor"),
+        ("set", "Settings"),
+        ("iv", "Image Viewer"),
+        ("fr", "File Roller"),
+        // A weak fuzzy match on a long name scores below zero but must
+        // still pass the default threshold.
+        (
+            "xtz",
+            "Nextcloud Desktop Synchronization Client Preferences",
+        ),
+    ];
+
+    fn top(query: &str) -> Option<&'static str> {
+        let matcher = SkimMatcherV2::default();
+        let min_score = crate::config::SearchConfig::default().min_score;
+        NAMES
+            .iter()
+            .filter_map(|name| Some((score(&matcher, name, query)?.0, *name)))
+            .filter(|(score, _)| min_score.is_none_or(|min| *score >= min))
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, name)| name)
+    }
+
+    #[test]
+    fn corpus() {
+        let failures: Vec<String> = CORPUS
+            .iter()
+            .filter(|(query, expected)| top(query) != Some(*expected))
+            .map(|(query, expected)| {
+                format!("{:?}: expected {:?}, got {:?}", query, expected, top(query))
+            })
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn word_starts_include_camel_case() {
+        let chars: Vec<char> = "LibreOffice Calc".chars().collect();
+        assert_eq!(word_starts(&chars), vec![0, 5, 12]);
+    }
+
+    #[test]
+    fn match_fields_weighs_other_fields() {
+        let matcher = SkimMatcherV2::default();
+        let full = match_fields(&matcher, "Encode", ["base64"], "base64", |score| score).unwrap();
+        let halved =
+            match_fields(&matcher, "Encode", ["base64"], "base64", |score| score / 2).unwrap();
+        assert_eq!(halved.score, full.score / 2);
+        assert!(full.indices.is_empty());
+
+        // The title wins ties and keeps its highlight.
+        let tie = match_fields(&matcher, "Sig", ["Sig"], "sig", |score| score).unwrap();
+        assert_eq!(tie.indices, vec![0, 1, 2]);
+        assert!(match_fields(&matcher, "Sig", None, "xyz", |score| score).is_none());
+    }
+
+    #[test]
+    fn rank_keeps_order_among_equal_scores() {
+        let matcher = SkimMatcherV2::default();
+        let items = ["Files", "Firefox", "Fonts", "Terminal"];
+        let ranked = |query: &str| {
+            rank(
+                items,
+                query,
+                |item| {
+                    score(&matcher, item, query).map(|(score, indices)| Match { score, indices })
+                },
+                // Results may drop items.
+                |item, _| (item != "Fonts").then_some(item),
+            )
+        };
+        assert_eq!(ranked(""), ["Files", "Firefox", "Terminal"]);
+        assert_eq!(ranked("fire"), ["Firefox"]);
+        assert_eq!(ranked("term"), ["Terminal"]);
+    }
+
+    fn window(id: u32, title: &str, class: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            desktop: 0,
+            title: title.to_string(),
+            class: Some(WmClass {
+                instance: class.to_lowercase(),
+                class: class.to_string(),
+            }),
+            pid: None,
+            urgent: false,
+            minimized: false,
+            states: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn match_window_prefers_the_better_field() {
+        let matcher = SkimMatcherV2::default();
+        let browser = window(1, "Rust Programming Language - Firefox", "Firefox");
+
+        let by_title = match_window(&matcher, &browser, "rust").unwrap();
+        assert_eq!(by_title.indices, vec![0, 1, 2, 3]);
+        // The class matches better, so no title chars are highlighted.
+        let by_class = match_window(&matcher, &browser, "firefox").unwrap();
+        assert!(by_class.score > by_title.score);
+        assert!(by_class.indices.is_empty());
+        let by_instance = match_window(&matcher, &window(2, "~", "Alacritty"), "alac").unwrap();
+        assert!(by_instance.indices.is_empty());
+
+        assert!(match_window(&matcher, &browser, "xyz").is_none());
+    }
+
+    #[test]
+    fn ranks_windows_by_recent_use() {
+        let matcher = SkimMatcherV2::default();
+        let windows = || {
+            vec![
+                window(1, "notes.txt - Editor", "Editor"),
+                window(2, "~/src - Terminal", "Terminal"),
+                window(3, "Inbox - Mail", "Mail"),
+                window(4, "draft.txt - Editor", "Editor"),
+            ]
+        };
+        let ids = |results: Vec<WindowResult>| results.iter().map(|r| r.id).collect::<Vec<_>>();
+
+        // Recently active first, then the rest in stacking order.
+        assert_eq!(
+            ids(rank_windows(&matcher, windows(), &[3, 1], "")),
+            [3, 1, 2, 4]
+        );
+        // Equal scores keep that order; others go by score.
+        assert_eq!(
+            ids(rank_windows(&matcher, windows(), &[4], "editor")),
+            [4, 1]
+        );
+        assert_eq!(ids(rank_windows(&matcher, windows(), &[2], "inbox")), [3]);
+    }
+
+    #[test]
+    fn acronym_indices() {
+        let matcher = SkimMatcherV2::default();
+        let (_, indices) = score(&matcher, "Visual Studio Code", "vsc").unwrap();
+        assert_eq!(indices, vec![0, 7, 14]);
+    }
+}
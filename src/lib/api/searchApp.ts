@@ -11,6 +11,7 @@ export interface DesktopEntry {
   exec: string | null;
   path: string;
   iconPath: string | null;
+  matchedIndices?: number[];
 }
 
 export async function listApplications(
@@ -28,3 +29,134 @@ export async function listApplications(
 export async function lanuchApplication(appId: string): Promise<boolean> {
   return await invoke("launch_application", { appId });
 }
+
+export type Transform =
+  | "base64-encode"
+  | "base64-decode"
+  | "url-encode"
+  | "url-decode"
+  | "json-pretty"
+  | "json-minify"
+  | "trim"
+  | "upper"
+  | "lower"
+  | "snake-case"
+  | "camel-case"
+  | "strip-formatting"
+  | "sort-lines"
+  | "unique-lines"
+  | "jwt-decode";
+
+export interface TransformResult {
+  transform: Transform;
+  title: string;
+  preview: string;
+  matchedIndices: number[];
+}
+
+export async function searchTransforms(
+  id: number | null,
+  query: string | null,
+): Promise<TransformResult[]> {
+  return await invoke<TransformResult[]>("search_transforms", { id, query });
+}
+
+export async function applyTransform(
+  id: number | null,
+  transform: Transform,
+): Promise<boolean> {
+  return await invoke("apply_transform", { id, transform });
+}
+
+export interface SnippetResult {
+  name: string;
+  keyword: string | null;
+  body: string;
+  inputs: string[];
+  matchedIndices: number[];
+}
+
+export interface Expansion {
+  text: string;
+  cursor: number | null;
+}
+
+export async function searchSnippets(
+  query: string | null,
+): Promise<SnippetResult[]> {
+  return await invoke<SnippetResult[]>("search_snippets", { query });
+}
+
+export async function expandSnippet(
+  name: string,
+  inputs: Record<string, string>,
+  paste: boolean = false,
+): Promise<Expansion | null> {
+  return await invoke<Expansion | null>("expand_snippet", {
+    name,
+    inputs,
+    paste,
+  });
+}
+
+export async function rememberActiveWindow(): Promise<void> {
+  await invoke("remember_active_window");
+}
+
+export async function pasteToPreviousWindow(
+  text: string | null = null,
+  cursor: number | null = null,
+): Promise<boolean> {
+  return await invoke("paste_to_previous_window", { text, cursor });
+}
+
+export interface WindowResult {
+  id: number;
+  title: string;
+  class: string | null;
+  pid: number | null;
+  desktop: number | null;
+  urgent: boolean;
+  minimized: boolean;
+  maximized: boolean;
+  fullscreen: boolean;
+  above: boolean;
+  sticky: boolean;
+  shaded: boolean;
+  matchedIndices: number[];
+}
+
+export type WindowAction =
+  | "close"
+  | "minimize"
+  | "maximize"
+  | "fullscreen"
+  | "always-on-top"
+  | "sticky"
+  | "shade"
+  | "move-to-desktop";
+
+export async function searchWindows(
+  query: string | null,
+): Promise<WindowResult[]> {
+  return await invoke<WindowResult[]>("search_windows", { query });
+}
+
+export async function activateWindow(id: number): Promise<boolean> {
+  return await invoke("activate_window", { id });
+}
+
+export async function windowAction(
+  id: number,
+  action: WindowAction,
+  desktop: number | null = null,
+): Promise<boolean> {
+  return await invoke("window_action", { id, action, desktop });
+}
+
+export async function readWindowIcon(
+  id: number,
+  size: number,
+): Promise<string | null> {
+  return await invoke<string | null>("read_window_icon", { id, size });
+}